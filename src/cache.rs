@@ -0,0 +1,612 @@
+//! Persistent per-file index cache under `.contextsmith/cache`.
+//!
+//! Each scanned file gets a [`CacheEntry`] keyed by its relative path and
+//! validated against the file's modification time, size and content hash.
+//! An entry stores everything derived from the file's text that commands
+//! need repeatedly: line offsets, the symbol outline, lexical term
+//! frequencies and token counts per model.
+//!
+//! Freshness is checked with a single `stat`: when mtime and size match,
+//! the entry is trusted without reading the file. When they differ, the
+//! file is read and hashed; an unchanged hash (e.g. after `touch`) keeps
+//! the derived data, anything else rebuilds it.
+//!
+//! Search paths use the stored terms as a prefilter: a file whose terms
+//! cannot contain a literal query is skipped without being read.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{CacheConfig, Config};
+use crate::error::{ContextSmithError, Result};
+use crate::lexical;
+use crate::scanner::ScannedFile;
+use crate::symbols::{self, SymbolInfo};
+use crate::tokens::TokenEstimator;
+
+/// File name of the index inside the cache directory.
+pub const INDEX_FILE: &str = "index.json";
+
+/// Bumped whenever the entry layout or the derived data changes.
+const CACHE_VERSION: u32 = 1;
+
+// ---------------------------------------------------------------------------
+// Public types
+// ---------------------------------------------------------------------------
+
+/// Cached, content-derived data for one file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub mtime_ns: u64,
+    /// File size in bytes.
+    pub size: u64,
+    /// FNV-1a hash of the file content.
+    pub hash: u64,
+    /// Byte offset of the start of each line.
+    pub line_offsets: Vec<usize>,
+    /// Definition outline, as produced by [`symbols::extract_symbols`].
+    pub symbols: Vec<SymbolInfo>,
+    /// Occurrences of each lexical term, as produced by [`lexical::tokenize`].
+    pub term_freqs: BTreeMap<String, u32>,
+    /// Token counts keyed by [`TokenEstimator::cache_key`], filled on demand.
+    #[serde(default)]
+    pub token_counts: BTreeMap<String, usize>,
+}
+
+/// Summary of the cache contents, for `cache stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Cache directory.
+    pub dir: PathBuf,
+    /// Number of cached files.
+    pub entries: usize,
+    /// Size of the index file on disk in bytes.
+    pub size_bytes: u64,
+    /// Entries whose file has changed or been removed since indexing.
+    pub stale: usize,
+}
+
+/// The per-repository index cache.
+///
+/// A disabled cache (`--no-cache` or `cache.enabled = false`) never
+/// touches the disk and never skips a file.
+#[derive(Debug)]
+pub struct IndexCache {
+    /// Cache directory, `None` when caching is disabled.
+    dir: Option<PathBuf>,
+    /// Entries keyed by path relative to the project root.
+    entries: BTreeMap<String, CacheEntry>,
+    /// Whether entries changed since loading.
+    dirty: bool,
+}
+
+/// On-disk layout of the index file.
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+// ---------------------------------------------------------------------------
+// Cache location
+// ---------------------------------------------------------------------------
+
+/// Default cache directory for a project root.
+pub fn default_dir(root: &Path) -> PathBuf {
+    root.join(".contextsmith").join("cache")
+}
+
+/// Apply the `--no-cache` and `--cache-dir` CLI overrides to the config.
+pub fn resolve_config(config: &Config, no_cache: bool, cache_dir: Option<&Path>) -> CacheConfig {
+    let mut cache = config.cache.clone();
+    if no_cache {
+        cache.enabled = false;
+    }
+    if let Some(dir) = cache_dir {
+        cache.dir = Some(dir.to_path_buf());
+    }
+    cache
+}
+
+/// Cache directory for `root`: `cache.dir` (relative to the root) or the default.
+pub fn cache_dir(root: &Path, config: &CacheConfig) -> PathBuf {
+    match config.dir {
+        Some(ref dir) if dir.is_absolute() => dir.clone(),
+        Some(ref dir) => root.join(dir),
+        None => default_dir(root),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// IndexCache
+// ---------------------------------------------------------------------------
+
+impl IndexCache {
+    /// Open the cache for `root`, loading any existing index.
+    ///
+    /// A missing, unreadable or outdated index starts an empty cache
+    /// rather than failing the command.
+    pub fn open(root: &Path, config: &CacheConfig) -> Self {
+        if !config.enabled {
+            return Self::disabled();
+        }
+        let dir = cache_dir(root, config);
+        let entries = match read_index(&dir.join(INDEX_FILE)) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::debug!("starting with an empty index cache: {e}");
+                BTreeMap::new()
+            }
+        };
+        Self {
+            dir: Some(dir),
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// A cache that keeps nothing between runs and never skips files.
+    pub fn disabled() -> Self {
+        Self {
+            dir: None,
+            entries: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+
+    /// Whether the cache is persisted.
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry for `file` if it is still valid, without reading the file.
+    ///
+    /// Returns `None` for a disabled cache, an unknown file, or a file
+    /// whose mtime or size has changed.
+    pub fn fresh(&self, file: &ScannedFile) -> Option<&CacheEntry> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let entry = self.entries.get(&file.rel_path)?;
+        let (mtime_ns, size) = stamp(&file.abs_path)?;
+        (entry.mtime_ns == mtime_ns && entry.size == size).then_some(entry)
+    }
+
+    /// Whether `file` could contain `literal`, judged from cached terms.
+    ///
+    /// Returns `true` whenever the answer is unknown (no fresh entry), so
+    /// callers only ever skip files that certainly do not match.
+    pub fn may_contain(&self, file: &ScannedFile, literal: &str) -> bool {
+        self.fresh(file)
+            .is_none_or(|entry| entry.may_contain(literal))
+    }
+
+    /// Record `content` as the current text of `file`.
+    ///
+    /// Keeps the derived data when the content hash is unchanged. Does
+    /// nothing for a disabled cache.
+    pub fn update(&mut self, file: &ScannedFile, content: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.upsert(file, content);
+    }
+
+    /// The entry for `file`, reading and indexing it when stale.
+    ///
+    /// Returns `None` when the file cannot be read as text. A disabled
+    /// cache still indexes the file, but only for the current run.
+    pub fn load(&mut self, file: &ScannedFile) -> Option<&CacheEntry> {
+        if self.fresh(file).is_none() {
+            let content = std::fs::read_to_string(&file.abs_path).ok()?;
+            self.upsert(file, &content);
+        }
+        self.entries.get(&file.rel_path)
+    }
+
+    /// Token count of `file` for `estimator`, cached per model.
+    pub fn token_count(
+        &mut self,
+        file: &ScannedFile,
+        estimator: &dyn TokenEstimator,
+    ) -> Option<usize> {
+        let model = estimator.cache_key();
+        if !self.is_enabled() {
+            let content = std::fs::read_to_string(&file.abs_path).ok()?;
+            return Some(estimator.estimate(&content));
+        }
+        if let Some(count) = self
+            .fresh(file)
+            .and_then(|entry| entry.token_counts.get(&model))
+        {
+            return Some(*count);
+        }
+
+        let content = std::fs::read_to_string(&file.abs_path).ok()?;
+        let count = estimator.estimate(&content);
+        self.upsert(file, &content);
+        if let Some(entry) = self.entries.get_mut(&file.rel_path) {
+            entry.token_counts.insert(model, count);
+        }
+        Some(count)
+    }
+
+    /// Drop entries for files that are no longer in `files`.
+    ///
+    /// Only call this with the result of an unfiltered scan, or entries
+    /// for filtered-out files are lost.
+    pub fn prune(&mut self, files: &[ScannedFile]) {
+        let before = self.entries.len();
+        let keep: std::collections::HashSet<&str> =
+            files.iter().map(|f| f.rel_path.as_str()).collect();
+        self.entries.retain(|path, _| keep.contains(path.as_str()));
+        if self.entries.len() != before {
+            self.dirty = true;
+        }
+    }
+
+    /// Write the index back to disk if anything changed.
+    pub fn save(&mut self) -> Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) if self.dirty => dir,
+            _ => return Ok(()),
+        };
+        std::fs::create_dir_all(dir).map_err(|e| {
+            ContextSmithError::io(format!("creating directory '{}'", dir.display()), e)
+        })?;
+        // Keep the cache out of `git status` for the repository it lives in.
+        let ignore = dir.join(".gitignore");
+        if !ignore.exists() {
+            std::fs::write(&ignore, "*\n")
+                .map_err(|e| ContextSmithError::io(format!("writing '{}'", ignore.display()), e))?;
+        }
+
+        let index = IndexFile {
+            version: CACHE_VERSION,
+            entries: std::mem::take(&mut self.entries),
+        };
+        let json = serde_json::to_string(&index);
+        self.entries = index.entries;
+        let json = json.map_err(|e| {
+            ContextSmithError::config_with_source("failed to serialize index cache", e)
+        })?;
+
+        // Write to a temporary file first so a crash never leaves a
+        // truncated index behind.
+        let path = dir.join(INDEX_FILE);
+        let tmp = dir.join(format!("{INDEX_FILE}.tmp"));
+        std::fs::write(&tmp, json)
+            .map_err(|e| ContextSmithError::io(format!("writing '{}'", tmp.display()), e))?;
+        std::fs::rename(&tmp, &path)
+            .map_err(|e| ContextSmithError::io(format!("writing '{}'", path.display()), e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Insert or refresh the entry for `file` from its content.
+    fn upsert(&mut self, file: &ScannedFile, content: &str) {
+        let (mtime_ns, size) = stamp(&file.abs_path).unwrap_or((0, content.len() as u64));
+        let hash = content_hash(content.as_bytes());
+
+        if let Some(entry) = self.entries.get_mut(&file.rel_path) {
+            if entry.hash == hash {
+                if entry.mtime_ns != mtime_ns || entry.size != size {
+                    entry.mtime_ns = mtime_ns;
+                    entry.size = size;
+                    self.dirty = true;
+                }
+                return;
+            }
+        }
+
+        self.entries.insert(
+            file.rel_path.clone(),
+            CacheEntry::build(content, mtime_ns, size, hash),
+        );
+        self.dirty = true;
+    }
+}
+
+/// Delete the index in the cache directory for `root`.
+///
+/// Returns the number of entries that were removed.
+pub fn clear(root: &Path, config: &CacheConfig) -> Result<usize> {
+    let path = cache_dir(root, config).join(INDEX_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let removed = read_index(&path).map(|e| e.len()).unwrap_or(0);
+    std::fs::remove_file(&path)
+        .map_err(|e| ContextSmithError::io(format!("removing '{}'", path.display()), e))?;
+    Ok(removed)
+}
+
+/// Summarise the cache for `root`, checking each entry for staleness.
+pub fn stats(root: &Path, config: &CacheConfig) -> CacheStats {
+    let dir = cache_dir(root, config);
+    let path = dir.join(INDEX_FILE);
+    let entries = read_index(&path).unwrap_or_default();
+    let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let stale = entries
+        .iter()
+        .filter(|(rel, entry)| {
+            stamp(&root.join(rel)).is_none_or(|s| s != (entry.mtime_ns, entry.size))
+        })
+        .count();
+
+    CacheStats {
+        dir,
+        entries: entries.len(),
+        size_bytes,
+        stale,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CacheEntry
+// ---------------------------------------------------------------------------
+
+impl CacheEntry {
+    /// Derive an entry from file content.
+    pub fn build(content: &str, mtime_ns: u64, size: u64, hash: u64) -> Self {
+        let mut line_offsets = vec![0];
+        line_offsets.extend(
+            content
+                .match_indices('\n')
+                .map(|(i, _)| i + 1)
+                .filter(|&i| i < content.len()),
+        );
+        if content.is_empty() {
+            line_offsets.clear();
+        }
+
+        let mut term_freqs = BTreeMap::new();
+        for term in lexical::tokenize(content) {
+            *term_freqs.entry(term).or_insert(0) += 1;
+        }
+
+        Self {
+            mtime_ns,
+            size,
+            hash,
+            line_offsets,
+            symbols: symbols::extract_symbols(content),
+            term_freqs,
+            token_counts: BTreeMap::new(),
+        }
+    }
+
+    /// Number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_offsets.len()
+    }
+
+    /// Byte range of line `line` (1-based), including its line terminator.
+    ///
+    /// Lets callers read a line span with a seek instead of loading the
+    /// whole file.
+    pub fn line_range(&self, line: usize) -> Option<std::ops::Range<usize>> {
+        let start = *self.line_offsets.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_offsets
+            .get(line)
+            .map_or(self.size as usize, |&next| next);
+        Some(start..end)
+    }
+
+    /// Whether the file could contain `literal` as a substring.
+    ///
+    /// Every maximal alphanumeric run of the file is kept (lowercased)
+    /// among its terms, so each alphanumeric piece of the literal must be
+    /// a substring of some term. Pieces shorter than the tokenizer's
+    /// minimum term length are not checked.
+    pub fn may_contain(&self, literal: &str) -> bool {
+        literal
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|piece| piece.len() >= 2)
+            .all(|piece| {
+                let piece = piece.to_lowercase();
+                self.term_freqs.keys().any(|term| term.contains(&piece))
+            })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Modification time (ns since epoch) and size of a file.
+fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((mtime.as_nanos() as u64, meta.len()))
+}
+
+/// 64-bit FNV-1a hash of `bytes`.
+fn content_hash(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes
+        .iter()
+        .fold(OFFSET, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(PRIME))
+}
+
+/// Read an index file, rejecting other cache versions.
+fn read_index(path: &Path) -> Result<BTreeMap<String, CacheEntry>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ContextSmithError::io(format!("reading '{}'", path.display()), e))?;
+    let index: IndexFile = serde_json::from_str(&content).map_err(|e| {
+        ContextSmithError::config_with_source(
+            format!("invalid index cache '{}'", path.display()),
+            e,
+        )
+    })?;
+    if index.version != CACHE_VERSION {
+        return Err(ContextSmithError::config(format!(
+            "index cache '{}' has version {}, expected {CACHE_VERSION}",
+            path.display(),
+            index.version
+        )));
+    }
+    Ok(index.entries)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::CharEstimator;
+
+    fn scanned(dir: &Path, name: &str, content: &str) -> ScannedFile {
+        let abs_path = dir.join(name);
+        std::fs::write(&abs_path, content).unwrap();
+        ScannedFile {
+            rel_path: name.to_string(),
+            abs_path,
+            language: "rust".to_string(),
+            is_generated: false,
+            size: content.len() as u64,
+        }
+    }
+
+    fn enabled() -> CacheConfig {
+        CacheConfig {
+            enabled: true,
+            dir: None,
+        }
+    }
+
+    #[test]
+    fn entry_records_lines_symbols_and_terms() {
+        let content = "fn parse_config() {}\nstruct TokenBudget;\n";
+        let entry = CacheEntry::build(content, 0, content.len() as u64, 0);
+        assert_eq!(entry.line_count(), 2);
+        assert_eq!(
+            &content[entry.line_range(1).unwrap()],
+            "fn parse_config() {}\n"
+        );
+        assert_eq!(
+            &content[entry.line_range(2).unwrap()],
+            "struct TokenBudget;\n"
+        );
+        assert!(entry.line_range(3).is_none());
+        assert_eq!(entry.symbols.len(), 2);
+        assert_eq!(entry.term_freqs["budget"], 1);
+        assert_eq!(entry.term_freqs["tokenbudget"], 1);
+    }
+
+    #[test]
+    fn may_contain_never_rejects_a_real_match() {
+        let entry = CacheEntry::build("let retry_backoff = parseHTTPResponse(x);", 0, 0, 0);
+        assert!(entry.may_contain("retry_backoff"));
+        assert!(entry.may_contain("HTTPResp"));
+        assert!(entry.may_contain("ry_ba"));
+        assert!(entry.may_contain("x"));
+        assert!(!entry.may_contain("timeout"));
+    }
+
+    #[test]
+    fn cache_persists_and_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = scanned(dir.path(), "a.rs", "fn alpha() {}\n");
+
+        let mut cache = IndexCache::open(dir.path(), &enabled());
+        assert!(cache.fresh(&file).is_none());
+        assert_eq!(cache.load(&file).unwrap().symbols[0].name, "alpha");
+        cache.save().unwrap();
+        assert!(default_dir(dir.path()).join(INDEX_FILE).exists());
+        let ignore = std::fs::read_to_string(default_dir(dir.path()).join(".gitignore")).unwrap();
+        assert_eq!(ignore, "*\n");
+
+        let reopened = IndexCache::open(dir.path(), &enabled());
+        assert_eq!(reopened.len(), 1);
+        assert!(reopened.fresh(&file).is_some());
+        assert!(!reopened.may_contain(&file, "beta"));
+
+        // A change in size invalidates the entry.
+        let file = scanned(dir.path(), "a.rs", "fn beta_version() {}\n");
+        assert!(reopened.fresh(&file).is_none());
+        assert!(reopened.may_contain(&file, "beta"));
+    }
+
+    #[test]
+    fn token_counts_are_cached_per_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = scanned(dir.path(), "a.rs", "fn alpha() { let x = 1; }\n");
+        let estimator = CharEstimator::new(crate::tokens::ModelFamily::Gpt4);
+
+        let mut cache = IndexCache::open(dir.path(), &enabled());
+        let count = cache.token_count(&file, &estimator).unwrap();
+        let entry = cache.fresh(&file).unwrap();
+        assert_eq!(entry.token_counts[&estimator.cache_key()], count);
+    }
+
+    #[test]
+    fn disabled_cache_never_persists_or_skips() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = scanned(dir.path(), "a.rs", "fn alpha() {}\n");
+        let config = CacheConfig {
+            enabled: false,
+            dir: None,
+        };
+
+        let mut cache = IndexCache::open(dir.path(), &config);
+        assert!(!cache.is_enabled());
+        cache.load(&file);
+        assert!(cache.fresh(&file).is_none());
+        assert!(cache.may_contain(&file, "zzz"));
+        cache.save().unwrap();
+        assert!(!default_dir(dir.path()).exists());
+    }
+
+    #[test]
+    fn prune_clear_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = scanned(dir.path(), "a.rs", "fn alpha() {}\n");
+        let b = scanned(dir.path(), "b.rs", "fn beta() {}\n");
+
+        let mut cache = IndexCache::open(dir.path(), &enabled());
+        cache.load(&a);
+        cache.load(&b);
+        cache.prune(std::slice::from_ref(&a));
+        cache.save().unwrap();
+
+        let stats = stats(dir.path(), &enabled());
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.stale, 0);
+        assert!(stats.size_bytes > 0);
+
+        assert_eq!(clear(dir.path(), &enabled()).unwrap(), 1);
+        assert_eq!(stats_entries(dir.path()), 0);
+    }
+
+    fn stats_entries(root: &Path) -> usize {
+        stats(root, &enabled()).entries
+    }
+
+    #[test]
+    fn cache_dir_honours_config() {
+        let root = Path::new("/repo");
+        assert_eq!(cache_dir(root, &enabled()), default_dir(root));
+        let config = resolve_config(&Config::default(), true, Some(Path::new("tmp/cache")));
+        assert!(!config.enabled);
+        assert_eq!(cache_dir(root, &config), Path::new("/repo/tmp/cache"));
+    }
+}
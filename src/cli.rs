@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use clap::{Parser, Subcommand, ValueEnum};
 
@@ -86,8 +87,17 @@ pub enum Command {
     #[command(alias = "d")]
     Diff {
         /// Git revision range (e.g. HEAD~3..HEAD)
+        #[arg(conflicts_with = "base")]
         rev_range: Option<String>,
 
+        /// Diff from the merge-base of HEAD and this branch (e.g. main)
+        #[arg(long, value_name = "BRANCH", conflicts_with = "since")]
+        base: Option<String>,
+
+        /// With --base: include staged and unstaged work on top of HEAD
+        #[arg(long, requires = "base")]
+        worktree: bool,
+
         /// Include staged changes
         #[arg(long)]
         staged: bool,
@@ -108,6 +118,22 @@ pub enum Command {
         #[arg(long, default_value = "3")]
         context: usize,
 
+        /// Grow each hunk to its enclosing function/method (like git diff -W)
+        #[arg(long, conflicts_with = "hunks_only")]
+        function_context: bool,
+
+        /// Pair each changed region with its pre-change version
+        #[arg(long, conflicts_with = "hunks_only")]
+        show_old: bool,
+
+        /// Add a commit-message section per commit in the range
+        #[arg(long)]
+        commits: bool,
+
+        /// With --commits: place each snippet under the commit that introduced it
+        #[arg(long, requires = "commits")]
+        group_by_commit: bool,
+
         /// Include related symbols (callers, tests)
         #[arg(long)]
         include_related: bool,
@@ -127,15 +153,19 @@ pub enum Command {
         /// Token budget
         #[arg(long)]
         budget: Option<usize>,
+
+        /// Model name for tokenization
+        #[arg(long)]
+        model: Option<String>,
     },
 
     /// Collect context by query
     #[command(alias = "c")]
     Collect {
-        /// Free-text query or symbol name
+        /// Free-text query, split into stemmed terms (use --grep for a regex)
         query: Option<String>,
 
-        /// Scope: file, directory, or module
+        /// Limit to a file, directory or module path (e.g. crate::commands::pack)
         #[arg(long)]
         scope: Option<String>,
 
@@ -159,19 +189,27 @@ pub enum Command {
         #[arg(long)]
         path: Option<String>,
 
-        /// Include git diff context
-        #[arg(long)]
+        /// Boost sections changed in this git revision range (e.g. main..HEAD)
+        #[arg(long, value_name = "RANGE")]
         diff: Option<String>,
 
+        /// Keep only sections that overlap the --diff changes
+        #[arg(long, requires = "diff")]
+        diff_only: bool,
+
         /// Search by content pattern (grep)
         #[arg(long)]
         grep: Option<String>,
 
-        /// Line span (e.g. "10:50")
+        /// Line span, repeatable: START:END for every --files entry, or PATH:START-END
         #[arg(long)]
-        span: Option<String>,
+        span: Vec<String>,
 
-        /// Max snippets per file
+        /// Context around matches: a line count, or "unit" for the enclosing function/class/block
+        #[arg(long, value_name = "LINES|unit", default_value = "3")]
+        context: MatchContext,
+
+        /// Max grep/symbol windows per file (densest kept)
         #[arg(long)]
         max_snippets: Option<usize>,
 
@@ -195,7 +233,7 @@ pub enum Command {
         #[arg(long)]
         tests: bool,
 
-        /// Ranking strategy
+        /// Ranking strategy: weighted, bm25, recency-first, diff-first, path-order
         #[arg(long)]
         rank: Option<String>,
 
@@ -214,6 +252,10 @@ pub enum Command {
         /// Token budget
         #[arg(long)]
         budget: Option<usize>,
+
+        /// Model name for tokenization
+        #[arg(long)]
+        model: Option<String>,
     },
 
     /// Pack collected context into a token-budgeted bundle
@@ -372,6 +414,20 @@ pub enum Command {
         #[arg(long)]
         show_weights: bool,
     },
+
+    /// Inspect or clear the on-disk index cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheAction {
+    /// Show cache location, size and stale entries
+    Stats,
+    /// Delete the cached index
+    Clear,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -381,3 +437,25 @@ pub enum OutputFormat {
     Xml,
     Plain,
 }
+
+/// How much code `collect` takes around each match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchContext {
+    /// A fixed number of lines above and below.
+    Lines(usize),
+    /// The enclosing function, impl/class or block.
+    Unit,
+}
+
+impl FromStr for MatchContext {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("unit") {
+            return Ok(Self::Unit);
+        }
+        s.parse()
+            .map(Self::Lines)
+            .map_err(|_| format!("expected a line count or 'unit', got '{s}'"))
+    }
+}
@@ -0,0 +1,79 @@
+//! Handler for the `contextsmith cache` command.
+//!
+//! `cache stats` reports where the index cache lives, how many files it
+//! covers and how many entries are stale; `cache clear` deletes it.
+
+use std::path::PathBuf;
+
+use colored::Colorize;
+
+use crate::cache;
+use crate::cli::CacheAction;
+use crate::config::Config;
+use crate::error::Result;
+
+// ---------------------------------------------------------------------------
+// Public interface
+// ---------------------------------------------------------------------------
+
+/// All inputs needed to run the cache command.
+#[derive(Debug)]
+pub struct CacheCommandOptions {
+    /// Repository root directory.
+    pub root: PathBuf,
+    /// What to do with the cache.
+    pub action: CacheAction,
+    /// Override the cache directory.
+    pub cache_dir: Option<PathBuf>,
+    /// Suppress non-essential output.
+    pub quiet: bool,
+    /// Path to config file.
+    pub config_path: Option<PathBuf>,
+}
+
+/// Run the cache command.
+pub fn run(options: CacheCommandOptions) -> Result<()> {
+    let config = load_config(&options)?;
+    let cache_config = cache::resolve_config(&config, false, options.cache_dir.as_deref());
+
+    match options.action {
+        CacheAction::Stats => {
+            let stats = cache::stats(&options.root, &cache_config);
+            println!("{}", "Index Cache".bold());
+            println!("  location:        {}", stats.dir.display());
+            println!(
+                "  enabled:         {}",
+                if config.cache.enabled { "yes" } else { "no" }
+            );
+            println!("  entries:         {}", stats.entries);
+            println!(
+                "  size:            {}",
+                super::stats::format_bytes(stats.size_bytes)
+            );
+            println!("  stale entries:   {}", stats.stale);
+        }
+        CacheAction::Clear => {
+            let removed = cache::clear(&options.root, &cache_config)?;
+            if !options.quiet {
+                eprintln!(
+                    "{} removed {} entr{} from {}",
+                    "cache:".green().bold(),
+                    removed,
+                    if removed == 1 { "y" } else { "ies" },
+                    cache::cache_dir(&options.root, &cache_config).display()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Load config from explicit path or discovery.
+fn load_config(options: &CacheCommandOptions) -> Result<Config> {
+    let config_path = crate::config::find_config_file(options.config_path.as_deref());
+    match config_path {
+        Some(p) => Config::load(&p),
+        None => Ok(Config::default()),
+    }
+}
@@ -1,25 +1,46 @@
 //! Handler for the `contextsmith collect` command.
 //!
 //! Collects context from the codebase using explicit file paths (`--files`),
-//! content search (`--grep`), or symbol search (`--symbol`). Outputs a
-//! token-budgeted bundle with manifest.
-
+//! a free-text query (`<QUERY>`), content search (`--grep`) and symbol
+//! search (`--symbol`), optionally
+//! limited to a file, directory or module with `--scope`. `--diff`
+//! boosts sections that overlap a revision range's changes (or, with
+//! `--diff-only`, keeps only those). With
+//! `--include-refs`, call sites of a `--symbol` are added after its
+//! definitions; `--tests` adds the tests of the files that contributed
+//! sections; with `--include-defs`, definitions of the symbols the
+//! sections reference are added last. `--include-imports` puts each
+//! file's import header before its first included section. Outputs a token-budgeted
+//! bundle with manifest.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
 
 use colored::Colorize;
 
-use crate::cli::OutputFormat;
+use crate::cache::{self, IndexCache};
+use crate::cli::{MatchContext, OutputFormat};
 use crate::config::Config;
 use crate::error::{ContextSmithError, Result};
+use crate::git::{self, DiffOptions};
+use crate::imports;
 use crate::indexer;
-use crate::manifest::{self, ManifestEntry};
+use crate::lexical::{self, Corpus};
+use crate::manifest::{self, ManifestEntry, WeightsUsed};
+use crate::models::ModelRegistry;
 use crate::output::{self, Bundle, BundleSection, FormatOptions};
-use crate::ranker;
-use crate::scanner;
-use crate::symbols::{RegexSymbolFinder, SymbolFinder};
-use crate::tokens::{self, TokenEstimator};
+use crate::ranker::{self, Candidate, RankContext, RankingStrategy, ScoredSnippet};
+use crate::related::{self, RelatedOptions};
+use crate::scanner::{self, ScannedFile};
+use crate::slicer::Snippet;
+use crate::symbols::RegexSymbolFinder;
+use crate::tokens::TokenEstimator;
+use crate::units::UnitFinder;
 use crate::utils;
 
+/// Lines taken around a match when `--context unit` finds no unit.
+const UNIT_FALLBACK_LINES: usize = 3;
+
 // ---------------------------------------------------------------------------
 // Public interface
 // ---------------------------------------------------------------------------
@@ -29,6 +50,10 @@ use crate::utils;
 pub struct CollectCommandOptions {
     /// Repository root directory.
     pub root: PathBuf,
+    /// Limit collection to a file, directory or module path.
+    pub scope: Option<String>,
+    /// Free-text query, searched term by term.
+    pub query: Option<String>,
     /// Specific files to include.
     pub files: Vec<PathBuf>,
     /// Search by content pattern (grep).
@@ -41,10 +66,28 @@ pub struct CollectCommandOptions {
     pub lang: Option<String>,
     /// Filter by file path pattern.
     pub path: Option<String>,
-    /// Lines of context around grep matches.
-    pub context_lines: usize,
+    /// Line spans: `START:END` for every `--files` entry, or `PATH:START-END`.
+    pub span: Vec<String>,
+    /// Context taken around each match: lines, or the enclosing unit.
+    pub context: MatchContext,
     /// Max files to include.
     pub max_files: Option<usize>,
+    /// Max grep/symbol windows kept per file, densest first.
+    pub max_snippets: Option<usize>,
+    /// Add definitions of symbols referenced by collected sections.
+    pub include_defs: bool,
+    /// Add references to the `--symbol` being collected.
+    pub include_refs: bool,
+    /// Put each file's import header before its first included section.
+    pub include_imports: bool,
+    /// Add tests related to the collected files.
+    pub tests: bool,
+    /// Git revision range whose changes drive the diff signal.
+    pub diff: Option<String>,
+    /// Keep only sections that overlap the `--diff` changes.
+    pub diff_only: bool,
+    /// Ranking strategy name (falls back to `ranking.strategy`).
+    pub rank: Option<String>,
     /// Output format.
     pub format: OutputFormat,
     /// Write output to file.
@@ -55,62 +98,244 @@ pub struct CollectCommandOptions {
     pub quiet: bool,
     /// Token budget.
     pub budget: Option<usize>,
-    /// Model name for token estimation.
+    /// Model name for token estimation (falls back to `default_model`).
     pub model: Option<String>,
     /// Path to config file.
     pub config_path: Option<PathBuf>,
-    /// Accepted CLI flags that were provided but are not currently wired.
-    pub ignored_flags_used: Vec<String>,
+    /// Bypass the index cache.
+    pub no_cache: bool,
+    /// Override the cache directory.
+    pub cache_dir: Option<PathBuf>,
 }
 
-/// Collect mode — at least one must be specified.
-#[derive(Debug)]
+/// What a collect mode produced.
+#[derive(Debug, Default)]
+struct Collected {
+    /// Sections to rank and budget.
+    candidates: Vec<Candidate>,
+    /// One-line description for the bundle header.
+    summary: String,
+    /// Windows dropped per file by `--max-snippets`.
+    dropped_windows: BTreeMap<String, usize>,
+    /// Windows around references (`--include-refs`), ranked after
+    /// `candidates`.
+    references: Vec<Candidate>,
+    /// References found per file.
+    reference_counts: BTreeMap<String, usize>,
+}
+
+impl Collected {
+    fn new(candidates: Vec<Candidate>, summary: impl Into<String>) -> Self {
+        Self {
+            candidates,
+            summary: summary.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Collect mode — at least one must be specified; every one given runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum CollectMode {
     Files,
+    Query,
     Grep,
     Symbol,
 }
 
 /// Run the collect command.
 pub fn run(options: CollectCommandOptions) -> Result<()> {
-    if !options.ignored_flags_used.is_empty() && !options.quiet {
-        eprintln!(
-            "warn: collect currently ignores {}",
-            options.ignored_flags_used.join(", ")
-        );
-    }
-
-    // Step 1: Validate that at least one mode is specified.
-    let mode = validate_mode(&options)?;
+    // Step 1: Parse spans and validate that at least one mode is specified.
+    let spans = options
+        .span
+        .iter()
+        .map(|s| parse_span(s))
+        .collect::<Result<Vec<_>>>()?;
+    let modes = validate_modes(&options, &spans)?;
 
-    // Step 2: Load config (for scanner options).
+    // Step 2: Load config (for scanner options), pick the strategy and
+    // resolve --scope against an unfiltered scan.
     let config = load_config(&options)?;
+    let strategy = resolve_strategy(&options, &config)?;
+    let scope = match options.scope {
+        Some(ref scope) => {
+            let all = scanner::scan(&scanner::scan_options_from_config(&config, &options.root))?;
+            Some(scanner::resolve_scope(scope, &options.root, &all)?)
+        }
+        None => None,
+    };
+    let diff_context = match options.diff {
+        Some(ref range) => Some(
+            RankContext::from_diff(&git::get_diff(&DiffOptions {
+                root: options.root.clone(),
+                rev_range: Some(range.clone()),
+                staged: false,
+                untracked: false,
+                since: None,
+                base: None,
+                worktree: false,
+            })?)
+            .with_commit_times(&options.root),
+        ),
+        None => None,
+    };
+    let mut cache = IndexCache::open(
+        &options.root,
+        &cache::resolve_config(&config, options.no_cache, options.cache_dir.as_deref()),
+    );
+
+    // Step 3: Run every requested mode and union the results. Searches
+    // scan the repo once; the file list doubles as the BM25 corpus and as
+    // the search space for --include-defs and --tests.
+    let needs_scan =
+        modes.iter().any(|m| *m != CollectMode::Files) || options.include_defs || options.tests;
+    let files = if needs_scan {
+        scan_files(&options, &config, scope.as_ref())?
+    } else {
+        Vec::new()
+    };
+    // Stopwords in a free-text query would otherwise weigh as rare terms.
+    let free_text = options.query.as_deref().map(|q| {
+        q.split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|w| !w.is_empty() && !lexical::is_stopword(w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    let query_text = [
+        free_text.as_deref(),
+        options.grep.as_deref(),
+        options.symbol.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|q| !q.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ");
+    let query = (!query_text.is_empty()).then_some(query_text.as_str());
+
+    let mut results = Vec::new();
+    for mode in &modes {
+        results.push(match mode {
+            CollectMode::Files => {
+                let paths =
+                    scoped_paths(&options, explicit_files(&options, &spans), scope.as_ref());
+                collect_files(&options, &paths, &spans)?
+            }
+            CollectMode::Query => collect_query(&options, &files, &mut cache)?,
+            CollectMode::Grep => collect_grep(&options, &files, &mut cache)?,
+            CollectMode::Symbol => collect_symbol(&options, &files, &mut cache)?,
+        });
+    }
+    let Collected {
+        mut candidates,
+        mut summary,
+        dropped_windows,
+        mut references,
+        reference_counts,
+    } = union_results(&options, results);
+
+    // With --diff-only, drop sections that miss the changed hunks before
+    // anything is derived from them.
+    if let (true, Some(context)) = (options.diff_only, diff_context.as_ref()) {
+        let total = candidates.len() + references.len();
+        candidates.retain(|c| ranker::diff_score(c, context) == 1.0);
+        references.retain(|c| ranker::diff_score(c, context) == 1.0);
+        summary = format!(
+            "{summary}; {} of {} section{} overlap {}",
+            candidates.len() + references.len(),
+            total,
+            if total == 1 { "" } else { "s" },
+            options.diff.as_deref().unwrap_or(""),
+        );
+    }
 
-    // Step 3: Dispatch to the appropriate handler.
-    let (sections, summary) = match mode {
-        CollectMode::Files => collect_files(&options)?,
-        CollectMode::Grep => collect_grep(&options, &config)?,
-        CollectMode::Symbol => collect_symbol(&options, &config)?,
+    // Step 3b: Find the tests of the collected files (--tests) and resolve
+    // symbols referenced by the sections (--include-defs).
+    let tests = if options.tests {
+        collect_tests(&options, &candidates, &files)
+    } else {
+        Vec::new()
+    };
+    if !tests.is_empty() {
+        summary = format!(
+            "{summary}, {} test section{}",
+            tests.len(),
+            if tests.len() == 1 { "" } else { "s" },
+        );
+    }
+    let definitions = if options.include_defs {
+        collect_definitions(&options, &candidates, &files, &mut cache)?
+    } else {
+        Vec::new()
     };
+    if !definitions.is_empty() {
+        summary = format!(
+            "{summary}, {} referenced definition{}",
+            definitions.len(),
+            if definitions.len() == 1 { "" } else { "s" },
+        );
+    }
+    let corpus =
+        (query.is_some() && !candidates.is_empty()).then(|| Corpus::from_cache(&files, &mut cache));
+    if let Err(e) = cache.save() {
+        tracing::warn!("could not update the index cache: {e}");
+    }
 
-    if sections.is_empty() {
+    if candidates.is_empty() {
         if !options.quiet {
             println!("{}", "No matching content found.".dimmed());
         }
         return Ok(());
     }
 
-    // Step 4: Apply budget and build manifest entries.
-    let model = options
-        .model
-        .as_deref()
-        .map(tokens::parse_model)
-        .unwrap_or(tokens::ModelFamily::Gpt4);
-    let estimator = tokens::CharEstimator::new(model);
+    // Step 4: Rank with every signal; explicit files are proximity anchors
+    // alongside the changed files (of --diff, or else the working tree).
+    let base_context = match diff_context {
+        Some(context) => context,
+        None => RankContext::for_repo(&options.root),
+    };
+    let mut context = base_context.with_focus(
+        options
+            .files
+            .iter()
+            .map(|f| f.to_string_lossy().to_string()),
+    );
+    if let (Some(query), Some(corpus)) = (query, corpus) {
+        context = context.with_query(query, corpus);
+    }
+    context = context.with_test_ranges(test_ranges(
+        &options,
+        [&candidates, &references, &tests, &definitions],
+    ));
+    let weights = &config.ranking_weights;
+    let mut ranked = ranker::rank_candidates(&candidates, &context, weights, strategy.as_ref());
+    // References, tests, then definitions rank after every section, so
+    // they only use leftover budget.
+    for extra in [&references, &tests, &definitions] {
+        ranked.extend(ranker::rank_candidates(
+            extra,
+            &context,
+            weights,
+            strategy.as_ref(),
+        ));
+    }
+    let headers = if options.include_imports {
+        import_headers(&options, &ranked)
+    } else {
+        BTreeMap::new()
+    };
+
+    // Step 5: Apply budget (explicit or derived from --model) and build
+    // manifest entries.
+    let model = resolve_model(&options, &config);
+    let registry = ModelRegistry::from_config(&config);
+    let estimator = registry.estimator(&model);
+    let plan = registry.plan_budget(options.budget, None, options.model.as_deref());
 
-    let (included_sections, manifest_entries) = apply_budget(&sections, &estimator, options.budget);
+    let (included_sections, manifest_entries) =
+        apply_budget(&ranked, &headers, &estimator, plan.effective());
 
-    // Step 5: Build bundle.
+    // Step 6: Build bundle.
     let bundle = Bundle {
         summary: format!(
             "{} ({} section{})",
@@ -125,7 +350,7 @@ pub fn run(options: CollectCommandOptions) -> Result<()> {
         sections: included_sections,
     };
 
-    // Step 6: Format and write.
+    // Step 7: Format and write.
     let format = utils::cli_format_to_output_format(&options.format);
     let formatted = output::format_bundle(&bundle, format)?;
     output::write_output(
@@ -137,14 +362,20 @@ pub fn run(options: CollectCommandOptions) -> Result<()> {
         },
     )?;
 
-    // Step 7: Write manifest sibling.
+    // Step 8: Write manifest sibling.
     if let Some(ref out_path) = options.out {
-        let m = manifest::build_manifest(
-            manifest_entries.clone(),
-            estimator.model_name(),
-            options.budget,
-            0,
-        );
+        let mut m =
+            manifest::build_manifest(manifest_entries.clone(), &model, plan.budget, plan.reserve);
+        m.summary.weights_used = Some(WeightsUsed {
+            text: weights.text,
+            diff: weights.diff,
+            recency: weights.recency,
+            proximity: weights.proximity,
+            test: weights.test,
+        });
+        m.summary.ranking_strategy = Some(strategy.name().to_string());
+        m.summary.dropped_windows = dropped_windows;
+        m.summary.references = reference_counts;
         let manifest_path = utils::manifest_sibling_path(out_path);
         manifest::write_manifest(&m, &manifest_path)?;
         if !options.quiet {
@@ -156,14 +387,14 @@ pub fn run(options: CollectCommandOptions) -> Result<()> {
         }
     }
 
-    // Step 8: Print summary to stderr.
+    // Step 9: Print summary to stderr.
     if !options.quiet && !options.stdout {
         let total_tokens: usize = manifest_entries
             .iter()
             .filter(|e| e.included)
             .map(|e| e.token_estimate)
             .sum();
-        let budget_info = match options.budget {
+        let budget_info = match plan.effective() {
             Some(b) => format!(", ~{total_tokens} tokens (budget: {b})"),
             None => format!(", ~{total_tokens} tokens"),
         };
@@ -184,21 +415,231 @@ pub fn run(options: CollectCommandOptions) -> Result<()> {
 // Mode validation
 // ---------------------------------------------------------------------------
 
-/// Ensure at least one collect mode is specified.
-fn validate_mode(options: &CollectCommandOptions) -> Result<CollectMode> {
-    if !options.files.is_empty() {
-        return Ok(CollectMode::Files);
+/// Ensure at least one collect mode is specified, returning every mode
+/// given (files, then query, then grep, then symbol).
+///
+/// `--scope` on its own collects every file in the scope, and a
+/// `PATH:START-END` span names a file just like `--files`.
+fn validate_modes(options: &CollectCommandOptions, spans: &[LineSpan]) -> Result<Vec<CollectMode>> {
+    if options.include_refs && options.symbol.is_none() {
+        return Err(ContextSmithError::validation(
+            "include-refs",
+            "--include-refs needs --symbol",
+        ));
+    }
+    if let Some(span) = spans.iter().find(|s| s.path.is_none()) {
+        if options.files.is_empty() {
+            return Err(ContextSmithError::validation(
+                "span",
+                format!(
+                    "'{}:{}' needs --files, or use the PATH:START-END form",
+                    span.start, span.end
+                ),
+            ));
+        }
+    }
+
+    let mut modes = Vec::new();
+    if !options.files.is_empty() || spans.iter().any(|s| s.path.is_some()) {
+        modes.push(CollectMode::Files);
+    }
+    if options.query.is_some() {
+        modes.push(CollectMode::Query);
     }
     if options.grep.is_some() {
-        return Ok(CollectMode::Grep);
+        modes.push(CollectMode::Grep);
     }
     if options.symbol.is_some() {
-        return Ok(CollectMode::Symbol);
+        modes.push(CollectMode::Symbol);
     }
-    Err(ContextSmithError::validation(
-        "mode",
-        "at least one of <query>, --files, --grep, or --symbol must be specified",
-    ))
+    if modes.is_empty() && options.scope.is_some() {
+        modes.push(CollectMode::Files);
+    }
+    if modes.is_empty() {
+        return Err(ContextSmithError::validation(
+            "mode",
+            "at least one of <query>, --files, --grep, --symbol, or --scope must be specified",
+        ));
+    }
+    Ok(modes)
+}
+
+/// A `--span` line range, optionally tied to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LineSpan {
+    /// File the span belongs to (`PATH:START-END`), or `None` for every file.
+    path: Option<PathBuf>,
+    /// First line (1-based).
+    start: usize,
+    /// Last line (1-based, inclusive).
+    end: usize,
+}
+
+/// Parse a span: `START:END`, `START-END` or `PATH:START-END`.
+fn parse_span(value: &str) -> Result<LineSpan> {
+    let invalid = || {
+        ContextSmithError::validation(
+            "span",
+            format!("invalid span '{value}'; expected START:END, START-END or PATH:START-END"),
+        )
+    };
+    let parse_range = |range: &str, sep: char| -> Option<(usize, usize)> {
+        let (start, end) = range.split_once(sep)?;
+        Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+    };
+
+    let (path, (start, end)) = match value.rsplit_once(':') {
+        Some((path, range)) if !path.is_empty() && parse_range(range, '-').is_some() => (
+            Some(PathBuf::from(path)),
+            parse_range(range, '-').ok_or_else(invalid)?,
+        ),
+        _ => (
+            None,
+            parse_range(value, ':')
+                .or_else(|| parse_range(value, '-'))
+                .ok_or_else(invalid)?,
+        ),
+    };
+
+    if start == 0 || end < start {
+        return Err(ContextSmithError::validation(
+            "span",
+            format!("invalid span '{value}'; lines start at 1 and END must not precede START"),
+        ));
+    }
+    Ok(LineSpan { path, start, end })
+}
+
+/// `--files` plus any files named only by `PATH:START-END` spans.
+fn explicit_files(options: &CollectCommandOptions, spans: &[LineSpan]) -> Vec<PathBuf> {
+    let mut files = options.files.clone();
+    for path in spans.iter().filter_map(|s| s.path.as_ref()) {
+        if !files.iter().any(|f| same_file(f, path, &options.root)) {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+/// Whether two user-supplied paths name the same file under `root`.
+fn same_file(a: &std::path::Path, b: &std::path::Path, root: &std::path::Path) -> bool {
+    scanner::relative_to_root(&a.to_string_lossy(), root)
+        == scanner::relative_to_root(&b.to_string_lossy(), root)
+}
+
+/// The files to read in files mode: `files` limited to the scope, or
+/// the whole scope when no files were given.
+fn scoped_paths(
+    options: &CollectCommandOptions,
+    files: Vec<PathBuf>,
+    scope: Option<&BTreeSet<String>>,
+) -> Vec<PathBuf> {
+    let Some(scope) = scope else {
+        return files;
+    };
+    if files.is_empty() {
+        return scope.iter().map(PathBuf::from).collect();
+    }
+
+    let (inside, outside): (Vec<&PathBuf>, Vec<&PathBuf>) = files.iter().partition(|f| {
+        scope.contains(&scanner::relative_to_root(
+            &f.to_string_lossy(),
+            &options.root,
+        ))
+    });
+    if !outside.is_empty() && !options.quiet {
+        eprintln!(
+            "warn: skipping files outside --scope: {}",
+            outside
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    inside.into_iter().cloned().collect()
+}
+
+// ---------------------------------------------------------------------------
+// Combining modes
+// ---------------------------------------------------------------------------
+
+/// Union the results of every mode that ran.
+///
+/// Summaries are joined, and with more than one mode, sections of the
+/// same file whose line ranges overlap are merged. References that
+/// overlap a section are dropped.
+fn union_results(options: &CollectCommandOptions, results: Vec<Collected>) -> Collected {
+    let combined = results.len() > 1;
+    let mut union = Collected::default();
+    let mut summaries = Vec::new();
+    for result in results {
+        union.candidates.extend(result.candidates);
+        union.references.extend(result.references);
+        for (path, count) in result.dropped_windows {
+            *union.dropped_windows.entry(path).or_default() += count;
+        }
+        union.reference_counts.extend(result.reference_counts);
+        summaries.push(result.summary);
+    }
+    union.summary = summaries.join("; ");
+
+    if combined {
+        union.candidates = merge_sections(options, std::mem::take(&mut union.candidates));
+        let sections = &union.candidates;
+        union.references.retain(|r| {
+            !sections.iter().any(|s| {
+                same_file(
+                    s.section.file_path.as_ref(),
+                    r.section.file_path.as_ref(),
+                    &options.root,
+                ) && s.start_line <= r.end_line
+                    && r.start_line <= s.end_line
+            })
+        });
+    }
+    union
+}
+
+/// Merge sections of the same file whose line ranges overlap.
+///
+/// A merged section covers both ranges (re-read from disk), counts both
+/// sections' matches and carries every distinct reason, joined by `; `.
+fn merge_sections(options: &CollectCommandOptions, candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let key = |c: &Candidate| scanner::relative_to_root(&c.section.file_path, &options.root);
+    let mut sorted = candidates;
+    sorted.sort_by(|a, b| key(a).cmp(&key(b)).then(a.start_line.cmp(&b.start_line)));
+
+    let mut merged: Vec<Candidate> = Vec::new();
+    for candidate in sorted {
+        let Some(last) = merged
+            .last_mut()
+            .filter(|last| key(last) == key(&candidate) && candidate.start_line <= last.end_line)
+        else {
+            merged.push(candidate);
+            continue;
+        };
+
+        if candidate.end_line > last.end_line {
+            let path = options.root.join(&last.section.file_path);
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                let lines: Vec<&str> = content.lines().collect();
+                let end = candidate.end_line.min(lines.len());
+                last.section.content = lines[last.start_line.saturating_sub(1)..end].join("\n");
+                last.end_line = end;
+            }
+        }
+        last.match_count += candidate.match_count;
+        if !last
+            .section
+            .reason
+            .split("; ")
+            .any(|r| r == candidate.section.reason)
+        {
+            last.section.reason = format!("{}; {}", last.section.reason, candidate.section.reason);
+        }
+    }
+    merged
 }
 
 // ---------------------------------------------------------------------------
@@ -207,11 +648,16 @@ fn validate_mode(options: &CollectCommandOptions) -> Result<CollectMode> {
 
 /// Collect context from explicitly specified files.
 ///
-/// Reads each file in full and creates one section per file.
-fn collect_files(options: &CollectCommandOptions) -> Result<(Vec<BundleSection>, String)> {
+/// Reads each file and creates one section per file, or one section per
+/// span when spans apply to it.
+fn collect_files(
+    options: &CollectCommandOptions,
+    paths: &[PathBuf],
+    spans: &[LineSpan],
+) -> Result<Collected> {
     let mut sections = Vec::new();
 
-    for file_path in &options.files {
+    for file_path in paths {
         let abs_path = if file_path.is_absolute() {
             file_path.clone()
         } else {
@@ -225,56 +671,252 @@ fn collect_files(options: &CollectCommandOptions) -> Result<(Vec<BundleSection>,
         let rel_path = file_path.to_string_lossy().to_string();
         let language = utils::infer_language(&rel_path);
 
-        sections.push(BundleSection {
-            file_path: rel_path,
-            language,
-            content,
-            reason: "explicit file".to_string(),
+        let file_spans: Vec<&LineSpan> = spans
+            .iter()
+            .filter(|s| {
+                s.path
+                    .as_deref()
+                    .is_none_or(|p| same_file(p, file_path, &options.root))
+            })
+            .collect();
+        if !file_spans.is_empty() {
+            let lines: Vec<&str> = content.lines().collect();
+            for span in file_spans {
+                if span.start > lines.len() {
+                    return Err(ContextSmithError::validation(
+                        "span",
+                        format!(
+                            "{}:{}-{} starts past the end of the file ({} lines)",
+                            rel_path,
+                            span.start,
+                            span.end,
+                            lines.len()
+                        ),
+                    ));
+                }
+                let end = span.end.min(lines.len());
+                sections.push(Candidate {
+                    start_line: span.start,
+                    end_line: end,
+                    match_count: 1,
+                    section: BundleSection {
+                        file_path: rel_path.clone(),
+                        language: language.clone(),
+                        content: lines[span.start - 1..end].join("\n"),
+                        reason: format!("explicit span (lines {}-{})", span.start, end),
+                    },
+                });
+            }
+            continue;
+        }
+
+        sections.push(Candidate {
+            start_line: 1,
+            end_line: content.lines().count(),
+            match_count: 1,
+            section: BundleSection {
+                file_path: rel_path,
+                language,
+                content,
+                reason: "explicit file".to_string(),
+            },
         });
     }
 
+    let summary = if spans.is_empty() {
+        format!(
+            "collected {} file{}",
+            sections.len(),
+            if sections.len() == 1 { "" } else { "s" },
+        )
+    } else {
+        format!(
+            "collected {} section{} from {} file{}",
+            sections.len(),
+            if sections.len() == 1 { "" } else { "s" },
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" },
+        )
+    };
+
+    Ok(Collected::new(sections, summary))
+}
+
+// ---------------------------------------------------------------------------
+// collect <QUERY>
+// ---------------------------------------------------------------------------
+
+/// Collect context for a free-text query.
+///
+/// The query is split into stemmed terms (see
+/// [`lexical::free_text_terms`]), each searched on its own as a
+/// case-insensitive substring. Files are ordered by how many distinct
+/// terms they cover (`--max-files` keeps the best), and each window's
+/// match count is the distinct terms it covers plus those of its file,
+/// so windows hitting more of the query rank higher.
+fn collect_query(
+    options: &CollectCommandOptions,
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+) -> Result<Collected> {
+    let query = options.query.as_deref().unwrap_or("");
+    let terms = lexical::free_text_terms(query);
+    if terms.is_empty() {
+        return Err(ContextSmithError::validation(
+            "query",
+            format!("'{query}' has no searchable terms; use --grep for an exact pattern"),
+        ));
+    }
+
+    // Search each term independently, remembering which term hit where.
+    let mut matches: Vec<indexer::TextMatch> = Vec::new();
+    let mut match_terms: Vec<usize> = Vec::new();
+    for (idx, term) in terms.iter().enumerate() {
+        let candidates: Vec<ScannedFile> = files
+            .iter()
+            .filter(|f| cache.may_contain(f, term))
+            .cloned()
+            .collect();
+        let pattern = format!("(?i){}", regex::escape(term));
+        let result = indexer::search_files_cached(&candidates, &pattern, cache)?;
+        match_terms.extend(std::iter::repeat_n(idx, result.matches.len()));
+        matches.extend(result.matches);
+    }
+
+    if matches.is_empty() {
+        return Ok(Collected::new(Vec::new(), "no matches found"));
+    }
+
+    let mut by_file: BTreeMap<&str, Vec<(&indexer::TextMatch, usize)>> = BTreeMap::new();
+    for (m, &term) in matches.iter().zip(&match_terms) {
+        by_file.entry(&m.file_path).or_default().push((m, term));
+    }
+    let coverage = |hits: &[(&indexer::TextMatch, usize)]| {
+        hits.iter().map(|&(_, t)| t).collect::<BTreeSet<_>>().len()
+    };
+
+    // Best-covered files first; ties stay in path order.
+    let mut file_paths: Vec<&str> = by_file.keys().copied().collect();
+    file_paths.sort_by_key(|path| std::cmp::Reverse(coverage(&by_file[path])));
+    if let Some(max) = options.max_files {
+        file_paths.truncate(max);
+    }
+
+    let mut candidates = Vec::new();
+    let mut dropped_windows = BTreeMap::new();
+    for file_path in file_paths {
+        let hits = &by_file[file_path];
+        let file_coverage = coverage(hits);
+
+        let scanned = files.iter().find(|f| f.rel_path == file_path);
+        let content = match scanned {
+            Some(f) => match std::fs::read_to_string(&f.abs_path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let total_lines = lines.len();
+
+        let file_matches: Vec<&indexer::TextMatch> = hits.iter().map(|&(m, _)| m).collect();
+        let ranges = compute_match_ranges(&file_matches, options.context, &content, file_path);
+        let (ranges, dropped) = cap_windows(ranges, &file_matches, options.max_snippets);
+        if dropped > 0 {
+            dropped_windows.insert(file_path.to_string(), dropped);
+        }
+
+        for (start, end) in ranges {
+            let covered: BTreeSet<usize> = hits
+                .iter()
+                .filter(|(m, _)| m.line_number >= start && m.line_number <= end)
+                .map(|&(_, t)| t)
+                .collect();
+            let covered_terms: Vec<&str> = covered.iter().map(|&t| terms[t].as_str()).collect();
+
+            candidates.push(Candidate {
+                section: BundleSection {
+                    file_path: file_path.to_string(),
+                    language: utils::infer_language(file_path),
+                    content: lines[start.saturating_sub(1)..end.min(total_lines)].join("\n"),
+                    reason: format!(
+                        "query term{} {}",
+                        if covered_terms.len() == 1 { "" } else { "s" },
+                        covered_terms.join(", "),
+                    ),
+                },
+                start_line: start,
+                end_line: end.min(total_lines),
+                match_count: covered.len() + file_coverage,
+            });
+        }
+    }
+
     let summary = format!(
-        "collected {} file{}",
-        sections.len(),
-        if sections.len() == 1 { "" } else { "s" },
+        "query '{}' ({} term{}: {}): {} match{} in {} file{}",
+        query,
+        terms.len(),
+        if terms.len() == 1 { "" } else { "s" },
+        terms.join(", "),
+        matches.len(),
+        if matches.len() == 1 { "" } else { "es" },
+        by_file.len(),
+        if by_file.len() == 1 { "" } else { "s" },
     );
 
-    Ok((sections, summary))
+    Ok(Collected {
+        candidates,
+        summary,
+        dropped_windows,
+        ..Collected::default()
+    })
 }
 
 // ---------------------------------------------------------------------------
 // collect --grep
 // ---------------------------------------------------------------------------
 
-/// Collect context by searching the codebase for a pattern.
-///
-/// Scans the repo for files, searches for the pattern, then extracts
-/// context around each match to create sections.
-fn collect_grep(
+/// Scan the repo for files, applying the language, path and exclude
+/// filters, then keep only files inside the scope (if any).
+fn scan_files(
     options: &CollectCommandOptions,
     config: &Config,
-) -> Result<(Vec<BundleSection>, String)> {
-    let pattern = options.grep.as_deref().unwrap_or("");
-
-    // Scan the repo for files.
+    scope: Option<&BTreeSet<String>>,
+) -> Result<Vec<ScannedFile>> {
     let mut scan_options = scanner::scan_options_from_config(config, &options.root);
     scan_options.lang_filter = options.lang.clone();
     scan_options.path_filter = options.path.clone();
     scan_options.exclude_patterns = options.exclude.clone();
 
-    let files = scanner::scan(&scan_options)?;
+    let mut files = scanner::scan(&scan_options)?;
+    if let Some(scope) = scope {
+        files.retain(|f| scope.contains(&f.rel_path));
+    }
+    Ok(files)
+}
+
+/// Collect context by searching the codebase for a pattern.
+///
+/// Scans the repo for files, searches for the pattern, then extracts
+/// context around each match to create sections.
+fn collect_grep(
+    options: &CollectCommandOptions,
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+) -> Result<Collected> {
+    let pattern = options.grep.as_deref().unwrap_or("");
 
     // Search across files.
-    let result = indexer::search_files(&files, pattern)?;
+    let result = indexer::search_files_cached(files, pattern, cache)?;
 
     if result.matches.is_empty() {
-        return Ok((Vec::new(), "no matches found".to_string()));
+        return Ok(Collected::new(Vec::new(), "no matches found"));
     }
 
     // Group matches by file and build sections with context.
     let grouped = indexer::group_by_file(&result.matches);
-    let mut sections = Vec::new();
-    let mut match_counts = Vec::new();
+    let mut candidates = Vec::new();
+    let mut dropped_windows = BTreeMap::new();
 
     // Sort file paths for deterministic output.
     let mut file_paths: Vec<&String> = grouped.keys().collect();
@@ -301,8 +943,13 @@ fn collect_grep(
         let lines: Vec<&str> = content.lines().collect();
         let total_lines = lines.len();
 
-        // Build context snippets around each match.
-        let ranges = compute_match_ranges(file_matches, options.context_lines, total_lines);
+        // Build context snippets around each match, keeping the densest
+        // windows when --max-snippets caps the file.
+        let ranges = compute_match_ranges(file_matches, options.context, &content, file_path);
+        let (ranges, dropped) = cap_windows(ranges, file_matches, options.max_snippets);
+        if dropped > 0 {
+            dropped_windows.insert(file_path.clone(), dropped);
+        }
 
         for (start, end) in ranges {
             let snippet_content = lines[start.saturating_sub(1)..end.min(total_lines)].join("\n");
@@ -312,25 +959,24 @@ fn collect_grep(
                 .filter(|m| m.line_number >= start && m.line_number <= end)
                 .count();
 
-            sections.push(BundleSection {
-                file_path: file_path.clone(),
-                language: utils::infer_language(file_path),
-                content: snippet_content,
-                reason: format!(
-                    "grep match{} for '{}'",
-                    if match_count == 1 { "" } else { "es" },
-                    pattern,
-                ),
+            candidates.push(Candidate {
+                section: BundleSection {
+                    file_path: file_path.clone(),
+                    language: utils::infer_language(file_path),
+                    content: snippet_content,
+                    reason: format!(
+                        "grep match{} for '{}'",
+                        if match_count == 1 { "" } else { "es" },
+                        pattern,
+                    ),
+                },
+                start_line: start,
+                end_line: end.min(total_lines),
+                match_count,
             });
-            match_counts.push(match_count);
         }
     }
 
-    // Rank sections using TF-IDF scoring.
-    let weights = config.ranking_weights.clone();
-    let ranked = ranker::rank_snippets(&sections, &match_counts, &weights);
-    let sections: Vec<BundleSection> = ranked.iter().map(|r| r.section.clone()).collect();
-
     let summary = format!(
         "grep '{}': {} match{} in {} file{}",
         pattern,
@@ -340,7 +986,12 @@ fn collect_grep(
         if result.files_matched == 1 { "" } else { "s" },
     );
 
-    Ok((sections, summary))
+    Ok(Collected {
+        candidates,
+        summary,
+        dropped_windows,
+        ..Collected::default()
+    })
 }
 
 // ---------------------------------------------------------------------------
@@ -353,30 +1004,25 @@ fn collect_grep(
 /// definitions, then extracts context around each definition.
 fn collect_symbol(
     options: &CollectCommandOptions,
-    config: &Config,
-) -> Result<(Vec<BundleSection>, String)> {
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+) -> Result<Collected> {
     let symbol = options.symbol.as_deref().unwrap_or("");
 
-    // Scan the repo for files.
-    let mut scan_options = scanner::scan_options_from_config(config, &options.root);
-    scan_options.lang_filter = options.lang.clone();
-    scan_options.path_filter = options.path.clone();
-    scan_options.exclude_patterns = options.exclude.clone();
-
-    let files = scanner::scan(&scan_options)?;
-
     // Find symbol definitions.
-    let finder = RegexSymbolFinder;
-    let matches = finder.find_definitions(&files, symbol)?;
+    let matches = RegexSymbolFinder.find_definitions_cached(files, symbol, cache)?;
 
     if matches.is_empty() {
-        return Ok((Vec::new(), format!("no definitions found for '{symbol}'")));
+        return Ok(Collected::new(
+            Vec::new(),
+            format!("no definitions found for '{symbol}'"),
+        ));
     }
 
     // Group matches by file and build sections with context.
     let grouped = indexer::group_by_file(&matches);
-    let mut sections = Vec::new();
-    let mut match_counts = Vec::new();
+    let mut candidates = Vec::new();
+    let mut dropped_windows = BTreeMap::new();
 
     let mut file_paths: Vec<&String> = grouped.keys().collect();
     file_paths.sort();
@@ -401,7 +1047,11 @@ fn collect_symbol(
         let lines: Vec<&str> = content.lines().collect();
         let total_lines = lines.len();
 
-        let ranges = compute_match_ranges(&file_match_refs, options.context_lines, total_lines);
+        let ranges = compute_match_ranges(&file_match_refs, options.context, &content, file_path);
+        let (ranges, dropped) = cap_windows(ranges, &file_match_refs, options.max_snippets);
+        if dropped > 0 {
+            dropped_windows.insert(file_path.clone(), dropped);
+        }
 
         for (start, end) in ranges {
             let snippet_content = lines[start.saturating_sub(1)..end.min(total_lines)].join("\n");
@@ -411,23 +1061,21 @@ fn collect_symbol(
                 .filter(|m| m.line_number >= start && m.line_number <= end)
                 .count();
 
-            sections.push(BundleSection {
-                file_path: file_path.clone(),
-                language: utils::infer_language(file_path),
-                content: snippet_content,
-                reason: format!("definition of '{symbol}'"),
+            candidates.push(Candidate {
+                section: BundleSection {
+                    file_path: file_path.clone(),
+                    language: utils::infer_language(file_path),
+                    content: snippet_content,
+                    reason: format!("definition of '{symbol}'"),
+                },
+                start_line: start,
+                end_line: end.min(total_lines),
+                match_count,
             });
-            match_counts.push(match_count);
         }
     }
 
-    // Rank sections using the ranker.
-    let weights = config.ranking_weights.clone();
-    let ranked = ranker::rank_snippets(&sections, &match_counts, &weights);
-
-    let ranked_sections: Vec<BundleSection> = ranked.iter().map(|r| r.section.clone()).collect();
-
-    let summary = format!(
+    let mut summary = format!(
         "symbol '{}': {} definition{} in {} file{}",
         symbol,
         matches.len(),
@@ -436,7 +1084,266 @@ fn collect_symbol(
         if grouped.len() == 1 { "" } else { "s" },
     );
 
-    Ok((ranked_sections, summary))
+    let (references, reference_counts) = if options.include_refs {
+        collect_references(options, files, cache, &candidates)?
+    } else {
+        (Vec::new(), BTreeMap::new())
+    };
+    if options.include_refs {
+        let total: usize = reference_counts.values().sum();
+        summary.push_str(&format!(
+            ", {} reference{} in {} file{}",
+            total,
+            if total == 1 { "" } else { "s" },
+            reference_counts.len(),
+            if reference_counts.len() == 1 { "" } else { "s" },
+        ));
+    }
+
+    Ok(Collected {
+        candidates,
+        summary,
+        dropped_windows,
+        references,
+        reference_counts,
+    })
+}
+
+/// Collect windows around references to the `--symbol` (`--include-refs`).
+///
+/// Every reference is counted per file, but lines already shown in a
+/// definition window get no window of their own.
+fn collect_references(
+    options: &CollectCommandOptions,
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+    definitions: &[Candidate],
+) -> Result<(Vec<Candidate>, BTreeMap<String, usize>)> {
+    let symbol = options.symbol.as_deref().unwrap_or("");
+    let matches = RegexSymbolFinder.find_references_cached(files, symbol, cache)?;
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for m in &matches {
+        *counts.entry(m.file_path.clone()).or_default() += 1;
+    }
+
+    let uncovered: Vec<indexer::TextMatch> = matches
+        .into_iter()
+        .filter(|m| {
+            !definitions.iter().any(|d| {
+                d.section.file_path == m.file_path
+                    && d.start_line <= m.line_number
+                    && m.line_number <= d.end_line
+            })
+        })
+        .collect();
+    let grouped = indexer::group_by_file(&uncovered);
+    let mut file_paths: Vec<&String> = grouped.keys().collect();
+    file_paths.sort();
+
+    let mut candidates = Vec::new();
+    for file_path in file_paths {
+        let file_matches = &grouped[file_path];
+        let scanned = files.iter().find(|f| &f.rel_path == file_path);
+        let content = match scanned {
+            Some(f) => match std::fs::read_to_string(&f.abs_path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let total_lines = lines.len();
+
+        for (start, end) in compute_match_ranges(file_matches, options.context, &content, file_path)
+        {
+            let match_count = file_matches
+                .iter()
+                .filter(|m| m.line_number >= start && m.line_number <= end)
+                .count();
+
+            candidates.push(Candidate {
+                section: BundleSection {
+                    file_path: file_path.clone(),
+                    language: utils::infer_language(file_path),
+                    content: lines[start.saturating_sub(1)..end.min(total_lines)].join("\n"),
+                    reason: format!(
+                        "reference{} to '{symbol}'",
+                        if match_count == 1 { "" } else { "s" },
+                    ),
+                },
+                start_line: start,
+                end_line: end.min(total_lines),
+                match_count,
+            });
+        }
+    }
+
+    Ok((candidates, counts))
+}
+
+// ---------------------------------------------------------------------------
+// collect --include-defs
+// ---------------------------------------------------------------------------
+
+/// Find definitions of the symbols referenced by the collected sections.
+///
+/// Definitions already covered by a section are skipped. The results
+/// carry no matches of their own.
+fn collect_definitions(
+    options: &CollectCommandOptions,
+    candidates: &[Candidate],
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+) -> Result<Vec<Candidate>> {
+    let snippets = to_snippets(options, candidates);
+    let definitions = related::find_referenced_definitions(&snippets, files, cache)?;
+    Ok(from_snippets(definitions))
+}
+
+// ---------------------------------------------------------------------------
+// collect --tests
+// ---------------------------------------------------------------------------
+
+/// Find the tests of the files that contributed sections (`--tests`).
+///
+/// Sections that are already tests are not searched from, and tests
+/// already covered by a section are skipped.
+fn collect_tests(
+    options: &CollectCommandOptions,
+    candidates: &[Candidate],
+    files: &[ScannedFile],
+) -> Vec<Candidate> {
+    let snippets = to_snippets(options, candidates);
+    let mut sources: Vec<String> = Vec::new();
+    for snippet in &snippets {
+        if !utils::is_test_path(&snippet.file_path) && !sources.contains(&snippet.file_path) {
+            sources.push(snippet.file_path.clone());
+        }
+    }
+    let context_lines = match options.context {
+        MatchContext::Lines(lines) => lines,
+        MatchContext::Unit => UNIT_FALLBACK_LINES,
+    };
+    from_snippets(related::find_tests_for(
+        &sources,
+        files,
+        &snippets,
+        &RelatedOptions { context_lines },
+    ))
+}
+
+/// Inline test module ranges of every file with a section, for the
+/// test signal.
+fn test_ranges<'a>(
+    options: &CollectCommandOptions,
+    tiers: impl IntoIterator<Item = &'a Vec<Candidate>>,
+) -> HashMap<String, Vec<(usize, usize)>> {
+    let mut ranges = HashMap::new();
+    for candidate in tiers.into_iter().flatten() {
+        let path = &candidate.section.file_path;
+        if candidate.section.language != "rust" || ranges.contains_key(path) {
+            continue;
+        }
+        let content = std::fs::read_to_string(options.root.join(path)).unwrap_or_default();
+        ranges.insert(
+            path.clone(),
+            related::inline_test_ranges(&content, &candidate.section.language),
+        );
+    }
+    ranges
+}
+
+/// Sections as related-code snippets, with paths relative to the root.
+fn to_snippets(options: &CollectCommandOptions, candidates: &[Candidate]) -> Vec<Snippet> {
+    candidates
+        .iter()
+        .map(|c| Snippet {
+            file_path: scanner::relative_to_root(&c.section.file_path, &options.root),
+            start_line: c.start_line,
+            end_line: c.end_line,
+            content: c.section.content.clone(),
+            reason: c.section.reason.clone(),
+        })
+        .collect()
+}
+
+/// Related-code snippets as candidates with no matches of their own.
+fn from_snippets(snippets: Vec<Snippet>) -> Vec<Candidate> {
+    snippets
+        .into_iter()
+        .map(|s| Candidate {
+            section: BundleSection {
+                language: utils::infer_language(&s.file_path),
+                file_path: s.file_path,
+                content: s.content,
+                reason: s.reason,
+            },
+            start_line: s.start_line,
+            end_line: s.end_line,
+            match_count: 0,
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// collect --include-imports
+// ---------------------------------------------------------------------------
+
+/// A file's top-level import statements: each one's line range and text.
+type ImportHeader = Vec<(usize, usize, String)>;
+
+/// Read the import header of every file with a ranked section
+/// (`--include-imports`).
+fn import_headers(
+    options: &CollectCommandOptions,
+    ranked: &[ScoredSnippet],
+) -> BTreeMap<String, ImportHeader> {
+    let mut headers = BTreeMap::new();
+    for scored in ranked {
+        let file_path = &scored.section.file_path;
+        if headers.contains_key(file_path) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(options.root.join(file_path)) else {
+            continue;
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let header: ImportHeader = imports::import_header(&content, &scored.section.language)
+            .into_iter()
+            .map(|(start, end)| {
+                let end = end.min(lines.len());
+                (start, end, lines[start - 1..end].join("\n"))
+            })
+            .collect();
+        headers.insert(file_path.clone(), header);
+    }
+    headers
+}
+
+/// The import header to put before `scored`, as its own section.
+///
+/// Only import lines above the section are used, so a section that
+/// already starts at the header gets none.
+fn header_snippet(header: &ImportHeader, scored: &ScoredSnippet) -> Option<ScoredSnippet> {
+    let above: Vec<&(usize, usize, String)> = header
+        .iter()
+        .filter(|(_, end, _)| *end < scored.start_line)
+        .collect();
+    let (first, last) = (above.first()?, above.last()?);
+    let content: Vec<&str> = above.iter().map(|(_, _, text)| text.as_str()).collect();
+    Some(ScoredSnippet {
+        section: BundleSection {
+            file_path: scored.section.file_path.clone(),
+            language: scored.section.language.clone(),
+            content: content.join("\n"),
+            reason: "imports".to_string(),
+        },
+        start_line: first.0,
+        end_line: last.1,
+        score: scored.score,
+        signals: scored.signals.clone(),
+    })
 }
 
 // ---------------------------------------------------------------------------
@@ -445,26 +1352,83 @@ fn collect_symbol(
 
 /// Compute merged line ranges around grep matches with context.
 ///
-/// Each match expands by `context_lines` above and below, then
-/// overlapping ranges are merged. Returns 1-based inclusive ranges.
+/// With [`MatchContext::Lines`] each match expands by that many lines
+/// above and below; with [`MatchContext::Unit`] it grows to its enclosing
+/// function, impl/class or block, falling back to
+/// [`UNIT_FALLBACK_LINES`] lines when no unit is found. Overlapping
+/// ranges are then merged. Returns 1-based inclusive ranges.
 fn compute_match_ranges(
     matches: &[&indexer::TextMatch],
-    context_lines: usize,
-    total_lines: usize,
+    context: MatchContext,
+    content: &str,
+    file_path: &str,
 ) -> Vec<(usize, usize)> {
-    let mut ranges: Vec<(usize, usize)> = matches
-        .iter()
-        .map(|m| {
-            let start = m.line_number.saturating_sub(context_lines).max(1);
-            let end = (m.line_number + context_lines).min(total_lines);
-            (start, end)
-        })
-        .collect();
+    let total_lines = content.lines().count();
+    let window = |line: usize, lines: usize| {
+        let start = line.saturating_sub(lines).max(1);
+        let end = (line + lines).min(total_lines);
+        (start, end)
+    };
+
+    let mut ranges: Vec<(usize, usize)> = match context {
+        MatchContext::Lines(lines) => matches
+            .iter()
+            .map(|m| window(m.line_number, lines))
+            .collect(),
+        MatchContext::Unit => {
+            let finder = UnitFinder::new(content, &utils::infer_language(file_path));
+            matches
+                .iter()
+                .map(|m| match finder.enclosing(m.line_number) {
+                    Some(unit) => (unit.start_line, unit.end_line),
+                    None => window(m.line_number, UNIT_FALLBACK_LINES),
+                })
+                .collect()
+        }
+    };
 
     ranges.sort_by_key(|&(s, _)| s);
     merge_overlapping_ranges(ranges)
 }
 
+/// Keep at most `max` windows, densest (matches per line) first.
+///
+/// Ties go to the window with more matches, then the earlier one. The
+/// kept windows are returned in line order together with the number
+/// dropped.
+fn cap_windows(
+    ranges: Vec<(usize, usize)>,
+    matches: &[&indexer::TextMatch],
+    max: Option<usize>,
+) -> (Vec<(usize, usize)>, usize) {
+    let max = match max {
+        Some(max) if ranges.len() > max => max,
+        _ => return (ranges, 0),
+    };
+
+    let count_in = |&(start, end): &(usize, usize)| {
+        matches
+            .iter()
+            .filter(|m| m.line_number >= start && m.line_number <= end)
+            .count()
+    };
+    let mut ranked: Vec<((usize, usize), usize)> =
+        ranges.iter().map(|r| (*r, count_in(r))).collect();
+    ranked.sort_by(|(a, a_count), (b, b_count)| {
+        let a_density = *a_count as f64 / (a.1 - a.0 + 1) as f64;
+        let b_density = *b_count as f64 / (b.1 - b.0 + 1) as f64;
+        b_density
+            .total_cmp(&a_density)
+            .then(b_count.cmp(a_count))
+            .then(a.0.cmp(&b.0))
+    });
+
+    let dropped = ranked.len() - max;
+    let mut kept: Vec<(usize, usize)> = ranked.into_iter().take(max).map(|(r, _)| r).collect();
+    kept.sort_unstable();
+    (kept, dropped)
+}
+
 /// Merge sorted ranges, combining overlapping or adjacent ones.
 fn merge_overlapping_ranges(sorted: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
     let mut merged: Vec<(usize, usize)> = Vec::new();
@@ -486,20 +1450,35 @@ fn merge_overlapping_ranges(sorted: Vec<(usize, usize)>) -> Vec<(usize, usize)>
 
 /// Apply budget constraints and build manifest entries.
 ///
-/// Same greedy algorithm as diff: always include at least one section,
-/// then greedily include sections until budget is exhausted.
+/// Same greedy algorithm as diff: walk sections in rank order, always
+/// include the highest-scoring one, then greedily include sections until
+/// the budget is exhausted. The first included section of a file with an
+/// import header is preceded by that header as an "imports" section,
+/// and the two only go in together.
 fn apply_budget(
-    sections: &[BundleSection],
+    ranked: &[ScoredSnippet],
+    headers: &BTreeMap<String, ImportHeader>,
     estimator: &dyn TokenEstimator,
     budget: Option<usize>,
 ) -> (Vec<BundleSection>, Vec<ManifestEntry>) {
     let mut included = Vec::new();
     let mut entries = Vec::new();
     let mut tokens_used: usize = 0;
+    let mut files_included = BTreeSet::new();
 
-    for (i, section) in sections.iter().enumerate() {
+    for scored in ranked {
+        let section = &scored.section;
+        let header = if files_included.contains(&section.file_path) {
+            None
+        } else {
+            headers
+                .get(&section.file_path)
+                .and_then(|header| header_snippet(header, scored))
+        };
+        let header_tokens = header
+            .as_ref()
+            .map_or(0, |h| estimator.estimate(&h.section.content));
         let token_est = estimator.estimate(&section.content);
-        let char_count = section.content.len();
 
         let is_included = match budget {
             None => true,
@@ -507,36 +1486,75 @@ fn apply_budget(
                 if included.is_empty() {
                     true
                 } else {
-                    tokens_used + token_est <= b
+                    tokens_used + header_tokens + token_est <= b
                 }
             }
         };
 
         if is_included {
+            if let Some(header) = header {
+                tokens_used += header_tokens;
+                included.push(header.section.clone());
+                entries.push(manifest_entry(&header, header_tokens, true));
+            }
             tokens_used += token_est;
             included.push(section.clone());
+            files_included.insert(section.file_path.clone());
         }
 
-        entries.push(ManifestEntry {
-            file_path: section.file_path.clone(),
-            start_line: 0,
-            end_line: 0,
-            token_estimate: token_est,
-            char_count,
-            reason: section.reason.clone(),
-            score: (sections.len() - i) as f64,
-            included: is_included,
-            language: section.language.clone(),
-        });
+        entries.push(manifest_entry(scored, token_est, is_included));
     }
 
     (included, entries)
 }
 
+/// Build the manifest entry for a ranked section.
+fn manifest_entry(scored: &ScoredSnippet, token_estimate: usize, included: bool) -> ManifestEntry {
+    let section = &scored.section;
+    ManifestEntry {
+        file_path: section.file_path.clone(),
+        start_line: scored.start_line,
+        end_line: scored.end_line,
+        token_estimate,
+        char_count: section.content.len(),
+        reason: section.reason.clone(),
+        score: scored.score,
+        included,
+        language: section.language.clone(),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Config loading
 // ---------------------------------------------------------------------------
 
+/// Resolve the ranking strategy: `--rank` first, then `ranking.strategy`.
+fn resolve_strategy(
+    options: &CollectCommandOptions,
+    config: &Config,
+) -> Result<Box<dyn RankingStrategy>> {
+    match options.rank.as_deref() {
+        Some(name) => ranker::strategy_by_name(name).ok_or_else(|| {
+            ContextSmithError::validation(
+                "rank",
+                format!(
+                    "unknown strategy '{name}'; expected one of {}",
+                    ranker::STRATEGY_NAMES.join(", ")
+                ),
+            )
+        }),
+        None => Ok(ranker::configured_strategy(config)),
+    }
+}
+
+/// Resolve the model name: `--model` first, then the config default.
+fn resolve_model(options: &CollectCommandOptions, config: &Config) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| config.default_model.clone())
+}
+
 /// Load config from explicit path or discovery.
 fn load_config(options: &CollectCommandOptions) -> Result<Config> {
     let config_path = crate::config::find_config_file(options.config_path.as_deref());
@@ -553,19 +1571,46 @@ fn load_config(options: &CollectCommandOptions) -> Result<Config> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ranker::SignalScores;
+    use crate::tokens;
+
+    /// Wrap sections as already-ranked snippets, preserving their order.
+    fn ranked(sections: Vec<BundleSection>) -> Vec<ScoredSnippet> {
+        let n = sections.len();
+        sections
+            .into_iter()
+            .enumerate()
+            .map(|(i, section)| ScoredSnippet {
+                section,
+                start_line: 0,
+                end_line: 0,
+                score: (n - i) as f64,
+                signals: SignalScores::default(),
+            })
+            .collect()
+    }
 
     #[test]
     fn validate_mode_requires_at_least_one() {
         let options = CollectCommandOptions {
             root: PathBuf::from("/tmp"),
+            scope: None,
+            query: None,
             files: vec![],
             grep: None,
             symbol: None,
             exclude: vec![],
             lang: None,
             path: None,
-            context_lines: 3,
+            span: vec![],
+            context: MatchContext::Lines(3),
             max_files: None,
+            max_snippets: None,
+            include_defs: false,
+            include_refs: false,
+            include_imports: false,
+            tests: false,
+            rank: None,
             format: OutputFormat::Markdown,
             out: None,
             stdout: true,
@@ -573,23 +1618,35 @@ mod tests {
             budget: None,
             model: None,
             config_path: None,
-            ignored_flags_used: vec![],
+            no_cache: false,
+            cache_dir: None,
+            diff: None,
+            diff_only: false,
         };
-        assert!(validate_mode(&options).is_err());
+        assert!(validate_modes(&options, &[]).is_err());
     }
 
     #[test]
     fn validate_mode_files() {
         let options = CollectCommandOptions {
             root: PathBuf::from("/tmp"),
+            scope: None,
+            query: None,
             files: vec![PathBuf::from("main.rs")],
             grep: None,
             symbol: None,
             exclude: vec![],
             lang: None,
             path: None,
-            context_lines: 3,
+            span: vec![],
+            context: MatchContext::Lines(3),
             max_files: None,
+            max_snippets: None,
+            include_defs: false,
+            include_refs: false,
+            include_imports: false,
+            tests: false,
+            rank: None,
             format: OutputFormat::Markdown,
             out: None,
             stdout: true,
@@ -597,26 +1654,38 @@ mod tests {
             budget: None,
             model: None,
             config_path: None,
-            ignored_flags_used: vec![],
+            no_cache: false,
+            cache_dir: None,
+            diff: None,
+            diff_only: false,
         };
-        assert!(matches!(
-            validate_mode(&options).unwrap(),
-            CollectMode::Files
-        ));
+        assert_eq!(
+            validate_modes(&options, &[]).unwrap(),
+            vec![CollectMode::Files]
+        );
     }
 
     #[test]
     fn validate_mode_grep() {
         let options = CollectCommandOptions {
             root: PathBuf::from("/tmp"),
+            scope: None,
+            query: None,
             files: vec![],
             grep: Some("pattern".to_string()),
             symbol: None,
             exclude: vec![],
             lang: None,
             path: None,
-            context_lines: 3,
+            span: vec![],
+            context: MatchContext::Lines(3),
             max_files: None,
+            max_snippets: None,
+            include_defs: false,
+            include_refs: false,
+            include_imports: false,
+            tests: false,
+            rank: None,
             format: OutputFormat::Markdown,
             out: None,
             stdout: true,
@@ -624,12 +1693,140 @@ mod tests {
             budget: None,
             model: None,
             config_path: None,
-            ignored_flags_used: vec![],
+            no_cache: false,
+            cache_dir: None,
+            diff: None,
+            diff_only: false,
         };
-        assert!(matches!(
-            validate_mode(&options).unwrap(),
-            CollectMode::Grep
-        ));
+        assert_eq!(
+            validate_modes(&options, &[]).unwrap(),
+            vec![CollectMode::Grep]
+        );
+    }
+
+    #[test]
+    fn validate_modes_runs_every_mode() {
+        let options = CollectCommandOptions {
+            root: PathBuf::from("/tmp"),
+            scope: Some("src".to_string()),
+            query: Some("budget plan".to_string()),
+            files: vec![PathBuf::from("main.rs")],
+            grep: Some("pattern".to_string()),
+            symbol: Some("Config".to_string()),
+            exclude: vec![],
+            lang: None,
+            path: None,
+            span: vec![],
+            context: MatchContext::Lines(3),
+            max_files: None,
+            max_snippets: None,
+            include_defs: false,
+            include_refs: false,
+            include_imports: false,
+            tests: false,
+            rank: None,
+            format: OutputFormat::Markdown,
+            out: None,
+            stdout: true,
+            quiet: true,
+            budget: None,
+            model: None,
+            config_path: None,
+            no_cache: false,
+            cache_dir: None,
+            diff: None,
+            diff_only: false,
+        };
+        assert_eq!(
+            validate_modes(&options, &[]).unwrap(),
+            vec![
+                CollectMode::Files,
+                CollectMode::Query,
+                CollectMode::Grep,
+                CollectMode::Symbol
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_span_forms() {
+        assert_eq!(
+            parse_span("10:50").unwrap(),
+            LineSpan {
+                path: None,
+                start: 10,
+                end: 50
+            }
+        );
+        assert_eq!(parse_span("10-50").unwrap(), parse_span("10:50").unwrap());
+        assert_eq!(
+            parse_span("src/a.rs:120-180").unwrap(),
+            LineSpan {
+                path: Some(PathBuf::from("src/a.rs")),
+                start: 120,
+                end: 180
+            }
+        );
+        assert_eq!(parse_span("7:7").unwrap().end, 7);
+    }
+
+    #[test]
+    fn parse_span_rejects_bad_ranges() {
+        for bad in ["", "10", "a:b", "src/a.rs:10:50", "0:5", "50:10"] {
+            let err = parse_span(bad).unwrap_err();
+            assert!(err.to_string().contains("'span'"), "{bad}: {err}");
+        }
+    }
+
+    fn text_match(line_number: usize) -> indexer::TextMatch {
+        indexer::TextMatch {
+            file_path: "a.rs".to_string(),
+            line_number,
+            line_content: String::new(),
+            column: 0,
+            match_length: 1,
+        }
+    }
+
+    #[test]
+    fn cap_windows_keeps_densest() {
+        let matches: Vec<indexer::TextMatch> = [5, 40, 42, 44, 80, 82]
+            .into_iter()
+            .map(text_match)
+            .collect();
+        let refs: Vec<&indexer::TextMatch> = matches.iter().collect();
+        let ranges = vec![(2, 8), (37, 47), (77, 85)];
+
+        let (kept, dropped) = cap_windows(ranges.clone(), &refs, Some(2));
+        // (37, 47) has 3 matches in 11 lines, (77, 85) 2 in 9, (2, 8) 1 in 7.
+        assert_eq!(kept, vec![(37, 47), (77, 85)]);
+        assert_eq!(dropped, 1);
+
+        assert_eq!(
+            cap_windows(ranges.clone(), &refs, None),
+            (ranges.clone(), 0)
+        );
+        assert_eq!(cap_windows(ranges.clone(), &refs, Some(5)), (ranges, 0));
+    }
+
+    #[test]
+    fn unit_context_grows_matches_to_enclosing_fn() {
+        let content = "use std::io;\n\nfn load() {\n    let a = 1;\n    let b = 2;\n}\n";
+        let matches = [text_match(1), text_match(4), text_match(5)];
+        let refs: Vec<&indexer::TextMatch> = matches.iter().collect();
+
+        assert_eq!(
+            compute_match_ranges(&refs, MatchContext::Unit, content, "a.rs"),
+            vec![(1, 6)]
+        );
+        assert_eq!(
+            compute_match_ranges(&refs[1..], MatchContext::Unit, content, "a.rs"),
+            vec![(3, 6)]
+        );
+        assert_eq!(
+            compute_match_ranges(&refs[1..], MatchContext::Lines(0), content, "a.rs"),
+            vec![(4, 5)]
+        );
     }
 
     #[test]
@@ -663,7 +1860,8 @@ mod tests {
             },
         ];
         let estimator = tokens::default_estimator();
-        let (included, entries) = apply_budget(&sections, &estimator, None);
+        let (included, entries) =
+            apply_budget(&ranked(sections), &BTreeMap::new(), &estimator, None);
         assert_eq!(included.len(), 2);
         assert!(entries.iter().all(|e| e.included));
     }
@@ -686,8 +1884,44 @@ mod tests {
         ];
         let estimator = tokens::default_estimator();
         // Budget 8: first section fits (8 tokens), second exceeds (8+9=17 > 8).
-        let (included, _) = apply_budget(&sections, &estimator, Some(8));
+        let (included, _) = apply_budget(&ranked(sections), &BTreeMap::new(), &estimator, Some(8));
         assert_eq!(included.len(), 1);
         assert_eq!(included[0].file_path, "a.rs");
     }
+
+    #[test]
+    fn apply_budget_puts_header_before_first_included_section() {
+        let mut ranked = ranked(vec![
+            BundleSection {
+                file_path: "b.rs".to_string(),
+                language: "rust".to_string(),
+                content: "fn b() { do_something(); }".to_string(), // 26 chars = 7 tokens
+                reason: "test".to_string(),
+            },
+            BundleSection {
+                file_path: "a.rs".to_string(),
+                language: "rust".to_string(),
+                content: "fn a() {}".to_string(), // 9 chars = 3 tokens
+                reason: "test".to_string(),
+            },
+        ]);
+        ranked[1].start_line = 10;
+        ranked[1].end_line = 10;
+        let headers = BTreeMap::from([(
+            "a.rs".to_string(),
+            vec![(1, 1, "use crate::config::Config;".to_string())], // 7 tokens
+        )]);
+        let estimator = tokens::default_estimator();
+
+        let (included, entries) = apply_budget(&ranked, &headers, &estimator, None);
+        let reasons: Vec<&str> = included.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(reasons, vec!["test", "imports", "test"]);
+        assert_eq!((entries[1].start_line, entries[1].end_line), (1, 1));
+        assert_eq!(entries[1].token_estimate, 7);
+
+        // a.rs alone would fit, but not together with its header.
+        let (included, entries) = apply_budget(&ranked, &headers, &estimator, Some(12));
+        assert_eq!(included.len(), 1);
+        assert!(entries.iter().all(|e| e.reason != "imports"));
+    }
 }
@@ -4,20 +4,23 @@
 //! context around each hunk, builds an output bundle, and writes the
 //! result in the user's chosen format.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use colored::Colorize;
-use tracing::warn;
 
 use crate::cli::OutputFormat;
-use crate::config::Config;
-use crate::error::Result;
+use crate::config::{Config, RankingWeights};
+use crate::error::{ContextSmithError, Result};
 use crate::git::{self, DiffOptions, FileStatus};
 use crate::manifest::{self, ManifestEntry, WeightsUsed};
+use crate::models::ModelRegistry;
 use crate::output::{self, Bundle, BundleSection, FormatOptions};
-use crate::ranker;
+use crate::ranker::{self, Candidate, RankContext, RankingStrategy};
+use crate::related::{self, RelatedOptions};
+use crate::scanner;
 use crate::slicer::{self, SliceOptions, Snippet};
-use crate::tokens::{self, TokenEstimator};
+use crate::tokens;
 use crate::utils;
 
 // ---------------------------------------------------------------------------
@@ -31,6 +34,10 @@ pub struct DiffCommandOptions {
     pub root: PathBuf,
     /// Optional revision range (e.g. "HEAD~3..HEAD").
     pub rev_range: Option<String>,
+    /// Diff from the merge-base of `HEAD` and this branch.
+    pub base: Option<String>,
+    /// With `base`: include staged and unstaged work on top of `HEAD`.
+    pub worktree: bool,
     /// Diff only staged changes.
     pub staged: bool,
     /// Include untracked files.
@@ -41,7 +48,15 @@ pub struct DiffCommandOptions {
     pub hunks_only: bool,
     /// Number of context lines around each hunk.
     pub context_lines: usize,
-    /// Pull in related symbols (currently stubbed).
+    /// Grow each hunk to its enclosing function or method.
+    pub function_context: bool,
+    /// Pair each changed region with its pre-change lines.
+    pub show_old: bool,
+    /// Add a commit-message section per commit in the range.
+    pub commits: bool,
+    /// With `commits`: place each snippet under the commit that introduced it.
+    pub group_by_commit: bool,
+    /// Pull in callers, callees and tests of the changed code.
     pub include_related: bool,
     /// Output format.
     pub format: OutputFormat,
@@ -52,8 +67,9 @@ pub struct DiffCommandOptions {
     /// Suppress non-essential output.
     pub quiet: bool,
     /// Token budget — if set, greedily include snippets until budget fills.
+    /// Derived from the model's context window when only `--model` is given.
     pub budget: Option<usize>,
-    /// Model name for token estimation.
+    /// Model name for token estimation (falls back to `default_model`).
     pub model: Option<String>,
     /// Path to config file.
     pub config_path: Option<PathBuf>,
@@ -61,22 +77,21 @@ pub struct DiffCommandOptions {
 
 /// Run the diff command end-to-end.
 pub fn run(options: DiffCommandOptions) -> Result<()> {
-    // Warn about stubbed functionality.
-    if options.include_related {
-        warn!("--include-related is not yet implemented; ignoring");
-    }
-
-    // Step 1: Load config for ranking weights.
+    // Step 1: Load config for ranking weights and the default model.
     let config = load_config(&options)?;
+    let model = resolve_model(&options, &config);
 
     // Step 2: Get parsed diff from git.
-    let diff_files = git::get_diff(&DiffOptions {
+    let diff_options = DiffOptions {
         root: options.root.clone(),
-        rev_range: options.rev_range,
+        rev_range: options.rev_range.clone(),
         staged: options.staged,
         untracked: options.untracked,
-        since: options.since,
-    })?;
+        since: options.since.clone(),
+        base: options.base.clone(),
+        worktree: options.worktree,
+    };
+    let diff_files = git::get_diff(&diff_options)?;
 
     if diff_files.is_empty() {
         if !options.quiet {
@@ -86,29 +101,109 @@ pub fn run(options: DiffCommandOptions) -> Result<()> {
     }
 
     // Step 3: Slice context around hunks.
-    let snippets = slicer::slice_diff_hunks(
+    let mut snippets = slicer::slice_diff_hunks(
         &diff_files,
         &SliceOptions {
             context_lines: options.context_lines,
             hunks_only: options.hunks_only,
-            root: options.root,
+            function_context: options.function_context,
+            root: options.root.clone(),
         },
     )?;
-    let weights = &config.ranking_weights;
+    // Step 3a: Pair each changed region with the old file's lines.
+    let mut befores = HashMap::new();
+    if options.show_old {
+        let rev = git::old_revision(&diff_options)?;
+        (snippets, befores) = attach_old_sides(&diff_files, snippets, &options.root, &rev)?;
+    }
+    let hunk_snippets = snippets.len();
 
-    // Step 4: Apply budget if set.
-    let model = options
-        .model
-        .as_deref()
-        .map(tokens::parse_model)
-        .unwrap_or(tokens::ModelFamily::Gpt4);
-    let estimator = tokens::CharEstimator::new(model);
+    // Step 3b: Pull in callers, callees and tests of the changed code.
+    if options.include_related {
+        let files = scanner::scan(&scanner::scan_options_from_config(&config, &options.root))?;
+        let related_snippets = related::find_related(
+            &diff_files,
+            &files,
+            &snippets,
+            &RelatedOptions {
+                context_lines: options.context_lines,
+            },
+        )?;
+        snippets.extend(related_snippets);
+    }
+    let weights = &config.ranking_weights;
 
-    let (included_snippets, manifest_entries) =
-        apply_budget_and_build_entries(&snippets, &estimator, options.budget, weights);
+    // Step 3c: Commit messages for the diffed range.
+    let commits = if options.commits {
+        git::commits_in_range(&diff_options)?.ok_or_else(|| {
+            ContextSmithError::validation(
+                "commits",
+                "needs a revision range, --since or --base (working-tree diffs have no commits)",
+            )
+        })?
+    } else {
+        Vec::new()
+    };
+    let commit_snippets: Vec<Snippet> = commits.iter().map(commit_snippet).collect();
+
+    // Step 4: Apply budget if set (or derived from --model). Commit
+    // messages are always included, so they come off the top.
+    let registry = ModelRegistry::from_config(&config);
+    let estimator = registry.estimator(&model);
+    let plan = registry.plan_budget(options.budget, None, options.model.as_deref());
+    let commit_tokens: usize = commit_snippets
+        .iter()
+        .map(|s| estimator.estimate(&s.content))
+        .sum();
+
+    let context = RankContext::from_diff(&diff_files).with_commit_times(&options.root);
+    let strategy = ranker::configured_strategy(&config);
+    let (included_snippets, manifest_entries) = apply_budget_and_build_entries(
+        &snippets,
+        hunk_snippets,
+        &befores,
+        &Ranking {
+            context: &context,
+            strategy: strategy.as_ref(),
+            weights,
+            estimator: &estimator,
+            budget: plan.effective().map(|b| b.saturating_sub(commit_tokens)),
+        },
+    );
+    let manifest_entries: Vec<ManifestEntry> = commit_snippets
+        .iter()
+        .map(|s| ManifestEntry {
+            file_path: s.file_path.clone(),
+            start_line: 0,
+            end_line: 0,
+            token_estimate: estimator.estimate(&s.content),
+            char_count: s.content.len(),
+            reason: s.reason.clone(),
+            score: 1.0,
+            included: true,
+            language: String::new(),
+        })
+        .chain(manifest_entries)
+        .collect();
 
-    // Step 5: Build a bundle from included snippets.
-    let bundle = build_bundle(&diff_files, included_snippets);
+    // Step 5: Build a bundle from included snippets, commit messages first
+    // (or each followed by the snippets it introduced).
+    let ordered = if options.group_by_commit {
+        group_by_commit(
+            &options.root,
+            git::new_revision(&diff_options).as_deref(),
+            &commits,
+            commit_snippets,
+            included_snippets,
+            &befores,
+        )
+    } else {
+        commit_snippets
+            .into_iter()
+            .chain(included_snippets)
+            .collect()
+    };
+    let bundle = build_bundle(&diff_files, commits.len(), ordered);
 
     // Step 6: Format and write output.
     let format = utils::cli_format_to_output_format(&options.format);
@@ -124,8 +219,7 @@ pub fn run(options: DiffCommandOptions) -> Result<()> {
 
     // Step 7: Write manifest as sibling file when --out is specified.
     if let Some(ref out_path) = options.out {
-        let mut m =
-            manifest::build_manifest(manifest_entries, estimator.model_name(), options.budget, 0);
+        let mut m = manifest::build_manifest(manifest_entries, &model, plan.budget, plan.reserve);
         m.summary.weights_used = Some(WeightsUsed {
             text: weights.text,
             diff: weights.diff,
@@ -133,6 +227,11 @@ pub fn run(options: DiffCommandOptions) -> Result<()> {
             proximity: weights.proximity,
             test: weights.test,
         });
+        m.summary.ranking_strategy = Some(strategy.name().to_string());
+        if let Some(ref base) = options.base {
+            m.summary.base_sha = Some(git::merge_base(&options.root, base)?);
+            m.summary.head_sha = Some(git::head_sha(&options.root)?);
+        }
         let manifest_path = utils::manifest_sibling_path(out_path);
         manifest::write_manifest(&m, &manifest_path)?;
         if !options.quiet {
@@ -146,8 +245,12 @@ pub fn run(options: DiffCommandOptions) -> Result<()> {
 
     // Step 8: Print summary to stderr (unless writing to stdout or quiet).
     if !options.quiet && !options.stdout {
-        let total_tokens: usize = manifest_entries_total_tokens(&snippets, &estimator);
-        print_summary(&diff_files, total_tokens, options.budget);
+        let total_tokens: usize = manifest_entries_total_tokens(&snippets, &estimator)
+            + befores
+                .values()
+                .map(|s| estimator.estimate(&s.content))
+                .sum::<usize>();
+        print_summary(&diff_files, total_tokens, plan.effective());
     }
 
     Ok(())
@@ -157,48 +260,121 @@ pub fn run(options: DiffCommandOptions) -> Result<()> {
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Apply budget constraints and build manifest entries for all snippets.
+/// A snippet's location: file path, first and last line.
+type SnippetKey = (String, usize, usize);
+
+/// How snippets are ranked and budgeted.
+struct Ranking<'a> {
+    /// Changed lines, commit times and focus paths.
+    context: &'a RankContext,
+    /// Strategy combining the signals into a score.
+    strategy: &'a dyn RankingStrategy,
+    /// Weight of each signal.
+    weights: &'a RankingWeights,
+    /// Estimator for snippet token counts.
+    estimator: &'a dyn tokens::TokenEstimator,
+    /// Token budget left for snippets, if any.
+    budget: Option<usize>,
+}
+
+/// Read the old side of each changed file and pair it with its snippets.
 ///
-/// Returns the included snippets and manifest entries for every snippet.
+/// Deleted files are replaced by one snippet holding the whole old file;
+/// added files have no old side. Returns the (relabelled) new-side
+/// snippets and the "before" snippet of each, keyed by its location.
+fn attach_old_sides(
+    diff_files: &[git::DiffFile],
+    snippets: Vec<Snippet>,
+    root: &std::path::Path,
+    rev: &str,
+) -> Result<(Vec<Snippet>, HashMap<SnippetKey, Snippet>)> {
+    let mut old_contents: HashMap<&str, String> = HashMap::new();
+    let mut paired = Vec::new();
+    let mut befores = HashMap::new();
+    for snippet in snippets {
+        let file = match diff_files.iter().find(|f| f.path == snippet.file_path) {
+            Some(f) if f.status != FileStatus::Added => f,
+            _ => {
+                paired.push(snippet);
+                continue;
+            }
+        };
+        let old_path = file.old_path.as_deref().unwrap_or(&file.path);
+        if !old_contents.contains_key(old_path) {
+            old_contents.insert(old_path, git::show_file(root, rev, old_path)?);
+        } else if file.status == FileStatus::Deleted {
+            // The whole old file is already in.
+            continue;
+        }
+        let old_content = &old_contents[old_path];
+
+        if file.status == FileStatus::Deleted {
+            paired.push(slicer::deleted_file_snippet(file, old_content));
+            continue;
+        }
+        let (before, after) = slicer::pair_with_old(file, snippet, old_content);
+        befores.insert(
+            (after.file_path.clone(), after.start_line, after.end_line),
+            before,
+        );
+        paired.push(after);
+    }
+    Ok((paired, befores))
+}
+
+/// Rank snippets, apply budget constraints and build manifest entries.
+///
+/// Returns the included snippets (in rank order) and manifest entries for
+/// every snippet. The first `hunk_snippets` snippets come from the diff
+/// itself and carry the text signal; the rest are related code. A snippet
+/// with an entry in `befores` brings it along: the pair is included or
+/// dropped together, "before" first.
 /// If no budget is set, all snippets are included.
 /// Always includes at least one snippet even if it exceeds the budget.
 fn apply_budget_and_build_entries(
     snippets: &[Snippet],
-    estimator: &dyn tokens::TokenEstimator,
-    budget: Option<usize>,
-    weights: &crate::config::RankingWeights,
+    hunk_snippets: usize,
+    befores: &HashMap<SnippetKey, Snippet>,
+    ranking: &Ranking,
 ) -> (Vec<Snippet>, Vec<ManifestEntry>) {
-    // Build sections for ranking.
-    let sections: Vec<BundleSection> = snippets
+    let Ranking {
+        context,
+        strategy,
+        weights,
+        estimator,
+        budget,
+    } = *ranking;
+    let candidates: Vec<Candidate> = snippets
         .iter()
-        .map(|s| BundleSection {
-            file_path: s.file_path.clone(),
-            language: utils::infer_language(&s.file_path),
-            content: s.content.clone(),
-            reason: s.reason.clone(),
+        .enumerate()
+        .map(|(i, s)| Candidate {
+            section: BundleSection {
+                file_path: s.file_path.clone(),
+                language: utils::infer_language(&s.file_path),
+                content: s.content.clone(),
+                reason: s.reason.clone(),
+            },
+            start_line: s.start_line,
+            end_line: s.end_line,
+            match_count: usize::from(i < hunk_snippets),
         })
         .collect();
-
-    // Each diff snippet gets a match count of 1 (uniform diff signal).
-    let match_counts: Vec<usize> = vec![1; snippets.len()];
-    let scored = ranker::rank_snippets(&sections, &match_counts, weights);
+    let scored = ranker::rank_candidates(&candidates, context, weights, strategy);
 
     let mut included = Vec::new();
     let mut entries = Vec::new();
     let mut tokens_used: usize = 0;
 
-    for (idx, scored_snippet) in scored.iter().enumerate() {
-        // Find the original snippet for start/end line info.
-        let original = snippets
-            .iter()
-            .find(|s| {
-                s.file_path == scored_snippet.section.file_path
-                    && s.content == scored_snippet.section.content
-            })
-            .unwrap_or(&snippets[idx.min(snippets.len() - 1)]);
-
-        let token_est = estimator.estimate(&scored_snippet.section.content);
-        let char_count = scored_snippet.section.content.len();
+    for scored_snippet in &scored {
+        let section = &scored_snippet.section;
+        let token_est = estimator.estimate(&section.content);
+        let char_count = section.content.len();
+        let before = befores.get(&(
+            section.file_path.clone(),
+            scored_snippet.start_line,
+            scored_snippet.end_line,
+        ));
+        let before_est = before.map_or(0, |b| estimator.estimate(&b.content));
 
         let is_included = match budget {
             None => true,
@@ -206,26 +382,49 @@ fn apply_budget_and_build_entries(
                 if included.is_empty() {
                     true
                 } else {
-                    tokens_used + token_est <= b
+                    tokens_used + before_est + token_est <= b
                 }
             }
         };
 
+        if let Some(before) = before {
+            if is_included {
+                included.push(before.clone());
+            }
+            entries.push(ManifestEntry {
+                file_path: before.file_path.clone(),
+                start_line: before.start_line,
+                end_line: before.end_line,
+                token_estimate: before_est,
+                char_count: before.content.len(),
+                reason: before.reason.clone(),
+                score: scored_snippet.score,
+                included: is_included,
+                language: utils::infer_language(&before.file_path),
+            });
+        }
+
         if is_included {
-            tokens_used += token_est;
-            included.push(original.clone());
+            tokens_used += before_est + token_est;
+            included.push(Snippet {
+                file_path: section.file_path.clone(),
+                start_line: scored_snippet.start_line,
+                end_line: scored_snippet.end_line,
+                content: section.content.clone(),
+                reason: section.reason.clone(),
+            });
         }
 
         entries.push(ManifestEntry {
-            file_path: scored_snippet.section.file_path.clone(),
-            start_line: original.start_line,
-            end_line: original.end_line,
+            file_path: section.file_path.clone(),
+            start_line: scored_snippet.start_line,
+            end_line: scored_snippet.end_line,
             token_estimate: token_est,
             char_count,
-            reason: scored_snippet.section.reason.clone(),
+            reason: section.reason.clone(),
             score: scored_snippet.score,
             included: is_included,
-            language: scored_snippet.section.language.clone(),
+            language: section.language.clone(),
         });
     }
 
@@ -243,8 +442,96 @@ fn manifest_entries_total_tokens(
         .sum()
 }
 
+/// A commit message as a section: subject, body and trailers.
+fn commit_snippet(commit: &git::Commit) -> Snippet {
+    let mut content = commit.subject.clone();
+    for part in [commit.body.clone(), commit.trailers.join("\n")] {
+        if !part.is_empty() {
+            content.push_str("\n\n");
+            content.push_str(&part);
+        }
+    }
+    Snippet {
+        file_path: format!("commit {}", &commit.sha[..commit.sha.len().min(7)]),
+        start_line: 0,
+        end_line: 0,
+        content,
+        reason: format!("commit by {} on {}", commit.author, commit.date),
+    }
+}
+
+/// Order sections as each commit message followed by the snippets it
+/// introduced, then the snippets no commit in the range accounts for.
+///
+/// A snippet belongs to the commit that last changed most of its lines
+/// (`git blame` at `rev`, the diff's new side, or the working tree when
+/// `rev` is `None`); its reason gains the short SHA. A "before" snippet
+/// stays with the snippet that follows it.
+fn group_by_commit(
+    root: &std::path::Path,
+    rev: Option<&str>,
+    commits: &[git::Commit],
+    commit_snippets: Vec<Snippet>,
+    snippets: Vec<Snippet>,
+    befores: &HashMap<SnippetKey, Snippet>,
+) -> Vec<Snippet> {
+    let mut groups: Vec<Vec<Snippet>> = vec![Vec::new(); commits.len()];
+    let mut ungrouped = Vec::new();
+    let mut pending_before: Option<Snippet> = None;
+
+    for mut snippet in snippets {
+        if befores.values().any(|b| *b == snippet) {
+            pending_before = Some(snippet);
+            continue;
+        }
+        let owner = git::blame_lines(
+            root,
+            rev,
+            &snippet.file_path,
+            snippet.start_line,
+            snippet.end_line,
+        )
+        .ok()
+        .and_then(|shas| {
+            let mut counts = vec![0usize; commits.len()];
+            for sha in &shas {
+                if let Some(i) = commits.iter().position(|c| c.sha == *sha) {
+                    counts[i] += 1;
+                }
+            }
+            // Most lines wins; ties go to the later commit.
+            (0..commits.len())
+                .filter(|&i| counts[i] > 0)
+                .max_by_key(|&i| (counts[i], i))
+        });
+
+        let target = match owner {
+            Some(i) => {
+                snippet.reason = format!("{} (commit {})", snippet.reason, &commits[i].sha[..7]);
+                &mut groups[i]
+            }
+            None => &mut ungrouped,
+        };
+        target.extend(pending_before.take());
+        target.push(snippet);
+    }
+
+    commit_snippets
+        .into_iter()
+        .zip(groups)
+        .flat_map(|(commit, group)| std::iter::once(commit).chain(group))
+        .chain(ungrouped)
+        .collect()
+}
+
 /// Build an output [`Bundle`] from diff files and extracted snippets.
-fn build_bundle(diff_files: &[git::DiffFile], snippets: Vec<Snippet>) -> Bundle {
+///
+/// `commit_count` of the snippets are commit messages.
+fn build_bundle(
+    diff_files: &[git::DiffFile],
+    commit_count: usize,
+    snippets: Vec<Snippet>,
+) -> Bundle {
     let file_count = diff_files.len();
     let hunk_count: usize = diff_files.iter().map(|f| f.hunks.len()).sum();
 
@@ -258,20 +545,40 @@ fn build_bundle(diff_files: &[git::DiffFile], snippets: Vec<Snippet>) -> Bundle
         })
         .collect();
 
+    let snippet_count = sections.len() - commit_count;
+    let commits = if commit_count > 0 {
+        format!(
+            ", {} commit{}",
+            commit_count,
+            if commit_count == 1 { "" } else { "s" }
+        )
+    } else {
+        String::new()
+    };
+
     Bundle {
         summary: format!(
-            "{} file{} changed, {} hunk{}, {} snippet{}",
+            "{} file{} changed, {} hunk{}{}, {} snippet{}",
             file_count,
             if file_count == 1 { "" } else { "s" },
             hunk_count,
             if hunk_count == 1 { "" } else { "s" },
-            sections.len(),
-            if sections.len() == 1 { "" } else { "s" },
+            commits,
+            snippet_count,
+            if snippet_count == 1 { "" } else { "s" },
         ),
         sections,
     }
 }
 
+/// Resolve the model name: `--model` first, then the config default.
+fn resolve_model(options: &DiffCommandOptions, config: &Config) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| config.default_model.clone())
+}
+
 /// Load config from explicit path or discovery.
 fn load_config(options: &DiffCommandOptions) -> Result<Config> {
     let config_path = crate::config::find_config_file(options.config_path.as_deref());
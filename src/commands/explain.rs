@@ -42,9 +42,13 @@ pub fn run(options: ExplainCommandOptions) -> Result<()> {
         print_weights(&manifest);
     }
 
-    // Step 3: Sort entries by score descending.
+    // Step 3: Sort entries by score descending, unless a non-default
+    // strategy decided the order (then keep the manifest's order).
     let mut entries = manifest.entries.clone();
-    sort_entries_for_display(&mut entries);
+    let strategy = manifest.summary.ranking_strategy.as_deref();
+    if strategy.is_none_or(|s| s == crate::ranker::DEFAULT_STRATEGY) {
+        sort_entries_for_display(&mut entries);
+    }
 
     // Limit to top N if requested.
     if let Some(top) = options.top {
@@ -104,6 +108,28 @@ pub fn run(options: ExplainCommandOptions) -> Result<()> {
     if summary.reserve_tokens > 0 {
         println!("  reserve: {} tokens", summary.reserve_tokens);
     }
+    if let Some(ref strategy) = summary.ranking_strategy {
+        println!("  ranking: {strategy}");
+    }
+    if let (Some(base), Some(head)) = (&summary.base_sha, &summary.head_sha) {
+        println!("  diff: {base}..{head}");
+    }
+    if !summary.dropped_windows.is_empty() {
+        let per_file: Vec<String> = summary
+            .dropped_windows
+            .iter()
+            .map(|(path, count)| format!("{path} ({count})"))
+            .collect();
+        println!("  dropped windows: {}", per_file.join(", "));
+    }
+    if !summary.references.is_empty() {
+        let per_file: Vec<String> = summary
+            .references
+            .iter()
+            .map(|(path, count)| format!("{path} ({count})"))
+            .collect();
+        println!("  references: {}", per_file.join(", "));
+    }
 
     Ok(())
 }
@@ -57,7 +57,7 @@ pub fn run(options: InitOptions) -> Result<InitResult> {
     let mut cache_dir = None;
     let mut created_cache = false;
     if !options.no_cache {
-        let cache = options.root.join(".contextsmith").join("cache");
+        let cache = crate::cache::default_dir(&options.root);
         create_dir_if_needed(&cache)?;
         cache_dir = Some(cache);
         created_cache = true;
@@ -0,0 +1,442 @@
+//! Handler for the `contextsmith map` command.
+//!
+//! Builds a compact map of the repository: a token-annotated file tree,
+//! optionally followed by per-file symbol outlines (`--symbols`), the
+//! import dependency graph (`--graph`) and full file contents (`--full`).
+//! With `--watch`, the map is regenerated whenever the scanned files change.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use colored::Colorize;
+
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::error::Result;
+use crate::imports;
+use crate::output::{self, Bundle, BundleSection, Format, FormatOptions};
+use crate::scanner::{self, ScannedFile};
+use crate::symbols;
+use crate::tokens::{self, TokenEstimator};
+use crate::utils;
+
+/// Pseudo-path used for the file tree section.
+const TREE_PATH: &str = "PROJECT TREE";
+
+/// Pseudo-path used for the dependency graph section.
+const GRAPH_PATH: &str = "DEPENDENCY GRAPH";
+
+/// How often `--watch` polls the file system for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// ---------------------------------------------------------------------------
+// Public interface
+// ---------------------------------------------------------------------------
+
+/// All inputs needed to run the map command.
+#[derive(Debug)]
+pub struct MapCommandOptions {
+    /// Repository root directory.
+    pub root: PathBuf,
+    /// Include full file contents.
+    pub full: bool,
+    /// Force plain-text output regardless of `format`.
+    pub text: bool,
+    /// Include a per-file symbol outline.
+    pub symbols: bool,
+    /// Include the import dependency graph.
+    pub graph: bool,
+    /// Output format.
+    pub format: OutputFormat,
+    /// Write output to file (stdout when absent).
+    pub out: Option<PathBuf>,
+    /// Regenerate the map whenever files change.
+    pub watch: bool,
+    /// Suppress non-essential output.
+    pub quiet: bool,
+    /// Path to config file.
+    pub config_path: Option<PathBuf>,
+}
+
+/// Run the map command.
+pub fn run(options: MapCommandOptions) -> Result<()> {
+    // Step 1: Load config (for scanner options).
+    let config = load_config(&options)?;
+    let scan_options = scanner::scan_options_from_config(&config, &options.root);
+
+    // Step 2: Scan, render and write once.
+    let mut files = scanner::scan(&scan_options)?;
+    render_and_write(&files, &options)?;
+
+    if !options.watch {
+        return Ok(());
+    }
+
+    // Step 3: In watch mode, poll for changes and re-render.
+    if !options.quiet {
+        eprintln!(
+            "{} watching {} for changes (Ctrl-C to stop)",
+            "map:".green().bold(),
+            options.root.display()
+        );
+    }
+    let mut last = fingerprint(&files);
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let rescanned = scanner::scan(&scan_options)?;
+        let current = fingerprint(&rescanned);
+        if current != last {
+            files = rescanned;
+            last = current;
+            render_and_write(&files, &options)?;
+        }
+    }
+}
+
+/// Build the map bundle for `files`, format it and write it out.
+fn render_and_write(files: &[ScannedFile], options: &MapCommandOptions) -> Result<()> {
+    let estimator = tokens::estimator_for_model(tokens::DEFAULT_MODEL);
+    let bundle = build_map(files, options, &estimator);
+
+    let format = if options.text {
+        Format::Plain
+    } else {
+        utils::cli_format_to_output_format(&options.format)
+    };
+    let formatted = output::format_bundle(&bundle, format)?;
+    output::write_output(
+        &formatted,
+        &FormatOptions {
+            format,
+            stdout: options.out.is_none(),
+            out: options.out.clone(),
+        },
+    )?;
+
+    if !options.quiet {
+        if let Some(ref out_path) = options.out {
+            eprintln!(
+                "{} project map written to {}",
+                "map:".green().bold(),
+                out_path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Map building
+// ---------------------------------------------------------------------------
+
+/// A scanned file together with its content and token estimate.
+struct MappedFile<'a> {
+    file: &'a ScannedFile,
+    content: String,
+    tokens: usize,
+}
+
+/// Assemble all requested map sections into a bundle.
+fn build_map(
+    files: &[ScannedFile],
+    options: &MapCommandOptions,
+    estimator: &dyn TokenEstimator,
+) -> Bundle {
+    let mapped: Vec<MappedFile> = files
+        .iter()
+        .map(|file| {
+            let content = std::fs::read_to_string(&file.abs_path).unwrap_or_default();
+            let tokens = estimator.estimate(&content);
+            MappedFile {
+                file,
+                content,
+                tokens,
+            }
+        })
+        .collect();
+    let total_tokens: usize = mapped.iter().map(|m| m.tokens).sum();
+
+    let mut sections = vec![BundleSection {
+        file_path: TREE_PATH.to_string(),
+        language: String::new(),
+        content: render_tree(&root_label(&options.root), &mapped),
+        reason: "project tree".to_string(),
+    }];
+
+    if options.symbols {
+        sections.extend(
+            mapped
+                .iter()
+                .filter(|m| !m.file.is_generated)
+                .filter_map(|m| {
+                    let outline = render_outline(&symbols::extract_symbols(&m.content));
+                    (!outline.is_empty()).then(|| BundleSection {
+                        file_path: m.file.rel_path.clone(),
+                        language: String::new(),
+                        content: outline,
+                        reason: "symbol outline".to_string(),
+                    })
+                }),
+        );
+    }
+
+    if options.graph {
+        let graph = imports::dependency_graph(files);
+        sections.push(BundleSection {
+            file_path: GRAPH_PATH.to_string(),
+            language: String::new(),
+            content: render_graph(&graph),
+            reason: format!(
+                "dependency graph ({} file{} with imports)",
+                graph.len(),
+                if graph.len() == 1 { "" } else { "s" }
+            ),
+        });
+    }
+
+    if options.full {
+        sections.extend(
+            mapped
+                .iter()
+                .filter(|m| !m.file.is_generated && !m.content.is_empty())
+                .map(|m| BundleSection {
+                    file_path: m.file.rel_path.clone(),
+                    language: m.file.language.clone(),
+                    content: m.content.clone(),
+                    reason: format!("full file (~{} tokens)", m.tokens),
+                }),
+        );
+    }
+
+    Bundle {
+        summary: format!(
+            "Project map: {} file{}, ~{} tokens",
+            files.len(),
+            if files.len() == 1 { "" } else { "s" },
+            total_tokens
+        ),
+        sections,
+    }
+}
+
+/// Display name for the tree root (the directory's own name).
+fn root_label(root: &std::path::Path) -> String {
+    let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    canonical
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Tree rendering
+// ---------------------------------------------------------------------------
+
+/// A directory node in the rendered tree.
+#[derive(Default)]
+struct DirNode {
+    dirs: BTreeMap<String, DirNode>,
+    /// File name → (tokens, is_generated).
+    files: BTreeMap<String, (usize, bool)>,
+    tokens: usize,
+    file_count: usize,
+}
+
+/// Render a tree with per-file and per-directory token counts.
+fn render_tree(root_name: &str, mapped: &[MappedFile]) -> String {
+    let mut root = DirNode::default();
+    for m in mapped {
+        let mut node = &mut root;
+        node.tokens += m.tokens;
+        node.file_count += 1;
+        let mut parts: Vec<&str> = m.file.rel_path.split('/').collect();
+        let name = parts.pop().unwrap_or_default();
+        for part in parts {
+            node = node.dirs.entry(part.to_string()).or_default();
+            node.tokens += m.tokens;
+            node.file_count += 1;
+        }
+        node.files
+            .insert(name.to_string(), (m.tokens, m.file.is_generated));
+    }
+
+    let mut out = format!(
+        "{}/ (~{} tokens, {} file{})\n",
+        root_name,
+        root.tokens,
+        root.file_count,
+        if root.file_count == 1 { "" } else { "s" }
+    );
+    render_dir(&root, "", &mut out);
+    out
+}
+
+/// Render the children of `node`: directories first, then files.
+fn render_dir(node: &DirNode, prefix: &str, out: &mut String) {
+    let total = node.dirs.len() + node.files.len();
+    let mut index = 0;
+
+    for (name, child) in &node.dirs {
+        index += 1;
+        let last = index == total;
+        out.push_str(&format!(
+            "{}{}{}/ (~{} tokens, {} file{})\n",
+            prefix,
+            if last { "└── " } else { "├── " },
+            name,
+            child.tokens,
+            child.file_count,
+            if child.file_count == 1 { "" } else { "s" }
+        ));
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_dir(child, &child_prefix, out);
+    }
+
+    for (name, (tokens, generated)) in &node.files {
+        index += 1;
+        let last = index == total;
+        out.push_str(&format!(
+            "{}{}{} (~{} tokens){}\n",
+            prefix,
+            if last { "└── " } else { "├── " },
+            name,
+            tokens,
+            if *generated { " [generated]" } else { "" }
+        ));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Symbol and graph rendering
+// ---------------------------------------------------------------------------
+
+/// Render a symbol outline, one `kind name (line N)` per line.
+fn render_outline(symbols: &[symbols::SymbolInfo]) -> String {
+    let mut out = String::new();
+    for symbol in symbols {
+        out.push_str(&format!(
+            "{}{} {} (line {})\n",
+            " ".repeat(symbol.indent),
+            symbol.kind,
+            symbol.name,
+            symbol.line
+        ));
+    }
+    out
+}
+
+/// Render the dependency graph as `file -> import` adjacency lists.
+fn render_graph(graph: &imports::DependencyGraph) -> String {
+    if graph.is_empty() {
+        return "(no internal imports found)\n".to_string();
+    }
+    let mut out = String::new();
+    for (file, targets) in graph {
+        out.push_str(file);
+        out.push('\n');
+        for target in targets {
+            out.push_str(&format!("  -> {target}\n"));
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Watch support
+// ---------------------------------------------------------------------------
+
+/// Cheap change-detection key: path, size and modification time per file.
+fn fingerprint(files: &[ScannedFile]) -> Vec<(String, u64, Option<SystemTime>)> {
+    files
+        .iter()
+        .map(|f| {
+            let modified = std::fs::metadata(&f.abs_path)
+                .and_then(|m| m.modified())
+                .ok();
+            (f.rel_path.clone(), f.size, modified)
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Load config from explicit path or discovery.
+fn load_config(options: &MapCommandOptions) -> Result<Config> {
+    let config_path = crate::config::find_config_file(options.config_path.as_deref());
+    match config_path {
+        Some(p) => Config::load(&p),
+        None => Ok(Config::default()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanned(rel_path: &str, generated: bool) -> ScannedFile {
+        ScannedFile {
+            rel_path: rel_path.to_string(),
+            abs_path: PathBuf::from(rel_path),
+            language: "rust".to_string(),
+            is_generated: generated,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn tree_nests_directories_with_token_totals() {
+        let files = [
+            scanned("src/main.rs", false),
+            scanned("src/commands/map.rs", false),
+            scanned("Cargo.toml", false),
+            scanned("src/gen.pb.rs", true),
+        ];
+        let mapped: Vec<MappedFile> = files
+            .iter()
+            .map(|file| MappedFile {
+                file,
+                content: String::new(),
+                tokens: 10,
+            })
+            .collect();
+
+        let tree = render_tree("demo", &mapped);
+        let expected = "\
+demo/ (~40 tokens, 4 files)
+├── src/ (~30 tokens, 3 files)
+│   ├── commands/ (~10 tokens, 1 file)
+│   │   └── map.rs (~10 tokens)
+│   ├── gen.pb.rs (~10 tokens) [generated]
+│   └── main.rs (~10 tokens)
+└── Cargo.toml (~10 tokens)
+";
+        assert_eq!(tree, expected);
+    }
+
+    #[test]
+    fn graph_renders_adjacency_lists() {
+        let mut graph = imports::DependencyGraph::new();
+        graph.insert(
+            "src/main.rs".to_string(),
+            ["src/cli.rs".to_string(), "src/lib.rs".to_string()]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(
+            render_graph(&graph),
+            "src/main.rs\n  -> src/cli.rs\n  -> src/lib.rs\n"
+        );
+        assert_eq!(
+            render_graph(&imports::DependencyGraph::new()),
+            "(no internal imports found)\n"
+        );
+    }
+}
@@ -11,9 +11,11 @@ use colored::Colorize;
 use crate::cli::OutputFormat;
 use crate::config::Config;
 use crate::error::{ContextSmithError, Result};
-use crate::manifest::{self, ManifestEntry, WeightsUsed};
+use crate::manifest::{self, Manifest, ManifestEntry, WeightsUsed};
+use crate::models::ModelRegistry;
 use crate::output::{self, Bundle, BundleSection, FormatOptions};
-use crate::tokens::{self, TokenEstimator};
+use crate::ranker::{self, Candidate, RankContext, ScoredSnippet};
+use crate::tokens::TokenEstimator;
 use crate::utils;
 
 // ---------------------------------------------------------------------------
@@ -23,13 +25,15 @@ use crate::utils;
 /// All inputs needed to run the pack command.
 #[derive(Debug)]
 pub struct PackCommandOptions {
+    /// Project root the sections are ranked against.
+    pub root: PathBuf,
     /// Input JSON bundle file.
     pub bundle: Option<PathBuf>,
     /// Token budget.
     pub budget: Option<usize>,
     /// Character budget (alternative to token budget).
     pub chars: Option<usize>,
-    /// Model name for token estimation.
+    /// Model name for token estimation (falls back to `default_model`).
     pub model: Option<String>,
     /// Reserve tokens for model response.
     pub reserve: Option<usize>,
@@ -78,22 +82,19 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
     }
 
     // Step 2: Determine estimator and effective budget.
-    let model = options
-        .model
-        .as_deref()
-        .map(tokens::parse_model)
-        .unwrap_or(tokens::ModelFamily::Gpt4);
-    let estimator = tokens::CharEstimator::new(model);
-    let reserve = options.reserve.unwrap_or(0);
-
-    let effective_budget = options
-        .budget
-        .map(|b| b.saturating_sub(reserve))
-        .or_else(|| {
-            options
-                .chars
-                .map(|c| estimator.estimate(&"x".repeat(c)).saturating_sub(reserve))
-        });
+    let config = load_config(&options)?;
+    let model = resolve_model(&options, &config);
+    let registry = ModelRegistry::from_config(&config);
+    let estimator = registry.estimator(&model);
+    let chars_budget = options.chars.map(|c| registry.chars_to_tokens(&model, c));
+    let plan = registry.plan_budget(
+        options.budget.or(chars_budget),
+        options.reserve,
+        options.model.as_deref(),
+    );
+    let reserve = plan.reserve;
+
+    let effective_budget = plan.effective();
 
     // Step 3: Filter sections by --drop and --must.
     let drop_set: Vec<String> = options
@@ -107,16 +108,31 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
         .map(|p| p.to_string_lossy().to_string())
         .collect();
 
-    let filtered: Vec<&BundleSection> = input_bundle
-        .sections
-        .iter()
-        .filter(|s| !drop_set.iter().any(|d| s.file_path.contains(d.as_str())))
+    // Step 4: Rank sections against the repository at --root, with
+    // must-include files as proximity anchors. The text signal is seeded
+    // from the bundle's own manifest or, without one, its section order.
+    let previous = manifest::read_sibling_manifest(&bundle_path);
+    let candidates: Vec<Candidate> = seed_candidates(&input_bundle.sections, previous.as_ref())
+        .into_iter()
+        .filter(|c| {
+            !drop_set
+                .iter()
+                .any(|d| c.section.file_path.contains(d.as_str()))
+        })
         .collect();
-
-    // Step 4: Greedy packing.
-    let (included, entries) = greedy_pack(&filtered, &estimator, effective_budget, &must_set);
-
-    // Step 5: Build output bundle.
+    let context = RankContext::for_repo(&options.root).with_focus(must_set.iter().cloned());
+    let strategy = ranker::configured_strategy(&config);
+    let ranked = ranker::rank_candidates(
+        &candidates,
+        &context,
+        &config.ranking_weights,
+        strategy.as_ref(),
+    );
+
+    // Step 5: Greedy packing.
+    let (included, entries) = greedy_pack(&ranked, &estimator, effective_budget, &must_set);
+
+    // Step 6: Build output bundle.
     let output_bundle = Bundle {
         summary: format!(
             "{} section{} (packed from {})",
@@ -127,7 +143,7 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
         sections: included,
     };
 
-    // Step 6: Format and write.
+    // Step 7: Format and write.
     let format = utils::cli_format_to_output_format(&options.format);
     let formatted = output::format_bundle(&output_bundle, format)?;
     output::write_output(
@@ -139,16 +155,10 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
         },
     )?;
 
-    // Step 7: Write manifest alongside output.
+    // Step 8: Write manifest alongside output.
     if let Some(ref out_path) = options.out {
-        let config = load_config(&options)?;
         let weights = &config.ranking_weights;
-        let mut m = manifest::build_manifest(
-            entries.clone(),
-            estimator.model_name(),
-            options.budget,
-            reserve,
-        );
+        let mut m = manifest::build_manifest(entries.clone(), &model, plan.budget, reserve);
         m.summary.weights_used = Some(WeightsUsed {
             text: weights.text,
             diff: weights.diff,
@@ -156,6 +166,7 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
             proximity: weights.proximity,
             test: weights.test,
         });
+        m.summary.ranking_strategy = Some(strategy.name().to_string());
         let manifest_path = utils::manifest_sibling_path(out_path);
         manifest::write_manifest(&m, &manifest_path)?;
         if !options.quiet {
@@ -167,7 +178,7 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
         }
     }
 
-    // Step 8: Print summary.
+    // Step 9: Print summary.
     if !options.quiet && !options.stdout {
         let total_tokens: usize = entries
             .iter()
@@ -196,13 +207,55 @@ pub fn run(options: PackCommandOptions) -> Result<()> {
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Greedy pack sections into a budget.
+/// Scale applied to manifest scores (usually in `0..=1`) so they can
+/// stand in for match counts.
+const SCORE_SCALE: f64 = 1000.0;
+
+/// Build rank candidates for every bundle section.
+///
+/// When the manifest written with the bundle lines up with its sections
+/// (included entries, in bundle order), their scores seed the text
+/// signal and their line ranges the diff signal. Otherwise earlier
+/// sections count as more relevant, following the bundle's order.
+fn seed_candidates(sections: &[BundleSection], previous: Option<&Manifest>) -> Vec<Candidate> {
+    let prior: Vec<&ManifestEntry> = previous
+        .map(|m| m.entries.iter().filter(|e| e.included).collect())
+        .unwrap_or_default();
+    let aligned = prior.len() == sections.len()
+        && prior
+            .iter()
+            .zip(sections)
+            .all(|(e, s)| e.file_path == s.file_path);
+
+    let n = sections.len();
+    sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| {
+            let (start_line, end_line, match_count) = if aligned {
+                let entry = prior[i];
+                let seed = (entry.score.max(0.0) * SCORE_SCALE).round() as usize;
+                (entry.start_line, entry.end_line, seed)
+            } else {
+                (0, 0, n - i)
+            };
+            Candidate {
+                section: section.clone(),
+                start_line,
+                end_line,
+                match_count,
+            }
+        })
+        .collect()
+}
+
+/// Greedy pack ranked sections into a budget.
 ///
 /// Must-include sections go first (always included), then remaining
-/// sections in order until budget is exhausted. Always includes at
+/// sections in rank order until budget is exhausted. Always includes at
 /// least one section.
 fn greedy_pack(
-    sections: &[&BundleSection],
+    ranked: &[ScoredSnippet],
     estimator: &dyn TokenEstimator,
     budget: Option<usize>,
     must_paths: &[String],
@@ -212,20 +265,24 @@ fn greedy_pack(
     let mut tokens_used: usize = 0;
 
     // Separate must-include and optional sections.
-    let (must_sections, optional_sections): (Vec<&&BundleSection>, Vec<&&BundleSection>) = sections
-        .iter()
-        .partition(|s| must_paths.iter().any(|m| s.file_path.contains(m.as_str())));
+    let (must_sections, optional_sections): (Vec<&ScoredSnippet>, Vec<&ScoredSnippet>) =
+        ranked.iter().partition(|s| {
+            must_paths
+                .iter()
+                .any(|m| s.section.file_path.contains(m.as_str()))
+        });
 
     // Process must-include first.
-    for section in &must_sections {
-        let token_est = estimator.estimate(&section.content);
+    for scored in &must_sections {
+        let token_est = estimator.estimate(&scored.section.content);
         tokens_used += token_est;
-        included.push((**section).clone());
-        entries.push(make_entry(section, token_est, true, "must-include"));
+        included.push(scored.section.clone());
+        entries.push(make_entry(scored, token_est, true, "must-include"));
     }
 
     // Then optional sections with budget enforcement.
-    for section in &optional_sections {
+    for scored in &optional_sections {
+        let section = &scored.section;
         let token_est = estimator.estimate(&section.content);
 
         let is_included = match budget {
@@ -241,35 +298,44 @@ fn greedy_pack(
 
         if is_included {
             tokens_used += token_est;
-            included.push((**section).clone());
+            included.push(section.clone());
         }
 
-        entries.push(make_entry(section, token_est, is_included, &section.reason));
+        entries.push(make_entry(scored, token_est, is_included, &section.reason));
     }
 
     (included, entries)
 }
 
-/// Build a manifest entry from a bundle section.
+/// Build a manifest entry from a ranked section.
 fn make_entry(
-    section: &BundleSection,
+    scored: &ScoredSnippet,
     token_estimate: usize,
     included: bool,
     reason: &str,
 ) -> ManifestEntry {
+    let section = &scored.section;
     ManifestEntry {
         file_path: section.file_path.clone(),
-        start_line: 0,
-        end_line: 0,
+        start_line: scored.start_line,
+        end_line: scored.end_line,
         token_estimate,
         char_count: section.content.len(),
         reason: reason.to_string(),
-        score: 0.0,
+        score: scored.score,
         included,
         language: section.language.clone(),
     }
 }
 
+/// Resolve the model name: `--model` first, then the config default.
+fn resolve_model(options: &PackCommandOptions, config: &Config) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| config.default_model.clone())
+}
+
 /// Load config from explicit path or discovery.
 fn load_config(options: &PackCommandOptions) -> Result<Config> {
     let config_path = crate::config::find_config_file(options.config_path.as_deref());
@@ -286,6 +352,24 @@ fn load_config(options: &PackCommandOptions) -> Result<Config> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ranker::SignalScores;
+    use crate::tokens;
+
+    /// Wrap sections as already-ranked snippets, preserving their order.
+    fn ranked(sections: &[&BundleSection]) -> Vec<ScoredSnippet> {
+        let n = sections.len();
+        sections
+            .iter()
+            .enumerate()
+            .map(|(i, section)| ScoredSnippet {
+                section: (*section).clone(),
+                start_line: 0,
+                end_line: 0,
+                score: (n - i) as f64,
+                signals: SignalScores::default(),
+            })
+            .collect()
+    }
 
     fn sample_sections() -> Vec<BundleSection> {
         vec![
@@ -310,12 +394,49 @@ mod tests {
         ]
     }
 
+    #[test]
+    fn seed_candidates_follow_bundle_order_without_manifest() {
+        let sections = sample_sections();
+        let counts: Vec<usize> = seed_candidates(&sections, None)
+            .iter()
+            .map(|c| c.match_count)
+            .collect();
+        assert_eq!(counts, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn seed_candidates_carry_manifest_scores_and_lines() {
+        let sections = sample_sections();
+        let entries = sections
+            .iter()
+            .zip([0.2, 0.9, 0.5])
+            .enumerate()
+            .map(|(i, (s, score))| ManifestEntry {
+                file_path: s.file_path.clone(),
+                start_line: i + 1,
+                end_line: i + 10,
+                token_estimate: 1,
+                char_count: s.content.len(),
+                reason: s.reason.clone(),
+                score,
+                included: true,
+                language: s.language.clone(),
+            })
+            .collect();
+        let previous = manifest::build_manifest(entries, "gpt-4", None, 0);
+
+        let candidates = seed_candidates(&sections, Some(&previous));
+        assert!(candidates[1].match_count > candidates[2].match_count);
+        assert!(candidates[2].match_count > candidates[0].match_count);
+        assert_eq!((candidates[1].start_line, candidates[1].end_line), (2, 11));
+    }
+
     #[test]
     fn greedy_pack_no_budget_includes_all() {
         let sections = sample_sections();
         let refs: Vec<&BundleSection> = sections.iter().collect();
         let estimator = tokens::default_estimator();
-        let (included, entries) = greedy_pack(&refs, &estimator, None, &[]);
+        let (included, entries) = greedy_pack(&ranked(&refs), &estimator, None, &[]);
         assert_eq!(included.len(), 3);
         assert!(entries.iter().all(|e| e.included));
     }
@@ -327,7 +448,7 @@ mod tests {
         let estimator = tokens::default_estimator();
         // Budget of 10 tokens (~40 chars with GPT-4). First section is 33 chars = 9 tokens.
         // Second is 15 chars = 4 tokens. 9 + 4 = 13 > 10, so only first included.
-        let (included, entries) = greedy_pack(&refs, &estimator, Some(10), &[]);
+        let (included, entries) = greedy_pack(&ranked(&refs), &estimator, Some(10), &[]);
         assert_eq!(included.len(), 1);
         assert_eq!(included[0].file_path, "src/main.rs");
         assert_eq!(entries.iter().filter(|e| e.included).count(), 1);
@@ -339,7 +460,7 @@ mod tests {
         let refs: Vec<&BundleSection> = sections.iter().collect();
         let estimator = tokens::default_estimator();
         // Budget of 1 — still includes at least one.
-        let (included, _) = greedy_pack(&refs, &estimator, Some(1), &[]);
+        let (included, _) = greedy_pack(&ranked(&refs), &estimator, Some(1), &[]);
         assert!(!included.is_empty());
     }
 
@@ -350,7 +471,7 @@ mod tests {
         let estimator = tokens::default_estimator();
         let must = vec!["tests/test.rs".to_string()];
         // Tight budget: must-include goes first, then greedy.
-        let (included, entries) = greedy_pack(&refs, &estimator, Some(12), &must);
+        let (included, entries) = greedy_pack(&ranked(&refs), &estimator, Some(12), &must);
         // test.rs is must-include (11 tokens), then main.rs (9 tokens) would exceed 12.
         assert!(included.iter().any(|s| s.file_path == "tests/test.rs"));
         assert!(
@@ -371,7 +492,7 @@ mod tests {
             .filter(|s| !s.file_path.contains("tests/"))
             .collect();
         let estimator = tokens::default_estimator();
-        let (included, _) = greedy_pack(&refs, &estimator, None, &[]);
+        let (included, _) = greedy_pack(&ranked(&refs), &estimator, None, &[]);
         assert_eq!(included.len(), 2);
         assert!(!included.iter().any(|s| s.file_path.contains("tests/")));
     }
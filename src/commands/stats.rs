@@ -10,11 +10,12 @@ use std::path::PathBuf;
 
 use colored::Colorize;
 
+use crate::cache::{self, IndexCache};
 use crate::config::Config;
 use crate::error::Result;
 use crate::manifest;
 use crate::scanner;
-use crate::tokens::{self, TokenEstimator};
+use crate::tokens;
 
 // ---------------------------------------------------------------------------
 // Public interface
@@ -39,6 +40,10 @@ pub struct StatsCommandOptions {
     pub quiet: bool,
     /// Path to config file.
     pub config_path: Option<PathBuf>,
+    /// Bypass the index cache.
+    pub no_cache: bool,
+    /// Override the cache directory.
+    pub cache_dir: Option<PathBuf>,
 }
 
 /// Run the stats command.
@@ -77,7 +82,7 @@ fn run_bundle_mode(path: &std::path::Path, options: &StatsCommandOptions) -> Res
     // Top files by token count.
     let top_n = options.top_files.unwrap_or(10);
     let mut entries = manifest.entries.clone();
-    entries.sort_by(|a, b| b.token_estimate.cmp(&a.token_estimate));
+    entries.sort_by_key(|e| std::cmp::Reverse(e.token_estimate));
     entries.truncate(top_n);
 
     println!();
@@ -116,7 +121,7 @@ fn run_bundle_mode(path: &std::path::Path, options: &StatsCommandOptions) -> Res
             *tokens += entry.token_estimate;
         }
         let mut langs: Vec<_> = lang_stats.into_iter().collect();
-        langs.sort_by(|a, b| b.1 .1.cmp(&a.1 .1));
+        langs.sort_by_key(|l| std::cmp::Reverse(l.1 .1));
         for (lang, (count, tokens)) in &langs {
             println!("  {:<15} {:>4} snippets  {:>6} tokens", lang, count, tokens);
         }
@@ -140,7 +145,15 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
         return Ok(());
     }
 
-    let estimator = tokens::default_estimator();
+    // Token counts come from the index cache; files that changed since
+    // the last run are re-read, removed files are dropped.
+    let mut cache = IndexCache::open(
+        &options.root,
+        &cache::resolve_config(&config, options.no_cache, options.cache_dir.as_deref()),
+    );
+    cache.prune(&files);
+
+    let estimator = tokens::estimator_for_model(tokens::DEFAULT_MODEL);
     let mut total_tokens: usize = 0;
     let mut total_bytes: u64 = 0;
     let mut lang_stats: HashMap<String, (usize, u64, usize)> = HashMap::new(); // (count, bytes, tokens)
@@ -151,10 +164,7 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
         total_bytes += file_size;
 
         let tokens = if options.tokens {
-            match std::fs::read_to_string(&file.abs_path) {
-                Ok(content) => estimator.estimate(&content),
-                Err(_) => 0,
-            }
+            cache.token_count(file, &estimator).unwrap_or(0)
         } else {
             0
         };
@@ -174,6 +184,10 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
         file_tokens.push((file.rel_path.clone(), tokens, file_size));
     }
 
+    if let Err(e) = cache.save() {
+        tracing::warn!("could not update the index cache: {e}");
+    }
+
     println!("{}", "Repository Statistics".bold());
     println!("  files:           {}", files.len());
     println!("  total bytes:     {}", format_bytes(total_bytes));
@@ -188,7 +202,7 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
     // Top files.
     if options.tokens {
         let top_n = options.top_files.unwrap_or(10);
-        file_tokens.sort_by(|a, b| b.1.cmp(&a.1));
+        file_tokens.sort_by_key(|f| std::cmp::Reverse(f.1));
         file_tokens.truncate(top_n);
 
         println!();
@@ -203,7 +217,7 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
         println!();
         println!("{}", "By language:".bold());
         let mut langs: Vec<_> = lang_stats.into_iter().collect();
-        langs.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
+        langs.sort_by_key(|l| std::cmp::Reverse(l.1 .0));
         for (lang, (count, bytes, tokens)) in &langs {
             if options.tokens {
                 println!(
@@ -232,7 +246,7 @@ fn run_repo_mode(options: &StatsCommandOptions) -> Result<()> {
 // ---------------------------------------------------------------------------
 
 /// Format bytes as a human-readable string.
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     if bytes < 1024 {
         format!("{bytes} B")
     } else if bytes < 1024 * 1024 {
@@ -298,6 +312,8 @@ mod tests {
             tokens: true,
             quiet: false,
             config_path: None,
+            no_cache: true,
+            cache_dir: None,
         };
         run(options).unwrap();
     }
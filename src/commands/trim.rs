@@ -0,0 +1,520 @@
+//! Handler for the `contextsmith trim` command.
+//!
+//! Reads an existing bundle in any output format, cuts it down to a token
+//! budget, and writes the trimmed bundle in the same format. Unlike
+//! `pack`, which keeps or drops whole sections, `trim` shortens sections
+//! line by line so the most of each snippet survives a shrinking window.
+
+use std::path::PathBuf;
+
+use colored::Colorize;
+
+use crate::config::Config;
+use crate::error::{ContextSmithError, Result};
+use crate::manifest::{self, Manifest, ManifestEntry};
+use crate::models::ModelRegistry;
+use crate::output::{self, Bundle, BundleSection, FormatOptions};
+use crate::tokens::TokenEstimator;
+use crate::utils;
+
+// ---------------------------------------------------------------------------
+// Public interface
+// ---------------------------------------------------------------------------
+
+/// All inputs needed to run the trim command.
+#[derive(Debug)]
+pub struct TrimCommandOptions {
+    /// Input bundle file (Markdown, JSON, XML or plain).
+    pub input: Option<PathBuf>,
+    /// Token budget.
+    pub budget: Option<usize>,
+    /// Character budget (alternative to token budget).
+    pub chars: Option<usize>,
+    /// Model name for token estimation (falls back to `default_model`).
+    pub model: Option<String>,
+    /// Reserve tokens for model response.
+    pub reserve: Option<usize>,
+    /// Always keep the first N lines of every section.
+    pub keep_head: Option<usize>,
+    /// Prepend a table of contents listing every original section.
+    pub keep_manifest: bool,
+    /// Write output to file.
+    pub out: Option<PathBuf>,
+    /// Write to stdout.
+    pub stdout: bool,
+    /// Suppress non-essential output.
+    pub quiet: bool,
+    /// Path to config file.
+    pub config_path: Option<PathBuf>,
+}
+
+/// Outcome of trimming a single section.
+#[derive(Debug, Clone, PartialEq)]
+struct TrimmedSection {
+    /// Number of leading lines kept (0 = dropped).
+    kept_lines: usize,
+    /// Number of lines in the original section.
+    total_lines: usize,
+    /// Rendered content, including the truncation marker if any.
+    content: String,
+}
+
+/// File path used for the table-of-contents section.
+const TOC_PATH: &str = "TABLE OF CONTENTS";
+
+/// Run the trim command end-to-end.
+pub fn run(options: TrimCommandOptions) -> Result<()> {
+    // Step 1: Read and parse the input bundle.
+    let input_path = options
+        .input
+        .clone()
+        .ok_or_else(|| ContextSmithError::validation("input", "input bundle file is required"))?;
+
+    let content = std::fs::read_to_string(&input_path).map_err(|e| {
+        ContextSmithError::io(format!("reading bundle '{}'", input_path.display()), e)
+    })?;
+
+    let format = output::detect_format(&input_path, &content);
+    let bundle = output::parse_bundle(&content, format).map_err(|e| {
+        ContextSmithError::config_with_source(
+            format!("failed to parse bundle '{}'", input_path.display()),
+            e,
+        )
+    })?;
+
+    if bundle.sections.is_empty() {
+        if !options.quiet {
+            eprintln!("{}", "No sections in bundle.".dimmed());
+        }
+        return Ok(());
+    }
+
+    // Step 2: Determine estimator and effective budget.
+    let config = load_config(&options)?;
+    let model = resolve_model(&options, &config);
+    let registry = ModelRegistry::from_config(&config);
+    let estimator = registry.estimator(&model);
+    let chars_budget = options.chars.map(|c| registry.chars_to_tokens(&model, c));
+    let plan = registry.plan_budget(
+        options.budget.or(chars_budget),
+        options.reserve,
+        options.model.as_deref(),
+    );
+    let reserve = plan.reserve;
+
+    let effective_budget = plan.effective().ok_or_else(|| {
+        ContextSmithError::validation(
+            "budget",
+            "one of --budget, --chars or a known --model is required",
+        )
+    })?;
+
+    // Step 3: Reserve room for the table of contents, then trim sections.
+    let toc_tokens = if options.keep_manifest {
+        let placeholder: Vec<TrimmedSection> = bundle
+            .sections
+            .iter()
+            .map(|s| TrimmedSection {
+                kept_lines: 0,
+                total_lines: s.content.lines().count(),
+                content: String::new(),
+            })
+            .collect();
+        estimator.estimate(&render_toc(&bundle.sections, &placeholder))
+    } else {
+        0
+    };
+
+    let trimmed = trim_sections(
+        &bundle.sections,
+        &estimator,
+        effective_budget.saturating_sub(toc_tokens),
+        options.keep_head,
+    );
+
+    // Step 4: Build the output bundle.
+    let mut sections = Vec::new();
+    if options.keep_manifest {
+        sections.push(BundleSection {
+            file_path: TOC_PATH.to_string(),
+            language: String::new(),
+            content: render_toc(&bundle.sections, &trimmed),
+            reason: "manifest".to_string(),
+        });
+    }
+    for (section, t) in bundle.sections.iter().zip(&trimmed) {
+        if t.kept_lines == 0 {
+            continue;
+        }
+        sections.push(BundleSection {
+            file_path: section.file_path.clone(),
+            language: section.language.clone(),
+            content: t.content.clone(),
+            reason: trimmed_reason(&section.reason, t),
+        });
+    }
+
+    let kept = trimmed.iter().filter(|t| t.kept_lines > 0).count();
+    let output_bundle = Bundle {
+        summary: if bundle.summary.is_empty() {
+            format!("{kept} of {} sections kept", bundle.sections.len())
+        } else {
+            format!(
+                "{} (trimmed: {kept} of {} sections kept)",
+                strip_trim_suffix(&bundle.summary),
+                bundle.sections.len()
+            )
+        },
+        sections,
+    };
+
+    // Step 5: Format and write in the input's format.
+    let formatted = output::format_bundle(&output_bundle, format)?;
+    output::write_output(
+        &formatted,
+        &FormatOptions {
+            format,
+            stdout: options.stdout,
+            out: options.out.clone(),
+        },
+    )?;
+
+    // Step 6: Write an updated manifest alongside output.
+    let previous = manifest::read_sibling_manifest(&input_path);
+    let entries = build_entries(&bundle.sections, &trimmed, &estimator, previous.as_ref());
+    if let Some(ref out_path) = options.out {
+        let mut m = manifest::build_manifest(entries.clone(), &model, plan.budget, reserve);
+        if let Some(prev) = previous {
+            m.summary.weights_used = prev.summary.weights_used;
+        }
+        let manifest_path = utils::manifest_sibling_path(out_path);
+        manifest::write_manifest(&m, &manifest_path)?;
+        if !options.quiet {
+            eprintln!(
+                "{} manifest written to {}",
+                "ok:".green().bold(),
+                manifest_path.display()
+            );
+        }
+    }
+
+    // Step 7: Print summary.
+    if !options.quiet && !options.stdout {
+        let total_tokens: usize = entries
+            .iter()
+            .filter(|e| e.included)
+            .map(|e| e.token_estimate)
+            .sum::<usize>()
+            + toc_tokens;
+        let shortened = trimmed
+            .iter()
+            .filter(|t| t.kept_lines > 0 && t.kept_lines < t.total_lines)
+            .count();
+        eprintln!(
+            "{} {} of {} section{} kept ({} shortened), ~{} tokens (budget: {})",
+            "trim:".green().bold(),
+            kept,
+            trimmed.len(),
+            if trimmed.len() == 1 { "" } else { "s" },
+            shortened,
+            total_tokens,
+            effective_budget,
+        );
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Trimming
+// ---------------------------------------------------------------------------
+
+/// Trim sections to fit `budget` tokens.
+///
+/// With `keep_head`, the first N lines of every section are kept
+/// unconditionally. The remaining budget is then filled in section order:
+/// a section that fits is kept whole, otherwise it is cut to the longest
+/// line prefix that still fits. Sections left with no lines are dropped.
+fn trim_sections(
+    sections: &[BundleSection],
+    estimator: &dyn TokenEstimator,
+    budget: usize,
+    keep_head: Option<usize>,
+) -> Vec<TrimmedSection> {
+    let all_lines: Vec<Vec<&str>> = sections
+        .iter()
+        .map(|s| s.content.lines().collect())
+        .collect();
+
+    // Phase 1: guaranteed heads.
+    let mut result: Vec<TrimmedSection> = all_lines
+        .iter()
+        .map(|lines| {
+            let kept = keep_head.unwrap_or(0).min(lines.len());
+            TrimmedSection {
+                kept_lines: kept,
+                total_lines: lines.len(),
+                content: render_prefix(lines, kept),
+            }
+        })
+        .collect();
+    let mut used: usize = result.iter().map(|t| estimator.estimate(&t.content)).sum();
+
+    // Phase 2: extend sections in order while budget remains.
+    for (t, lines) in result.iter_mut().zip(&all_lines) {
+        if t.kept_lines == t.total_lines {
+            continue;
+        }
+        let current = estimator.estimate(&t.content);
+        let available = budget.saturating_sub(used - current);
+
+        // Binary search for the longest prefix that fits.
+        let (mut lo, mut hi) = (t.kept_lines, t.total_lines);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if estimator.estimate(&render_prefix(lines, mid)) <= available {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        if lo > t.kept_lines {
+            t.kept_lines = lo;
+            t.content = render_prefix(lines, lo);
+            used = used - current + estimator.estimate(&t.content);
+        }
+    }
+
+    result
+}
+
+/// Render the first `kept` lines, with a marker when lines were cut.
+fn render_prefix(lines: &[&str], kept: usize) -> String {
+    if kept == 0 {
+        return String::new();
+    }
+    let mut out = lines[..kept].join("\n");
+    out.push('\n');
+    if kept < lines.len() {
+        out.push_str(&format!("... [trimmed {} lines]\n", lines.len() - kept));
+    }
+    out
+}
+
+/// Render a table of contents listing every original section.
+fn render_toc(sections: &[BundleSection], trimmed: &[TrimmedSection]) -> String {
+    let mut out = String::new();
+    for (i, (section, t)) in sections.iter().zip(trimmed).enumerate() {
+        let status = if t.kept_lines == 0 {
+            "dropped".to_string()
+        } else if t.kept_lines < t.total_lines {
+            format!("trimmed {}/{}", t.kept_lines, t.total_lines)
+        } else {
+            "kept".to_string()
+        };
+        let reason = if section.reason.is_empty() {
+            String::new()
+        } else {
+            format!(" — {}", section.reason)
+        };
+        out.push_str(&format!(
+            "{}. {}{} ({} lines, {status})\n",
+            i + 1,
+            section.file_path,
+            reason,
+            t.total_lines,
+        ));
+    }
+    out
+}
+
+/// Annotate a section reason with how much of it was kept.
+fn trimmed_reason(reason: &str, t: &TrimmedSection) -> String {
+    if t.kept_lines >= t.total_lines {
+        return reason.to_string();
+    }
+    let note = format!("trimmed to {}/{} lines", t.kept_lines, t.total_lines);
+    if reason.is_empty() {
+        note
+    } else {
+        format!("{reason} ({note})")
+    }
+}
+
+/// Drop a previous "(trimmed: …)" suffix so repeated trims don't stack.
+fn strip_trim_suffix(summary: &str) -> &str {
+    match summary.find(" (trimmed: ") {
+        Some(idx) if summary.ends_with(')') => &summary[..idx],
+        _ => summary,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Manifest
+// ---------------------------------------------------------------------------
+
+/// Build manifest entries for the trimmed sections.
+///
+/// When the input bundle has a manifest, line ranges and scores are
+/// carried over from its included entries (which are in bundle order).
+fn build_entries(
+    sections: &[BundleSection],
+    trimmed: &[TrimmedSection],
+    estimator: &dyn TokenEstimator,
+    previous: Option<&Manifest>,
+) -> Vec<ManifestEntry> {
+    let prior: Vec<&ManifestEntry> = previous
+        .map(|m| m.entries.iter().filter(|e| e.included).collect())
+        .unwrap_or_default();
+    let aligned = prior.len() == sections.len()
+        && prior
+            .iter()
+            .zip(sections)
+            .all(|(e, s)| e.file_path == s.file_path);
+
+    sections
+        .iter()
+        .zip(trimmed)
+        .enumerate()
+        .map(|(i, (section, t))| {
+            let included = t.kept_lines > 0;
+            let (start_line, mut end_line, score) = if aligned {
+                (prior[i].start_line, prior[i].end_line, prior[i].score)
+            } else {
+                (0, 0, 0.0)
+            };
+            if included && start_line > 0 && t.kept_lines < t.total_lines {
+                end_line = start_line + t.kept_lines - 1;
+            }
+            let content = if included {
+                &t.content
+            } else {
+                &section.content
+            };
+            ManifestEntry {
+                file_path: section.file_path.clone(),
+                start_line,
+                end_line,
+                token_estimate: estimator.estimate(content),
+                char_count: content.len(),
+                reason: trimmed_reason(&section.reason, t),
+                score,
+                included,
+                language: section.language.clone(),
+            }
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Resolve the model name: `--model` first, then the config default.
+fn resolve_model(options: &TrimCommandOptions, config: &Config) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| config.default_model.clone())
+}
+
+/// Load config from explicit path or discovery.
+fn load_config(options: &TrimCommandOptions) -> Result<Config> {
+    let config_path = crate::config::find_config_file(options.config_path.as_deref());
+    match config_path {
+        Some(p) => Config::load(&p),
+        None => Ok(Config::default()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens;
+
+    fn section(path: &str, lines: usize) -> BundleSection {
+        BundleSection {
+            file_path: path.to_string(),
+            language: "rust".to_string(),
+            content: (1..=lines)
+                .map(|i| format!("let line_{i:03} = {i};\n"))
+                .collect(),
+            reason: "modified".to_string(),
+        }
+    }
+
+    #[test]
+    fn trim_keeps_everything_under_budget() {
+        let sections = vec![section("a.rs", 5), section("b.rs", 5)];
+        let estimator = tokens::default_estimator();
+        let trimmed = trim_sections(&sections, &estimator, 10_000, None);
+        assert!(trimmed.iter().all(|t| t.kept_lines == t.total_lines));
+        assert_eq!(trimmed[0].content, sections[0].content);
+    }
+
+    #[test]
+    fn trim_cuts_section_to_fit() {
+        // Each line is 20 chars = 5 tokens.
+        let sections = vec![section("a.rs", 4), section("b.rs", 40)];
+        let estimator = tokens::default_estimator();
+        let trimmed = trim_sections(&sections, &estimator, 60, None);
+
+        assert_eq!(trimmed[0].kept_lines, 4);
+        assert!(trimmed[1].kept_lines > 0 && trimmed[1].kept_lines < 40);
+        assert!(trimmed[1].content.contains("... [trimmed"));
+        let used: usize = trimmed.iter().map(|t| estimator.estimate(&t.content)).sum();
+        assert!(used <= 60);
+    }
+
+    #[test]
+    fn trim_drops_sections_once_budget_is_spent() {
+        let sections = vec![section("a.rs", 40), section("b.rs", 40)];
+        let estimator = tokens::default_estimator();
+        let trimmed = trim_sections(&sections, &estimator, 50, None);
+        assert!(trimmed[0].kept_lines > 0);
+        assert_eq!(trimmed[1].kept_lines, 0);
+    }
+
+    #[test]
+    fn keep_head_is_always_kept() {
+        let sections = vec![section("a.rs", 40), section("b.rs", 40)];
+        let estimator = tokens::default_estimator();
+        let trimmed = trim_sections(&sections, &estimator, 1, Some(2));
+        assert_eq!(trimmed[0].kept_lines, 2);
+        assert_eq!(trimmed[1].kept_lines, 2);
+    }
+
+    #[test]
+    fn toc_lists_every_section() {
+        let sections = vec![section("a.rs", 3), section("b.rs", 3)];
+        let trimmed = vec![
+            TrimmedSection {
+                kept_lines: 3,
+                total_lines: 3,
+                content: String::new(),
+            },
+            TrimmedSection {
+                kept_lines: 0,
+                total_lines: 3,
+                content: String::new(),
+            },
+        ];
+        let toc = render_toc(&sections, &trimmed);
+        assert!(toc.contains("1. a.rs — modified (3 lines, kept)"));
+        assert!(toc.contains("2. b.rs — modified (3 lines, dropped)"));
+    }
+
+    #[test]
+    fn strip_trim_suffix_avoids_stacking() {
+        assert_eq!(
+            strip_trim_suffix("1 file changed (trimmed: 1 of 2 sections kept)"),
+            "1 file changed"
+        );
+        assert_eq!(strip_trim_suffix("1 file changed"), "1 file changed");
+    }
+}
@@ -5,6 +5,7 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
 use crate::error::{ContextSmithError, Result};
+use crate::models::ModelSpec;
 
 /// Top-level configuration for ContextSmith.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -14,8 +15,12 @@ pub struct Config {
     pub generated: Vec<String>,
     pub default_budget: usize,
     pub reserve_tokens: usize,
+    pub default_model: String,
     pub ranking_weights: RankingWeights,
+    pub ranking: RankingConfig,
     pub languages: HashMap<String, LanguageConfig>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub models: HashMap<String, ModelSpec>,
     pub cache: CacheConfig,
 }
 
@@ -29,6 +34,12 @@ pub struct RankingWeights {
     pub test: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RankingConfig {
+    pub strategy: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LanguageConfig {
     pub extensions: Vec<String>,
@@ -65,8 +76,11 @@ impl Default for Config {
             ],
             default_budget: 12000,
             reserve_tokens: 500,
+            default_model: crate::tokens::DEFAULT_MODEL.into(),
             ranking_weights: RankingWeights::default(),
+            ranking: RankingConfig::default(),
             languages: default_languages(),
+            models: HashMap::new(),
             cache: CacheConfig::default(),
         }
     }
@@ -84,6 +98,14 @@ impl Default for RankingWeights {
     }
 }
 
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: crate::ranker::DEFAULT_STRATEGY.into(),
+        }
+    }
+}
+
 impl Default for CacheConfig {
     fn default() -> Self {
         Self {
@@ -162,6 +184,36 @@ impl Config {
                 "must be less than default_budget",
             ));
         }
+        if self.default_model.trim().is_empty() {
+            return Err(ContextSmithError::validation(
+                "default_model",
+                "must not be empty",
+            ));
+        }
+        if crate::ranker::strategy_by_name(&self.ranking.strategy).is_none() {
+            return Err(ContextSmithError::validation(
+                "ranking.strategy",
+                format!(
+                    "unknown strategy '{}'; expected one of {}",
+                    self.ranking.strategy,
+                    crate::ranker::STRATEGY_NAMES.join(", ")
+                ),
+            ));
+        }
+        for (name, spec) in &self.models {
+            if spec.context_window == 0 {
+                return Err(ContextSmithError::validation(
+                    format!("models.{name}.context_window"),
+                    "must be greater than 0",
+                ));
+            }
+            if spec.output_reserve >= spec.context_window {
+                return Err(ContextSmithError::validation(
+                    format!("models.{name}.output_reserve"),
+                    "must be less than context_window",
+                ));
+            }
+        }
         Ok(())
     }
 
@@ -173,6 +225,15 @@ impl Config {
         if overrides.reserve_tokens != Config::default().reserve_tokens {
             self.reserve_tokens = overrides.reserve_tokens;
         }
+        if overrides.default_model != Config::default().default_model {
+            self.default_model = overrides.default_model;
+        }
+        if overrides.ranking != Config::default().ranking {
+            self.ranking = overrides.ranking;
+        }
+        if !overrides.models.is_empty() {
+            self.models.extend(overrides.models);
+        }
         if overrides.ignore != Config::default().ignore {
             self.ignore = overrides.ignore;
         }
@@ -207,6 +268,11 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.config.default_model = model.into();
+        self
+    }
+
     pub fn with_cache_enabled(mut self, enabled: bool) -> Self {
         self.config.cache.enabled = enabled;
         self
@@ -298,6 +364,57 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn validation_rejects_empty_default_model() {
+        let config = Config {
+            default_model: "  ".into(),
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("default_model"));
+    }
+
+    #[test]
+    fn default_model_is_optional_in_toml() {
+        let config: Config = toml::from_str("default_budget = 8000").unwrap();
+        assert_eq!(config.default_model, "gpt-4");
+
+        let config: Config = toml::from_str("default_model = \"claude-3-opus\"").unwrap();
+        assert_eq!(config.default_model, "claude-3-opus");
+    }
+
+    #[test]
+    fn models_table_parses_and_validates() {
+        let config: Config = toml::from_str(
+            "[models.llama-70b]\ncontext_window = 32000\noutput_reserve = 2000\ntokenizer = \"cl100k_base\"\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let spec = &config.models["llama-70b"];
+        assert_eq!(spec.context_window, 32000);
+        assert_eq!(spec.tokenizer, crate::models::Tokenizer::Cl100kBase);
+
+        let bad: Config =
+            toml::from_str("[models.tiny]\ncontext_window = 100\noutput_reserve = 100\n").unwrap();
+        let err = bad.validate().unwrap_err();
+        assert!(err.to_string().contains("models.tiny.output_reserve"));
+
+        assert!(toml::from_str::<Config>("[models.x]\ntokenizer = \"nope\"\n").is_err());
+    }
+
+    #[test]
+    fn ranking_strategy_parses_and_validates() {
+        assert_eq!(Config::default().ranking.strategy, "weighted");
+
+        let config: Config = toml::from_str("[ranking]\nstrategy = \"diff-first\"\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.ranking.strategy, "diff-first");
+
+        let bad: Config = toml::from_str("[ranking]\nstrategy = \"random\"\n").unwrap();
+        let err = bad.validate().unwrap_err();
+        assert!(err.to_string().contains("ranking.strategy"));
+    }
+
     #[test]
     fn builder_with_budget() {
         let config = ConfigBuilder::new().with_budget(8000).build().unwrap();
@@ -315,6 +432,13 @@ mod tests {
         assert_eq!(base.default_budget, 5000);
     }
 
+    #[test]
+    fn merge_overrides_default_model() {
+        let mut base = Config::default();
+        base.merge(ConfigBuilder::new().with_model("claude").build().unwrap());
+        assert_eq!(base.default_model, "claude");
+    }
+
     #[test]
     fn save_and_load_roundtrip() {
         let dir = tempfile::tempdir().unwrap();
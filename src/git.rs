@@ -5,6 +5,7 @@
 //! all other modules work with the parsed [`DiffFile`] and [`DiffHunk`]
 //! types rather than raw git output.
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -27,6 +28,10 @@ pub struct DiffOptions {
     pub untracked: bool,
     /// Optional base reference or duration (e.g. "2h", "2024-01-01").
     pub since: Option<String>,
+    /// Diff from the merge-base of `HEAD` and this branch (PR view).
+    pub base: Option<String>,
+    /// With `base`: also include staged and unstaged work on top of `HEAD`.
+    pub worktree: bool,
 }
 
 /// A single file affected by the diff.
@@ -81,6 +86,23 @@ pub struct DiffLine {
     pub new_lineno: Option<usize>,
 }
 
+/// A commit in the diffed range, with its message split into parts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    /// Full commit SHA.
+    pub sha: String,
+    /// Author name.
+    pub author: String,
+    /// Author date (`YYYY-MM-DD`).
+    pub date: String,
+    /// First line of the message.
+    pub subject: String,
+    /// Message body, without the subject and trailers.
+    pub body: String,
+    /// Trailer lines (`Key: value`) from the end of the message.
+    pub trailers: Vec<String>,
+}
+
 /// Classification of a diff line.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineKind {
@@ -144,7 +166,18 @@ pub fn get_diff(options: &DiffOptions) -> Result<Vec<DiffFile>> {
 
     // Build the revision range or --merge-base for `--since`.
     let since_rev;
-    if let Some(ref range) = options.rev_range {
+    let base_rev;
+    if let Some(ref base) = options.base {
+        // Committed work only by default; with --staged or --worktree the
+        // merge-base is compared against the index or working tree.
+        let merge_base = merge_base(&options.root, base)?;
+        base_rev = if options.staged || options.worktree {
+            merge_base
+        } else {
+            format!("{merge_base}..HEAD")
+        };
+        args.push(&base_rev);
+    } else if let Some(ref range) = options.rev_range {
         args.push(range);
     } else if let Some(ref since) = options.since {
         // `git diff $(git rev-list -1 --before=<since> HEAD)..HEAD`
@@ -196,6 +229,136 @@ pub fn get_diff(options: &DiffOptions) -> Result<Vec<DiffFile>> {
     Ok(files)
 }
 
+/// The commit where `HEAD` forked from `base` (`git merge-base HEAD <base>`).
+pub fn merge_base(root: &Path, base: &str) -> Result<String> {
+    let output = run_git(&["merge-base", "HEAD", base], root)?;
+    let sha = output.trim();
+    if sha.is_empty() {
+        return Err(ContextSmithError::Git {
+            message: format!("no common ancestor of HEAD and '{base}'"),
+        });
+    }
+    Ok(sha.to_string())
+}
+
+/// Full SHA of `HEAD`.
+pub fn head_sha(root: &Path) -> Result<String> {
+    Ok(run_git(&["rev-parse", "HEAD"], root)?.trim().to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Commit history
+// ---------------------------------------------------------------------------
+
+/// The commits in the range `options` selects, oldest first.
+///
+/// `A..B` is walked as given, a bare `A` as `A..HEAD`, `A...B` and
+/// `--base` from their merge-base, and `--since` from its resolved base.
+/// Returns `None` for working-tree and `--staged` diffs, which have no
+/// commits.
+pub fn commits_in_range(options: &DiffOptions) -> Result<Option<Vec<Commit>>> {
+    let range = if options.base.is_some() {
+        format!("{}..HEAD", old_revision(options)?)
+    } else if let Some(ref range) = options.rev_range {
+        match range.split_once("...") {
+            Some((_, to)) => format!(
+                "{}..{}",
+                old_revision(options)?,
+                if to.is_empty() { "HEAD" } else { to }
+            ),
+            None if range.contains("..") => range.clone(),
+            None => format!("{range}..HEAD"),
+        }
+    } else if let Some(ref since) = options.since {
+        resolve_since_rev(&options.root, since)?
+    } else {
+        return Ok(None);
+    };
+
+    let output = run_git(
+        &[
+            "log",
+            "--reverse",
+            "--date=short",
+            "--format=%x1e%H%x1f%an%x1f%ad%x1f%B",
+            &range,
+        ],
+        &options.root,
+    )?;
+    Ok(Some(parse_log(&output)))
+}
+
+/// Parse `git log` output written with the record/unit separators used by
+/// [`commits_in_range`].
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.splitn(4, '\x1f');
+            let sha = fields.next()?.trim().to_string();
+            let author = fields.next()?.to_string();
+            let date = fields.next()?.to_string();
+            let message = fields.next()?.trim();
+            let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+            let (body, trailers) = split_trailers(rest.trim());
+            Some(Commit {
+                sha,
+                author,
+                date,
+                subject: subject.trim().to_string(),
+                body,
+                trailers,
+            })
+        })
+        .collect()
+}
+
+/// Split a message body into its text and trailing `Key: value` lines.
+///
+/// Trailers are the last paragraph when every line in it looks like
+/// `Token: value` (the token has no spaces), as `git interpret-trailers`
+/// expects.
+fn split_trailers(body: &str) -> (String, Vec<String>) {
+    let is_trailer = |line: &str| {
+        line.split_once(": ").is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+    };
+    let (text, last) = match body.rsplit_once("\n\n") {
+        Some((text, last)) => (text, last),
+        None => ("", body),
+    };
+    if !last.is_empty() && last.lines().all(is_trailer) {
+        let trailers = last.lines().map(str::to_string).collect();
+        (text.trim_end().to_string(), trailers)
+    } else {
+        (body.to_string(), Vec::new())
+    }
+}
+
+/// The commit that last changed each line of `path` between `start` and
+/// `end` (1-based, inclusive) as of `rev`, or in the working tree when
+/// `rev` is `None`, as full SHAs. Uncommitted lines come back as all
+/// zeros.
+pub fn blame_lines(
+    root: &Path,
+    rev: Option<&str>,
+    path: &str,
+    start: usize,
+    end: usize,
+) -> Result<Vec<String>> {
+    let lines = format!("{start},{end}");
+    let mut args = vec!["blame", "-l", "-s", "-L", &lines];
+    args.extend(rev);
+    args.extend(["--", path]);
+    let output = run_git(&args, root)?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|sha| sha.trim_start_matches('^').to_string())
+        .collect())
+}
+
 /// Resolve a `--since` value to a revision range string (e.g. "abc123..HEAD").
 fn resolve_since_rev(root: &Path, since: &str) -> Result<String> {
     let output = run_git(
@@ -221,6 +384,94 @@ fn get_untracked_files(root: &Path) -> Result<Vec<String>> {
         .collect())
 }
 
+/// How many recent commits [`last_commit_times`] looks at.
+pub const RECENCY_COMMIT_LIMIT: usize = 1000;
+
+/// Unix timestamp of the most recent commit touching each file.
+///
+/// Walks the last [`RECENCY_COMMIT_LIMIT`] commits of `git log`
+/// newest-first, so the first timestamp seen for a path is its last
+/// change; files untouched in that window are absent. Paths are relative
+/// to the repository root.
+pub fn last_commit_times(root: &Path) -> Result<HashMap<String, i64>> {
+    let limit = format!("-n{RECENCY_COMMIT_LIMIT}");
+    let output = run_git(
+        &[
+            "log",
+            &limit,
+            "--format=%x00%ct",
+            "--name-only",
+            "--no-renames",
+        ],
+        root,
+    )?;
+
+    let mut times = HashMap::new();
+    let mut current: Option<i64> = None;
+    for line in output.lines() {
+        if let Some(ts) = line.strip_prefix('\0') {
+            current = ts.trim().parse().ok();
+        } else if let (Some(ts), false) = (current, line.is_empty()) {
+            times.entry(line.to_string()).or_insert(ts);
+        }
+    }
+    Ok(times)
+}
+
+/// The revision holding the "after" side of the diff `options` selects,
+/// or `None` when that side is the working tree (or the index).
+///
+/// `A..B` and `A...B` give `B`, `--since` `HEAD` and `--base` `HEAD`
+/// unless `--worktree` or `--staged` is set; a bare `A` compares against
+/// the working tree.
+pub fn new_revision(options: &DiffOptions) -> Option<String> {
+    let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+    if options.base.is_some() {
+        return (!options.worktree && !options.staged).then(|| "HEAD".to_string());
+    }
+    if let Some(ref range) = options.rev_range {
+        let to = range.split_once("...").or_else(|| range.split_once(".."));
+        return to.map(|(_, to)| or_head(to));
+    }
+    options.since.as_ref().map(|_| "HEAD".to_string())
+}
+
+/// The revision holding the "before" side of the diff `options` selects,
+/// as a prefix for `git show <rev>:<path>`.
+///
+/// `--base` gives its merge-base with `HEAD`, `A..B` and a bare `A`
+/// give `A`, `A...B` the merge-base of `A` and `B`, `--since` its
+/// resolved base commit and `--staged` `HEAD`. A plain working-tree diff
+/// compares against the index, which is the empty revision
+/// (`git show :<path>`).
+pub fn old_revision(options: &DiffOptions) -> Result<String> {
+    if let Some(ref base) = options.base {
+        return merge_base(&options.root, base);
+    }
+    if let Some(ref range) = options.rev_range {
+        let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+        if let Some((from, to)) = range.split_once("...") {
+            let base = run_git(&["merge-base", &or_head(from), &or_head(to)], &options.root)?;
+            return Ok(base.trim().to_string());
+        }
+        return Ok(match range.split_once("..") {
+            Some((from, _)) => or_head(from),
+            None => range.clone(),
+        });
+    }
+    if let Some(ref since) = options.since {
+        let range = resolve_since_rev(&options.root, since)?;
+        return Ok(range.trim_end_matches("..HEAD").to_string());
+    }
+    Ok(if options.staged { "HEAD" } else { "" }.to_string())
+}
+
+/// Contents of `path` at `rev` (`git show <rev>:<path>`); an empty `rev`
+/// reads the index.
+pub fn show_file(root: &Path, rev: &str, path: &str) -> Result<String> {
+    run_git(&["show", &format!("{rev}:{path}")], root)
+}
+
 // ---------------------------------------------------------------------------
 // Unified diff parser
 // ---------------------------------------------------------------------------
@@ -451,6 +702,89 @@ diff --git a/old_name.rs b/new_name.rs
 +    new_code();
  }";
 
+    #[test]
+    fn parse_log_splits_subject_body_and_trailers() {
+        let output = "\x1eabc123\x1fAda\x1f2026-10-01\x1fFix budget overflow\n\nGreedy packing \
+                      ignored the reserve.\n\nReviewed-by: Bo <bo@example.com>\nFixes: #12\n\n\
+                      \x1edef456\x1fBo\x1f2026-10-02\x1fTidy up\n\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].sha, "abc123");
+        assert_eq!(commits[0].subject, "Fix budget overflow");
+        assert_eq!(commits[0].body, "Greedy packing ignored the reserve.");
+        assert_eq!(
+            commits[0].trailers,
+            vec!["Reviewed-by: Bo <bo@example.com>", "Fixes: #12"]
+        );
+        assert_eq!(commits[1].subject, "Tidy up");
+        assert!(commits[1].body.is_empty());
+        assert!(commits[1].trailers.is_empty());
+    }
+
+    #[test]
+    fn split_trailers_keeps_prose_paragraphs() {
+        let (body, trailers) = split_trailers("Note: this is prose, not a trailer line\nat all.");
+        assert!(trailers.is_empty());
+        assert!(body.starts_with("Note:"));
+
+        let (body, trailers) = split_trailers("Signed-off-by: Ada <ada@example.com>");
+        assert!(body.is_empty());
+        assert_eq!(trailers, vec!["Signed-off-by: Ada <ada@example.com>"]);
+    }
+
+    #[test]
+    fn old_revision_takes_the_range_start() {
+        let options = |rev_range: Option<&str>, staged: bool| DiffOptions {
+            root: PathBuf::from("."),
+            rev_range: rev_range.map(String::from),
+            staged,
+            untracked: false,
+            since: None,
+            base: None,
+            worktree: false,
+        };
+        let old = |o: DiffOptions| old_revision(&o).unwrap();
+        assert_eq!(old(options(Some("main..HEAD"), false)), "main");
+        assert_eq!(old(options(Some("..feature"), false)), "HEAD");
+        assert_eq!(old(options(Some("v1.2"), false)), "v1.2");
+        assert_eq!(old(options(None, true)), "HEAD");
+        assert_eq!(old(options(None, false)), "");
+    }
+
+    #[test]
+    fn new_revision_is_none_for_the_working_tree() {
+        let options = |rev_range: Option<&str>, base: Option<&str>, worktree: bool| DiffOptions {
+            root: PathBuf::from("."),
+            rev_range: rev_range.map(String::from),
+            staged: false,
+            untracked: false,
+            since: None,
+            base: base.map(String::from),
+            worktree,
+        };
+        let new = |o: DiffOptions| new_revision(&o);
+        assert_eq!(
+            new(options(Some("main..feature"), None, false)).as_deref(),
+            Some("feature")
+        );
+        assert_eq!(
+            new(options(Some("main..."), None, false)).as_deref(),
+            Some("HEAD")
+        );
+        assert_eq!(new(options(Some("v1.2"), None, false)), None);
+        assert_eq!(
+            new(options(None, Some("main"), false)).as_deref(),
+            Some("HEAD")
+        );
+        assert_eq!(new(options(None, Some("main"), true)), None);
+        assert_eq!(new(options(None, None, false)), None);
+        let staged = DiffOptions {
+            staged: true,
+            ..options(None, Some("main"), false)
+        };
+        assert_eq!(new(staged), None);
+    }
+
     #[test]
     fn parse_single_modified_file() {
         let files = parse_unified_diff(SAMPLE_DIFF);
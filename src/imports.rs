@@ -0,0 +1,825 @@
+//! Import detection and module path resolution.
+//!
+//! Recognises the import forms of the languages ContextSmith understands
+//! (Rust `use`/`mod`, Python `import`/`from`, JavaScript/TypeScript
+//! `import`/`require`, Go `import`) and resolves the imported modules to
+//! files inside the repository. Unresolvable imports (standard library,
+//! third-party packages) are simply skipped.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::scanner::ScannedFile;
+
+// ---------------------------------------------------------------------------
+// Public types
+// ---------------------------------------------------------------------------
+
+/// A single import statement found in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportStatement {
+    /// First line of the statement (1-based).
+    pub start_line: usize,
+    /// Last line of the statement (1-based, inclusive).
+    pub end_line: usize,
+    /// Module paths named by the statement, in language-native syntax
+    /// (e.g. `crate::config::Config`, `app.models`, `./util`, `net/http`).
+    pub modules: Vec<String>,
+}
+
+/// Directed dependency graph: file → files it imports.
+pub type DependencyGraph = BTreeMap<String, BTreeSet<String>>;
+
+// ---------------------------------------------------------------------------
+// Import detection
+// ---------------------------------------------------------------------------
+
+/// Find the import statements in `content` for the given language.
+///
+/// Returns an empty list for languages without import support.
+pub fn find_imports(content: &str, language: &str) -> Vec<ImportStatement> {
+    let lines: Vec<&str> = content.lines().collect();
+    match language {
+        "rust" => find_rust_imports(&lines),
+        "python" => find_python_imports(&lines),
+        "typescript" | "javascript" => find_js_imports(&lines),
+        "go" => find_go_imports(&lines),
+        _ => Vec::new(),
+    }
+}
+
+/// Line ranges of the top-level import statements in `content`.
+///
+/// Imports nested in functions, classes or inline modules (any leading
+/// indentation) are left out, so the result is the file's import header.
+pub fn import_header(content: &str, language: &str) -> Vec<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    find_imports(content, language)
+        .into_iter()
+        .filter(|import| {
+            lines
+                .get(import.start_line - 1)
+                .is_some_and(|line| !line.starts_with(char::is_whitespace))
+        })
+        .map(|import| (import.start_line, import.end_line))
+        .collect()
+}
+
+/// Rust: `use a::b::{c, d};`, `pub use ...;` and out-of-line `mod name;`.
+///
+/// `mod name;` is recorded as `self::name` since it resolves the same way.
+fn find_rust_imports(lines: &[&str]) -> Vec<ImportStatement> {
+    static USE_RE: OnceLock<Regex> = OnceLock::new();
+    static MOD_RE: OnceLock<Regex> = OnceLock::new();
+    let use_re = USE_RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+").expect("use regex is valid")
+    });
+    let mod_re = MOD_RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+            .expect("mod regex is valid")
+    });
+
+    let mut imports = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(caps) = mod_re.captures(lines[i]) {
+            imports.push(ImportStatement {
+                start_line: i + 1,
+                end_line: i + 1,
+                modules: vec![format!("self::{}", &caps[1])],
+            });
+            i += 1;
+            continue;
+        }
+        if let Some(m) = use_re.find(lines[i]) {
+            let start = i;
+            let mut text = lines[i][m.end()..].to_string();
+            while !text.contains(';') && i + 1 < lines.len() {
+                i += 1;
+                text.push(' ');
+                text.push_str(lines[i].trim());
+            }
+            let body = text.split(';').next().unwrap_or("");
+            imports.push(ImportStatement {
+                start_line: start + 1,
+                end_line: i + 1,
+                modules: expand_rust_use_tree(body),
+            });
+        }
+        i += 1;
+    }
+    imports
+}
+
+/// Expand a Rust use tree like `a::{b, c::{d, e as f}}` into flat paths.
+fn expand_rust_use_tree(tree: &str) -> Vec<String> {
+    static ALIAS_RE: OnceLock<Regex> = OnceLock::new();
+    let alias_re = ALIAS_RE.get_or_init(|| {
+        Regex::new(r"\s+as\s+[A-Za-z_][A-Za-z0-9_]*").expect("alias regex is valid")
+    });
+    let tree = alias_re.replace_all(tree, "");
+    let tree: String = tree.chars().filter(|c| !c.is_whitespace()).collect();
+    expand_compact_use_tree(tree.trim_start_matches("::"))
+}
+
+/// Expand a whitespace- and alias-free use tree.
+fn expand_compact_use_tree(tree: &str) -> Vec<String> {
+    let Some(open) = tree.find('{') else {
+        let path = tree.trim_end_matches("::*");
+        return if path.is_empty() || path == "*" {
+            Vec::new()
+        } else {
+            vec![path.to_string()]
+        };
+    };
+
+    let prefix = tree[..open].trim_end_matches("::");
+    let inner = tree[open + 1..]
+        .strip_suffix('}')
+        .unwrap_or(&tree[open + 1..]);
+
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut last = 0;
+    for (idx, ch) in inner.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[last..idx]);
+                last = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[last..]);
+
+    items
+        .into_iter()
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| {
+            if item == "self" {
+                vec![prefix.to_string()]
+            } else {
+                expand_compact_use_tree(item)
+                    .into_iter()
+                    .map(|p| format!("{prefix}::{p}"))
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// Python: `import a.b, c as d`, `from x.y import z`, `from . import w`.
+fn find_python_imports(lines: &[&str]) -> Vec<ImportStatement> {
+    let mut imports = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim_start();
+        if let Some(rest) = trimmed.strip_prefix("import ") {
+            let modules = rest
+                .split(',')
+                .map(|m| m.split(" as ").next().unwrap_or("").trim().to_string())
+                .filter(|m| !m.is_empty())
+                .collect();
+            imports.push(ImportStatement {
+                start_line: i + 1,
+                end_line: i + 1,
+                modules,
+            });
+        } else if let Some(rest) = trimmed.strip_prefix("from ") {
+            let start = i;
+            let mut text = rest.to_string();
+            if text.contains('(') {
+                while !text.contains(')') && i + 1 < lines.len() {
+                    i += 1;
+                    text.push(' ');
+                    text.push_str(lines[i].trim());
+                }
+            } else {
+                while text.trim_end().ends_with('\\') && i + 1 < lines.len() {
+                    i += 1;
+                    text = text.trim_end().trim_end_matches('\\').to_string();
+                    text.push(' ');
+                    text.push_str(lines[i].trim());
+                }
+            }
+            if let Some((module, names)) = text.split_once(" import ") {
+                let module = module.trim();
+                let joiner = if module.ends_with('.') { "" } else { "." };
+                let modules = names
+                    .trim()
+                    .trim_start_matches('(')
+                    .trim_end_matches(')')
+                    .split(',')
+                    .map(|n| n.split(" as ").next().unwrap_or("").trim())
+                    .filter(|n| !n.is_empty() && *n != "*")
+                    .map(|n| format!("{module}{joiner}{n}"))
+                    .collect::<Vec<_>>();
+                imports.push(ImportStatement {
+                    start_line: start + 1,
+                    end_line: i + 1,
+                    modules: if modules.is_empty() {
+                        vec![module.to_string()]
+                    } else {
+                        modules
+                    },
+                });
+            }
+        }
+        i += 1;
+    }
+    imports
+}
+
+/// JavaScript/TypeScript: `import ... from '...'`, `import '...'`,
+/// `export ... from '...'` and `require('...')`.
+fn find_js_imports(lines: &[&str]) -> Vec<ImportStatement> {
+    static SPEC_RE: OnceLock<Regex> = OnceLock::new();
+    static REQUIRE_RE: OnceLock<Regex> = OnceLock::new();
+    let spec_re = SPEC_RE.get_or_init(|| {
+        Regex::new(r#"(?:from\s+|import\s*\(?\s*)['"]([^'"]+)['"]"#).expect("spec regex is valid")
+    });
+    let require_re = REQUIRE_RE.get_or_init(|| {
+        Regex::new(r#"require\(\s*['"]([^'"]+)['"]\s*\)"#).expect("require regex is valid")
+    });
+
+    let mut imports = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim_start();
+        let is_import = trimmed.starts_with("import ")
+            || trimmed.starts_with("import{")
+            || (trimmed.starts_with("export ") && trimmed.contains(" from "))
+            || (trimmed.starts_with("export ") && trimmed.ends_with('{'));
+        if is_import {
+            let start = i;
+            let mut text = trimmed.to_string();
+            while !spec_re.is_match(&text) && !text.contains(';') && i + 1 < lines.len() {
+                i += 1;
+                text.push(' ');
+                text.push_str(lines[i].trim());
+            }
+            let modules: Vec<String> = spec_re
+                .captures_iter(&text)
+                .map(|c| c[1].to_string())
+                .collect();
+            if !modules.is_empty() {
+                imports.push(ImportStatement {
+                    start_line: start + 1,
+                    end_line: i + 1,
+                    modules,
+                });
+            }
+        } else if let Some(caps) = require_re.captures(lines[i]) {
+            imports.push(ImportStatement {
+                start_line: i + 1,
+                end_line: i + 1,
+                modules: vec![caps[1].to_string()],
+            });
+        }
+        i += 1;
+    }
+    imports
+}
+
+/// Go: `import "fmt"` and `import ( ... )` blocks, with optional aliases.
+fn find_go_imports(lines: &[&str]) -> Vec<ImportStatement> {
+    static QUOTED_RE: OnceLock<Regex> = OnceLock::new();
+    let quoted_re =
+        QUOTED_RE.get_or_init(|| Regex::new(r#""([^"]+)""#).expect("quoted regex is valid"));
+
+    let mut imports = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim_start();
+        if let Some(rest) = trimmed.strip_prefix("import") {
+            let rest = rest.trim_start();
+            if rest.starts_with('(') {
+                let start = i;
+                let mut modules = Vec::new();
+                let mut text = rest.to_string();
+                loop {
+                    modules.extend(quoted_re.captures_iter(&text).map(|c| c[1].to_string()));
+                    if text.contains(')') || i + 1 >= lines.len() {
+                        break;
+                    }
+                    i += 1;
+                    text = lines[i].to_string();
+                }
+                imports.push(ImportStatement {
+                    start_line: start + 1,
+                    end_line: i + 1,
+                    modules,
+                });
+            } else if let Some(caps) = quoted_re.captures(rest) {
+                imports.push(ImportStatement {
+                    start_line: i + 1,
+                    end_line: i + 1,
+                    modules: vec![caps[1].to_string()],
+                });
+            }
+        }
+        i += 1;
+    }
+    imports
+}
+
+// ---------------------------------------------------------------------------
+// Resolution
+// ---------------------------------------------------------------------------
+
+/// Resolve an imported module to a file in the repository.
+///
+/// `from_file` is the importing file and `files` the set of all known
+/// relative paths. Returns `None` for external or unresolvable modules.
+pub fn resolve_import(
+    module: &str,
+    from_file: &str,
+    language: &str,
+    files: &BTreeSet<String>,
+) -> Option<String> {
+    match language {
+        "rust" => resolve_rust(module, from_file, files),
+        "python" => resolve_python(module, from_file, files),
+        "typescript" | "javascript" => resolve_js(module, from_file, files),
+        "go" => resolve_go(module, files),
+        _ => None,
+    }
+}
+
+/// Resolve a Rust path (`crate::a::b`, `self::x`, `super::y`).
+fn resolve_rust(module: &str, from_file: &str, files: &BTreeSet<String>) -> Option<String> {
+    let (src_dir, current) = rust_module_of(from_file);
+    let mut segments: Vec<String> = Vec::new();
+    let mut parts = module.split("::").peekable();
+
+    match parts.peek().copied() {
+        Some("crate") => {
+            parts.next();
+        }
+        Some("self") => {
+            parts.next();
+            segments = current;
+        }
+        Some("super") => {
+            segments = current;
+            while parts.peek() == Some(&"super") {
+                parts.next();
+                segments.pop();
+            }
+        }
+        // Bare paths in 2018+ are either external crates or items in scope.
+        _ => return None,
+    }
+    segments.extend(parts.map(String::from));
+
+    resolve_rust_segments(&src_dir, &segments, files)
+}
+
+/// Try progressively shorter module paths until one maps to a file.
+pub(crate) fn resolve_rust_segments(
+    src_dir: &str,
+    segments: &[String],
+    files: &BTreeSet<String>,
+) -> Option<String> {
+    for len in (1..=segments.len()).rev() {
+        let base = join_path(src_dir, &segments[..len].join("/"));
+        for candidate in [format!("{base}.rs"), format!("{base}/mod.rs")] {
+            if files.contains(&candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+/// Return the crate source directory and module path of a Rust file.
+///
+/// `crates/foo/src/a/b.rs` → (`crates/foo/src`, `[a, b]`); `lib.rs`,
+/// `main.rs` and `mod.rs` name their parent module.
+pub(crate) fn rust_module_of(path: &str) -> (String, Vec<String>) {
+    let (src_dir, rest) = match path.rfind("src/") {
+        Some(idx) if idx == 0 || path[..idx].ends_with('/') => {
+            (path[..idx + 3].to_string(), &path[idx + 4..])
+        }
+        _ => match path.rfind('/') {
+            Some(idx) => (path[..idx].to_string(), &path[idx + 1..]),
+            None => (String::new(), path),
+        },
+    };
+
+    let mut segments: Vec<String> = rest.split('/').map(String::from).collect();
+    if let Some(last) = segments.pop() {
+        let stem = last.trim_end_matches(".rs");
+        let is_root = segments.is_empty() && (stem == "lib" || stem == "main");
+        if stem != "mod" && !is_root {
+            segments.push(stem.to_string());
+        }
+    }
+    (src_dir, segments)
+}
+
+/// Resolve a Python module (`a.b.c`, `.sibling`, `..pkg.mod`).
+fn resolve_python(module: &str, from_file: &str, files: &BTreeSet<String>) -> Option<String> {
+    let dots = module.chars().take_while(|&c| c == '.').count();
+    let rest = &module[dots..];
+    let segments: Vec<&str> = rest.split('.').filter(|s| !s.is_empty()).collect();
+
+    let bases: Vec<String> = if dots > 0 {
+        let mut dir = parent_dir(from_file).to_string();
+        for _ in 1..dots {
+            dir = parent_dir(&dir).to_string();
+        }
+        vec![dir]
+    } else {
+        vec![String::new(), "src".to_string()]
+    };
+
+    for base in &bases {
+        for len in (1..=segments.len()).rev() {
+            let stem = join_path(base, &segments[..len].join("/"));
+            for candidate in [format!("{stem}.py"), format!("{stem}/__init__.py")] {
+                if files.contains(&candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+        if dots > 0 && segments.is_empty() {
+            let candidate = join_path(base, "__init__.py");
+            if files.contains(&candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+/// Resolve a relative JS/TS specifier (`./util`, `../lib/index.js`).
+fn resolve_js(module: &str, from_file: &str, files: &BTreeSet<String>) -> Option<String> {
+    if !module.starts_with('.') {
+        return None;
+    }
+    let base = normalize_path(&join_path(parent_dir(from_file), module));
+    const EXTS: [&str; 6] = ["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+    if files.contains(&base) {
+        return Some(base);
+    }
+    for ext in EXTS {
+        let candidate = format!("{base}.{ext}");
+        if files.contains(&candidate) {
+            return Some(candidate);
+        }
+    }
+    for ext in EXTS {
+        let candidate = join_path(&base, &format!("index.{ext}"));
+        if files.contains(&candidate) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Resolve a Go import path to the first non-test file of the package
+/// directory whose path is the longest suffix of the import path.
+fn resolve_go(module: &str, files: &BTreeSet<String>) -> Option<String> {
+    let segments: Vec<&str> = module.split('/').collect();
+    for start in 0..segments.len() {
+        let dir = segments[start..].join("/");
+        let found = files
+            .iter()
+            .find(|f| parent_dir(f) == dir && f.ends_with(".go") && !f.ends_with("_test.go"));
+        if let Some(file) = found {
+            return Some(file.clone());
+        }
+    }
+    None
+}
+
+/// Files that make up a language-level module path.
+///
+/// Rust paths (`crate::commands::pack`, or with the crate name in place
+/// of `crate`) resolve against every `src/` directory; dotted paths
+/// (`app.services.billing`) resolve as Python modules from the root or
+/// `src/`. A module covers its own file plus everything under its
+/// directory. Returns an empty set when nothing matches.
+pub fn module_files(module: &str, files: &BTreeSet<String>) -> BTreeSet<String> {
+    let mut bases: Vec<String> = Vec::new();
+
+    if module.contains("::") || module == "crate" {
+        let segments: Vec<&str> = module.split("::").filter(|s| !s.is_empty()).collect();
+        let src_dirs: BTreeSet<String> = files
+            .iter()
+            .filter(|f| f.ends_with(".rs"))
+            .map(|f| rust_module_of(f).0)
+            .collect();
+        let paths: Vec<&[&str]> = match segments.split_first() {
+            Some((&"crate", rest)) => vec![rest],
+            // The first segment may name the crate itself.
+            Some((_, rest)) => vec![&segments[..], rest],
+            None => Vec::new(),
+        };
+        for src_dir in &src_dirs {
+            for path in &paths {
+                bases.push(join_path(src_dir, &path.join("/")));
+            }
+        }
+        return collect_module(&bases, &["rs"], files);
+    }
+
+    let segments: Vec<&str> = module.split('.').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() {
+        return BTreeSet::new();
+    }
+    for base in ["", "src"] {
+        bases.push(join_path(base, &segments.join("/")));
+    }
+    collect_module(&bases, &["py"], files)
+}
+
+/// Module files for each base path: `{base}.{ext}` plus everything
+/// under `{base}/`.
+fn collect_module(bases: &[String], exts: &[&str], files: &BTreeSet<String>) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+    for base in bases.iter().filter(|b| !b.is_empty()) {
+        for ext in exts {
+            let candidate = format!("{base}.{ext}");
+            if files.contains(&candidate) {
+                found.insert(candidate);
+            }
+        }
+        let prefix = format!("{base}/");
+        found.extend(files.iter().filter(|f| f.starts_with(&prefix)).cloned());
+    }
+    found
+}
+
+// ---------------------------------------------------------------------------
+// Dependency graph
+// ---------------------------------------------------------------------------
+
+/// Build the import graph between the given files.
+///
+/// Only edges to files inside `files` are recorded; self-edges are
+/// dropped. Files with no resolvable imports are omitted.
+pub fn dependency_graph(files: &[ScannedFile]) -> DependencyGraph {
+    let known: BTreeSet<String> = files.iter().map(|f| f.rel_path.clone()).collect();
+    let mut graph = DependencyGraph::new();
+
+    for file in files {
+        let content = match std::fs::read_to_string(&file.abs_path) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let targets: BTreeSet<String> = find_imports(&content, &file.language)
+            .iter()
+            .flat_map(|stmt| stmt.modules.iter())
+            .filter_map(|m| resolve_import(m, &file.rel_path, &file.language, &known))
+            .filter(|target| target != &file.rel_path)
+            .collect();
+        if !targets.is_empty() {
+            graph.insert(file.rel_path.clone(), targets);
+        }
+    }
+
+    graph
+}
+
+// ---------------------------------------------------------------------------
+// Path helpers
+// ---------------------------------------------------------------------------
+
+/// Directory part of a relative path (`""` for top-level files).
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |idx| &path[..idx])
+}
+
+/// Join two relative path fragments with `/`.
+fn join_path(base: &str, rest: &str) -> String {
+    if base.is_empty() {
+        rest.to_string()
+    } else if rest.is_empty() {
+        base.to_string()
+    } else {
+        format!("{base}/{rest}")
+    }
+}
+
+/// Collapse `.` and `..` components in a relative path.
+fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn import_header_skips_nested_imports() {
+        let rust = "use std::fmt;\nuse crate::a::{B,\n    C};\n\nfn f() {\n    use std::io;\n}\n";
+        assert_eq!(import_header(rust, "rust"), vec![(1, 1), (2, 3)]);
+
+        let python = "import os\n\ndef f():\n    import json\n";
+        assert_eq!(import_header(python, "python"), vec![(1, 1)]);
+
+        let go = "package main\n\nimport (\n\t\"fmt\"\n)\n";
+        assert_eq!(import_header(go, "go"), vec![(3, 5)]);
+        assert!(import_header("<p>", "html").is_empty());
+    }
+
+    #[test]
+    fn rust_use_trees_are_expanded() {
+        let content =
+            "use std::path::Path;\nuse crate::output::{self, Bundle,\n    BundleSection};\nmod cli;\n";
+        let imports = find_imports(content, "rust");
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[0].modules, vec!["std::path::Path"]);
+        assert_eq!(imports[1].start_line, 2);
+        assert_eq!(imports[1].end_line, 3);
+        assert_eq!(
+            imports[1].modules,
+            vec![
+                "crate::output",
+                "crate::output::Bundle",
+                "crate::output::BundleSection"
+            ]
+        );
+        assert_eq!(imports[2].modules, vec!["self::cli"]);
+    }
+
+    #[test]
+    fn rust_paths_resolve_to_files() {
+        let files = known(&[
+            "src/lib.rs",
+            "src/output.rs",
+            "src/commands/mod.rs",
+            "src/commands/pack.rs",
+        ]);
+        assert_eq!(
+            resolve_import(
+                "crate::output::Bundle",
+                "src/commands/pack.rs",
+                "rust",
+                &files
+            ),
+            Some("src/output.rs".to_string())
+        );
+        assert_eq!(
+            resolve_import("self::pack", "src/commands/mod.rs", "rust", &files),
+            Some("src/commands/pack.rs".to_string())
+        );
+        assert_eq!(
+            resolve_import(
+                "super::super::output",
+                "src/commands/pack.rs",
+                "rust",
+                &files
+            ),
+            Some("src/output.rs".to_string())
+        );
+        assert_eq!(
+            resolve_import("std::io", "src/lib.rs", "rust", &files),
+            None
+        );
+    }
+
+    #[test]
+    fn module_paths_cover_module_files() {
+        let files = known(&[
+            "src/lib.rs",
+            "src/commands/mod.rs",
+            "src/commands/pack.rs",
+            "src/commands/pack/budget.rs",
+            "src/commands/trim.rs",
+            "app/services/billing/__init__.py",
+            "app/services/billing/invoice.py",
+            "app/services/users.py",
+        ]);
+        let pack: Vec<&str> = vec!["src/commands/pack.rs", "src/commands/pack/budget.rs"];
+        let found = module_files("crate::commands::pack", &files);
+        assert_eq!(found.iter().map(String::as_str).collect::<Vec<_>>(), pack);
+        // The crate name works in place of `crate`.
+        assert_eq!(module_files("contextsmith::commands::pack", &files), found);
+        assert_eq!(module_files("crate::commands", &files).len(), 4);
+
+        let billing = module_files("app.services.billing", &files);
+        assert_eq!(billing.len(), 2);
+        assert_eq!(
+            module_files("app.services.users", &files),
+            known(&["app/services/users.py"])
+        );
+        assert!(module_files("crate::missing", &files).is_empty());
+        assert!(module_files("app.missing", &files).is_empty());
+    }
+
+    #[test]
+    fn python_imports_resolve() {
+        let content =
+            "import os\nfrom app.models import (\n    User,\n    Order,\n)\nfrom . import util\n";
+        let imports = find_imports(content, "python");
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[1].end_line, 5);
+        assert_eq!(
+            imports[1].modules,
+            vec!["app.models.User", "app.models.Order"]
+        );
+        assert_eq!(imports[2].modules, vec![".util"]);
+
+        let files = known(&["app/models.py", "app/util.py", "app/__init__.py"]);
+        assert_eq!(
+            resolve_import("app.models.User", "main.py", "python", &files),
+            Some("app/models.py".to_string())
+        );
+        assert_eq!(
+            resolve_import(".util", "app/views.py", "python", &files),
+            Some("app/util.py".to_string())
+        );
+        assert_eq!(resolve_import("os", "main.py", "python", &files), None);
+    }
+
+    #[test]
+    fn js_imports_resolve_relative_specifiers() {
+        let content = "import React from 'react';\nimport {\n  a,\n} from './lib/util';\nconst x = require('../x');\n";
+        let imports = find_imports(content, "typescript");
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[1].modules, vec!["./lib/util"]);
+        assert_eq!(imports[1].end_line, 4);
+
+        let files = known(&["src/lib/util.ts", "x/index.js"]);
+        assert_eq!(
+            resolve_import("./lib/util", "src/app.ts", "typescript", &files),
+            Some("src/lib/util.ts".to_string())
+        );
+        assert_eq!(
+            resolve_import("../x", "src/app.ts", "typescript", &files),
+            Some("x/index.js".to_string())
+        );
+        assert_eq!(
+            resolve_import("react", "src/app.ts", "typescript", &files),
+            None
+        );
+    }
+
+    #[test]
+    fn go_import_blocks() {
+        let content = "package main\n\nimport (\n\t\"fmt\"\n\tsrv \"example.com/app/server\"\n)\n";
+        let imports = find_imports(content, "go");
+        assert_eq!(imports.len(), 1);
+        assert_eq!((imports[0].start_line, imports[0].end_line), (3, 6));
+        assert_eq!(imports[0].modules, vec!["fmt", "example.com/app/server"]);
+
+        let files = known(&["server/server.go", "server/server_test.go"]);
+        assert_eq!(
+            resolve_import("example.com/app/server", "main.go", "go", &files),
+            Some("server/server.go".to_string())
+        );
+    }
+
+    #[test]
+    fn dependency_graph_links_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "mod util;\nuse crate::util::x;\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/util.rs"), "pub fn x() {}\n").unwrap();
+
+        let files: Vec<ScannedFile> = ["src/lib.rs", "src/util.rs"]
+            .iter()
+            .map(|p| ScannedFile {
+                rel_path: p.to_string(),
+                abs_path: dir.path().join(p),
+                language: "rust".to_string(),
+                is_generated: false,
+                size: 0,
+            })
+            .collect();
+
+        let graph = dependency_graph(&files);
+        assert_eq!(graph.len(), 1);
+        assert!(graph["src/lib.rs"].contains("src/util.rs"));
+    }
+}
@@ -9,6 +9,7 @@ use std::path::Path;
 
 use regex::Regex;
 
+use crate::cache::IndexCache;
 use crate::error::{ContextSmithError, Result};
 use crate::scanner::ScannedFile;
 
@@ -52,16 +53,34 @@ pub struct SearchResult {
 /// Files that cannot be read (binary, permission errors) are silently
 /// skipped.
 pub fn search_files(files: &[ScannedFile], pattern: &str) -> Result<SearchResult> {
+    search_files_cached(files, pattern, &mut IndexCache::disabled())
+}
+
+/// Like [`search_files`], but consults and refreshes the index cache.
+///
+/// When `pattern` is a plain literal, files whose cached terms rule out
+/// a match are skipped without being read. Every file that is read is
+/// re-indexed into the cache.
+pub fn search_files_cached(
+    files: &[ScannedFile],
+    pattern: &str,
+    cache: &mut IndexCache,
+) -> Result<SearchResult> {
     let re = Regex::new(pattern).map_err(|e| ContextSmithError::pattern(pattern, e.to_string()))?;
+    let literal = literal_pattern(pattern);
 
     let mut all_matches = Vec::new();
     let mut files_matched = 0;
 
     for file in files {
+        if literal.is_some_and(|lit| !cache.may_contain(file, lit)) {
+            continue;
+        }
         let content = match std::fs::read_to_string(&file.abs_path) {
             Ok(c) => c,
             Err(_) => continue, // Skip unreadable files (binary, permissions, etc.)
         };
+        cache.update(file, &content);
 
         let file_matches = search_content(&re, &content, &file.rel_path);
         if !file_matches.is_empty() {
@@ -99,6 +118,15 @@ pub fn search_content(re: &Regex, content: &str, file_path: &str) -> Vec<TextMat
     matches
 }
 
+/// The pattern itself if it contains no regex syntax, so it matches
+/// only as a literal substring.
+pub fn literal_pattern(pattern: &str) -> Option<&str> {
+    const META: &[char] = &[
+        '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$', '#',
+    ];
+    (!pattern.is_empty() && !pattern.contains(META)).then_some(pattern)
+}
+
 /// Group matches by file path.
 ///
 /// Returns a map from file path to the list of matches in that file,
@@ -205,6 +233,42 @@ mod tests {
         assert_eq!(result.matches.len(), 3);
     }
 
+    #[test]
+    fn literal_pattern_rejects_regex_syntax() {
+        assert_eq!(literal_pattern("greedy_pack"), Some("greedy_pack"));
+        assert_eq!(literal_pattern("hello world"), Some("hello world"));
+        assert_eq!(literal_pattern(r"fn\s+run"), None);
+        assert_eq!(literal_pattern("a|b"), None);
+        assert_eq!(literal_pattern("(?i)run"), None);
+        assert_eq!(literal_pattern(""), None);
+    }
+
+    #[test]
+    fn search_files_cached_skips_files_ruled_out_by_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn alpha() {}").unwrap();
+        let file = ScannedFile {
+            rel_path: "a.rs".to_string(),
+            abs_path: dir.path().join("a.rs"),
+            language: "rust".to_string(),
+            is_generated: false,
+            size: 0,
+        };
+        let config = crate::config::CacheConfig {
+            enabled: true,
+            dir: None,
+        };
+        let mut cache = IndexCache::open(dir.path(), &config);
+
+        let files = std::slice::from_ref(&file);
+        let result = search_files_cached(files, "alpha", &mut cache).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert!(cache.fresh(&file).is_some());
+        assert!(!cache.may_contain(&file, "beta"));
+        let result = search_files_cached(files, "beta", &mut cache).unwrap();
+        assert!(result.matches.is_empty());
+    }
+
     #[test]
     fn search_files_invalid_pattern_errors() {
         let result = search_files(&[], "[invalid");
@@ -0,0 +1,418 @@
+//! Lexical relevance: identifier-aware tokenisation and BM25 scoring.
+//!
+//! Source text is split into lowercase terms, breaking `camelCase`,
+//! `PascalCase` and `snake_case` identifiers into their parts. A
+//! [`Corpus`] holds document frequencies over the scanned files, and
+//! [`Corpus::bm25`] scores a section against a multi-term query with
+//! length normalisation. [`free_text_terms`] turns a natural-language
+//! query into stemmed search terms.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::cache::IndexCache;
+use crate::scanner::ScannedFile;
+
+/// BM25 term-frequency saturation.
+const K1: f64 = 1.2;
+
+/// BM25 length normalisation strength (0 = none, 1 = full).
+const B: f64 = 0.75;
+
+/// Shortest term kept by the tokenizer.
+const MIN_TERM_LEN: usize = 2;
+
+/// Shortest stem left after stripping a suffix. Stems are matched as
+/// substrings, so shorter ones (`str` from `string`) match unrelated
+/// words.
+const MIN_STEM_LEN: usize = 5;
+
+/// English words that carry no meaning in a code search query.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "all", "an", "and", "any", "are", "as", "at", "be", "by", "can", "do", "does",
+    "for", "from", "how", "i", "if", "in", "into", "is", "it", "its", "me", "my", "no", "not",
+    "of", "on", "or", "our", "should", "so", "that", "the", "their", "then", "there", "these",
+    "this", "to", "was", "we", "what", "when", "where", "which", "who", "why", "will", "with",
+];
+
+// ---------------------------------------------------------------------------
+// Tokenisation
+// ---------------------------------------------------------------------------
+
+/// Split text into lowercase terms.
+///
+/// Words are separated by anything that is not a letter or digit (so
+/// `snake_case` splits on `_`), then split again at case boundaries:
+/// `parseHTTPResponse` yields `parse`, `http`, `response`. Compound
+/// identifiers also keep their whole lowercase form, so an exact
+/// identifier match outscores its parts appearing separately.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        let parts = split_identifier(word);
+        if parts.len() > 1 && word.len() >= MIN_TERM_LEN {
+            terms.push(word.to_lowercase());
+        }
+        terms.extend(
+            parts
+                .into_iter()
+                .filter(|p| p.len() >= MIN_TERM_LEN)
+                .map(|p| p.to_lowercase()),
+        );
+    }
+    terms
+}
+
+/// Distinct query terms from a search string.
+///
+/// Regex syntax is treated as separators, so a `--grep` pattern like
+/// `fn\s+greedy_pack` yields `fn`, `greedy`, `pack`.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tokenize(&strip_regex_escapes(query))
+        .into_iter()
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+/// Search terms for a natural-language query.
+///
+/// Words are split like identifiers (`retryBackoff` → `retry`, `backoff`)
+/// but compounds are not kept whole; stopwords are dropped and the rest
+/// stemmed with [`stem`]. Terms are distinct and in query order.
+pub fn free_text_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .flat_map(split_identifier)
+        .map(str::to_lowercase)
+        .filter(|w| w.len() >= MIN_TERM_LEN && !is_stopword(w))
+        .map(|w| stem(&w))
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+/// Whether `word` is an English stopword (case-insensitive).
+pub fn is_stopword(word: &str) -> bool {
+    STOPWORDS.contains(&word.to_lowercase().as_str())
+}
+
+/// Strip a common English inflection: `retries` → `retri`,
+/// `handling` → `handl`, `caches` → `cache`, `models` → `model`.
+///
+/// Stems are matched as case-insensitive substrings, so a stem must be a
+/// prefix shared by the word's forms (`retri` finds `retries` and
+/// `retried`) and long enough not to turn up inside unrelated words:
+/// suffixes that would leave fewer than five characters are not
+/// stripped, so `string` stays whole.
+pub fn stem(word: &str) -> String {
+    const SUFFIXES: &[&str] = &["ies", "ied", "ing", "ed", "es", "s"];
+    for suffix in SUFFIXES {
+        if let Some(base) = word.strip_suffix(suffix) {
+            // `class`, `status` and `analysis` are not plurals.
+            let not_plural = *suffix == "s" && base.ends_with(['s', 'u', 'i']);
+            if base.len() >= MIN_STEM_LEN && !not_plural {
+                return base.to_string();
+            }
+        }
+    }
+    word.to_string()
+}
+
+/// Split one alphanumeric word at lower→upper and acronym→word boundaries.
+fn split_identifier(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..chars.len() {
+        let (idx, c) = chars[i];
+        let prev = chars[i - 1].1;
+        let next_is_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+        let boundary = (c.is_uppercase() && (prev.is_lowercase() || prev.is_ascii_digit()))
+            || (c.is_uppercase() && prev.is_uppercase() && next_is_lower);
+        if boundary {
+            parts.push(&word[start..idx]);
+            start = idx;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+/// Drop regex escape sequences such as `\s`, `\b` or `\d` so their
+/// letters are not mistaken for query terms.
+fn strip_regex_escapes(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Corpus statistics
+// ---------------------------------------------------------------------------
+
+/// Document frequencies over a set of documents (one per file).
+#[derive(Debug, Clone, Default)]
+pub struct Corpus {
+    /// Number of documents containing each term.
+    doc_freq: HashMap<String, usize>,
+    /// Total number of documents.
+    doc_count: usize,
+}
+
+impl Corpus {
+    /// Build a corpus from document texts.
+    pub fn from_documents<'a, I>(documents: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut corpus = Self::default();
+        for doc in documents {
+            corpus.add_document(doc);
+        }
+        corpus
+    }
+
+    /// Build a corpus over scanned files. Unreadable files are skipped.
+    pub fn from_files(files: &[ScannedFile]) -> Self {
+        let mut corpus = Self::default();
+        for file in files {
+            if let Ok(content) = std::fs::read_to_string(&file.abs_path) {
+                corpus.add_document(&content);
+            }
+        }
+        corpus
+    }
+
+    /// Build a corpus from the index cache, indexing files that are stale.
+    ///
+    /// Equivalent to [`Corpus::from_files`], but fresh files are not read.
+    pub fn from_cache(files: &[ScannedFile], cache: &mut IndexCache) -> Self {
+        let mut corpus = Self::default();
+        for file in files {
+            if let Some(entry) = cache.load(file) {
+                for term in entry.term_freqs.keys() {
+                    *corpus.doc_freq.entry(term.clone()).or_default() += 1;
+                }
+                corpus.doc_count += 1;
+            }
+        }
+        corpus
+    }
+
+    /// Count one document's distinct terms.
+    pub fn add_document(&mut self, text: &str) {
+        let distinct: HashSet<String> = tokenize(text).into_iter().collect();
+        for term in distinct {
+            *self.doc_freq.entry(term).or_default() += 1;
+        }
+        self.doc_count += 1;
+    }
+
+    /// Number of documents in the corpus.
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    /// BM25 inverse document frequency: `ln(1 + (N - df + 0.5) / (df + 0.5))`.
+    ///
+    /// Always positive; terms missing from the corpus get the largest value.
+    pub fn idf(&self, term: &str) -> f64 {
+        let n = self.doc_count as f64;
+        let df = self.doc_freq.get(term).copied().unwrap_or(0) as f64;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    /// BM25 score of `text` for the query `terms`.
+    ///
+    /// `avg_len` is the average term count of the texts being compared,
+    /// so long sections do not win just by repeating a term.
+    pub fn bm25(&self, terms: &[String], text: &str, avg_len: f64) -> f64 {
+        let tokens = tokenize(text);
+        if tokens.is_empty() || terms.is_empty() {
+            return 0.0;
+        }
+
+        let mut tf: HashMap<&str, usize> = HashMap::new();
+        for token in &tokens {
+            *tf.entry(token.as_str()).or_default() += 1;
+        }
+
+        let len_ratio = if avg_len > 0.0 {
+            tokens.len() as f64 / avg_len
+        } else {
+            1.0
+        };
+        terms
+            .iter()
+            .map(|term| {
+                let f = tf.get(term.as_str()).copied().unwrap_or(0) as f64;
+                if f == 0.0 {
+                    return 0.0;
+                }
+                self.idf(term) * f * (K1 + 1.0) / (f + K1 * (1.0 - B + B * len_ratio))
+            })
+            .sum()
+    }
+}
+
+/// Average term count over texts (0.0 for an empty slice).
+pub fn average_length<'a, I>(texts: I) -> f64
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let (total, count) = texts.into_iter().fold((0usize, 0usize), |(t, c), text| {
+        (t + tokenize(text).len(), c + 1)
+    });
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_splits_identifiers() {
+        assert_eq!(
+            tokenize("parseHTTPResponse(greedy_pack, x)"),
+            vec![
+                "parsehttpresponse",
+                "parse",
+                "http",
+                "response",
+                "greedy",
+                "pack"
+            ]
+        );
+        assert_eq!(
+            tokenize("TokenEstimator"),
+            vec!["tokenestimator", "token", "estimator"]
+        );
+        assert_eq!(tokenize("utf8Decode"), vec!["utf8decode", "utf8", "decode"]);
+    }
+
+    #[test]
+    fn query_terms_ignore_regex_syntax() {
+        assert_eq!(
+            query_terms(r"fn\s+greedy_pack"),
+            vec!["fn", "greedy", "pack"]
+        );
+        assert_eq!(query_terms("retry retry backoff"), vec!["retry", "backoff"]);
+    }
+
+    #[test]
+    fn free_text_terms_drop_stopwords_and_stem() {
+        assert_eq!(
+            free_text_terms("how does the retryBackoff handle HTTP retries?"),
+            vec!["retry", "backoff", "handle", "http", "retri"]
+        );
+        assert_eq!(
+            free_text_terms("handling models and caches"),
+            vec!["handl", "model", "cache"]
+        );
+        assert!(free_text_terms("what is the").is_empty());
+    }
+
+    #[test]
+    fn stem_keeps_short_and_double_s_words() {
+        assert_eq!(stem("class"), "class");
+        assert_eq!(stem("uses"), "uses");
+        assert_eq!(stem("string"), "string");
+        assert_eq!(stem("strings"), "string");
+        assert_eq!(stem("status"), "status");
+        assert_eq!(stem("http"), "http");
+    }
+
+    #[test]
+    fn stems_are_shared_by_every_form() {
+        for word in ["retries", "retried", "policies", "caches", "handling"] {
+            let stem = stem(word);
+            assert!(word.starts_with(&stem), "{word} → {stem}");
+        }
+        assert_eq!(stem("retries"), stem("retried"));
+        assert!("policy".starts_with(&stem("policies")));
+    }
+
+    #[test]
+    fn rare_terms_have_higher_idf() {
+        let corpus = Corpus::from_documents(["fn config", "fn run", "fn budget config"]);
+        assert_eq!(corpus.doc_count(), 3);
+        assert!(corpus.idf("budget") > corpus.idf("config"));
+        assert!(corpus.idf("config") > corpus.idf("fn"));
+        assert!(corpus.idf("missing") > corpus.idf("budget"));
+    }
+
+    #[test]
+    fn bm25_rewards_rare_terms_and_coverage() {
+        let corpus = Corpus::from_documents(["fn config", "fn run", "fn budget config", "fn"]);
+        let terms = query_terms("fn budget");
+        let avg = 3.0;
+
+        let rare = corpus.bm25(&terms, "fn budget", avg);
+        let common = corpus.bm25(&terms, "fn fn", avg);
+        assert!(rare > common);
+
+        let both = corpus.bm25(&query_terms("budget config"), "budget config", avg);
+        let one = corpus.bm25(&query_terms("budget config"), "budget other", avg);
+        assert!(both > one);
+    }
+
+    #[test]
+    fn bm25_normalises_length() {
+        let corpus = Corpus::from_documents(["alpha beta", "gamma"]);
+        let terms = query_terms("alpha");
+        let short = corpus.bm25(&terms, "alpha beta", 4.0);
+        let long = corpus.bm25(&terms, "alpha beta gamma delta epsilon zeta eta theta", 4.0);
+        assert!(short > long);
+        assert_eq!(corpus.bm25(&terms, "", 4.0), 0.0);
+    }
+
+    #[test]
+    fn corpus_from_cache_matches_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<ScannedFile> = [("a.rs", "fn config()"), ("b.rs", "fn budget()")]
+            .iter()
+            .map(|(name, content)| {
+                std::fs::write(dir.path().join(name), content).unwrap();
+                ScannedFile {
+                    rel_path: name.to_string(),
+                    abs_path: dir.path().join(name),
+                    language: "rust".to_string(),
+                    is_generated: false,
+                    size: content.len() as u64,
+                }
+            })
+            .collect();
+
+        let direct = Corpus::from_files(&files);
+        let cached = Corpus::from_cache(&files, &mut IndexCache::disabled());
+        assert_eq!(cached.doc_count(), direct.doc_count());
+        assert_eq!(cached.idf("budget"), direct.idf("budget"));
+        assert_eq!(cached.idf("fn"), direct.idf("fn"));
+    }
+
+    #[test]
+    fn average_length_counts_terms() {
+        assert_eq!(average_length(["ab_cd ef", "gh"]), 2.0);
+        assert_eq!(average_length(std::iter::empty()), 0.0);
+    }
+}
@@ -1,14 +1,20 @@
+pub mod cache;
 pub mod cli;
 pub mod commands;
 pub mod config;
 pub mod error;
 pub mod git;
+pub mod imports;
 pub mod indexer;
+pub mod lexical;
 pub mod manifest;
+pub mod models;
 pub mod output;
 pub mod ranker;
+pub mod related;
 pub mod scanner;
 pub mod slicer;
 pub mod symbols;
 pub mod tokens;
+pub mod units;
 pub mod utils;
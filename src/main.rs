@@ -6,12 +6,15 @@ use colored::Colorize;
 
 use contextsmith::cli::{Cli, ColorMode, Command};
 use contextsmith::commands;
+use contextsmith::commands::cache::CacheCommandOptions;
 use contextsmith::commands::collect::CollectCommandOptions;
 use contextsmith::commands::diff::DiffCommandOptions;
 use contextsmith::commands::explain::ExplainCommandOptions;
 use contextsmith::commands::init::{InitOptions, InitResult};
+use contextsmith::commands::map::MapCommandOptions;
 use contextsmith::commands::pack::PackCommandOptions;
 use contextsmith::commands::stats::StatsCommandOptions;
+use contextsmith::commands::trim::TrimCommandOptions;
 use contextsmith::error::ContextSmithError;
 
 fn main() {
@@ -65,33 +68,46 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
         }
         Command::Diff {
             rev_range,
+            base,
+            worktree,
             staged,
             untracked,
             since,
             hunks_only,
             context,
+            function_context,
+            show_old,
+            commits,
+            group_by_commit,
             include_related,
             format,
             out,
             stdout,
             budget,
+            model,
         } => {
             let root = resolve_root(cli.root)?;
             commands::diff::run(DiffCommandOptions {
                 root,
                 rev_range,
+                base,
+                worktree,
                 staged,
                 untracked,
                 since,
                 hunks_only,
                 context_lines: context,
+                function_context,
+                show_old,
+                commits,
+                group_by_commit,
                 include_related,
                 format,
                 out,
                 stdout,
                 quiet: cli.quiet,
                 budget,
-                model: None,
+                model,
                 config_path: cli.config,
             })
         }
@@ -105,7 +121,9 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
             lang,
             path,
             diff,
+            diff_only,
             span,
+            context,
             max_snippets,
             include_defs,
             include_refs,
@@ -117,57 +135,39 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
             out,
             stdout,
             budget,
-            ..
+            model,
         } => {
             let root = resolve_root(cli.root)?;
-            // Treat positional query as implicit --grep when no explicit mode is set.
-            let effective_grep = grep.or(query);
-            let mut ignored_flags_used = Vec::new();
-            if scope.is_some() {
-                ignored_flags_used.push("--scope".to_string());
-            }
-            if diff.is_some() {
-                ignored_flags_used.push("--diff".to_string());
-            }
-            if span.is_some() {
-                ignored_flags_used.push("--span".to_string());
-            }
-            if max_snippets.is_some() {
-                ignored_flags_used.push("--max-snippets".to_string());
-            }
-            if include_defs {
-                ignored_flags_used.push("--include-defs".to_string());
-            }
-            if include_refs {
-                ignored_flags_used.push("--include-refs".to_string());
-            }
-            if include_imports {
-                ignored_flags_used.push("--include-imports".to_string());
-            }
-            if tests {
-                ignored_flags_used.push("--tests".to_string());
-            }
-            if rank.is_some() {
-                ignored_flags_used.push("--rank".to_string());
-            }
             commands::collect::run(CollectCommandOptions {
                 root,
+                scope,
                 files,
-                grep: effective_grep,
+                query,
+                grep,
                 symbol,
                 exclude,
                 lang,
                 path,
-                context_lines: 3,
+                span,
+                context,
                 max_files,
+                max_snippets,
+                include_defs,
+                include_refs,
+                include_imports,
+                tests,
+                diff,
+                diff_only,
+                rank,
                 format,
                 out,
                 stdout,
                 quiet: cli.quiet,
                 budget,
-                model: None,
+                model,
                 config_path: cli.config,
-                ignored_flags_used,
+                no_cache: cli.no_cache,
+                cache_dir: cli.cache_dir,
             })
         }
         Command::Pack {
@@ -183,6 +183,7 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
             stdout,
             out,
         } => commands::pack::run(PackCommandOptions {
+            root: resolve_root(cli.root)?,
             bundle,
             budget,
             chars,
@@ -197,8 +198,52 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
             quiet: cli.quiet,
             config_path: cli.config,
         }),
-        Command::Trim { .. } => commands::not_implemented("trim"),
-        Command::Map { .. } => commands::not_implemented("map"),
+        Command::Trim {
+            input,
+            budget,
+            chars,
+            model,
+            reserve,
+            keep_head,
+            keep_manifest,
+            out,
+            stdout,
+        } => commands::trim::run(TrimCommandOptions {
+            input,
+            budget,
+            chars,
+            model,
+            reserve,
+            keep_head,
+            keep_manifest,
+            out,
+            stdout,
+            quiet: cli.quiet,
+            config_path: cli.config,
+        }),
+        Command::Map {
+            full,
+            text,
+            symbols,
+            graph,
+            format,
+            out,
+            watch,
+        } => {
+            let root = resolve_root(cli.root)?;
+            commands::map::run(MapCommandOptions {
+                root,
+                full,
+                text,
+                symbols,
+                graph,
+                format,
+                out,
+                watch,
+                quiet: cli.quiet,
+                config_path: cli.config,
+            })
+        }
         Command::Stats {
             bundle,
             top_files,
@@ -216,6 +261,8 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
                 tokens,
                 quiet: cli.quiet,
                 config_path: cli.config,
+                no_cache: cli.no_cache,
+                cache_dir: cli.cache_dir,
             })
         }
         Command::Explain {
@@ -230,6 +277,16 @@ fn run(cli: Cli) -> Result<(), ContextSmithError> {
             show_weights,
             quiet: cli.quiet,
         }),
+        Command::Cache { action } => {
+            let root = resolve_root(cli.root)?;
+            commands::cache::run(CacheCommandOptions {
+                root,
+                action,
+                cache_dir: cli.cache_dir,
+                quiet: cli.quiet,
+                config_path: cli.config,
+            })
+        }
     }
 }
 
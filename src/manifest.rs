@@ -4,6 +4,7 @@
 //! and whether it was included in the final output. This enables the
 //! `explain` command and budget introspection.
 
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
@@ -40,6 +41,21 @@ pub struct ManifestSummary {
     pub model: String,
     /// Ranking weights used (if applicable).
     pub weights_used: Option<WeightsUsed>,
+    /// Ranking strategy that ordered the entries (if applicable).
+    #[serde(default)]
+    pub ranking_strategy: Option<String>,
+    /// Match windows dropped per file by a per-file snippet cap.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dropped_windows: BTreeMap<String, usize>,
+    /// References to the collected symbol per file (`collect --include-refs`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub references: BTreeMap<String, usize>,
+    /// Merge-base commit the diff started from (`diff --base`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_sha: Option<String>,
+    /// `HEAD` commit when the diff was taken (`diff --base`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_sha: Option<String>,
 }
 
 /// Ranking weights applied during snippet selection.
@@ -102,6 +118,11 @@ pub fn build_manifest(
             included_count,
             model: model.to_string(),
             weights_used: None,
+            ranking_strategy: None,
+            dropped_windows: BTreeMap::new(),
+            references: BTreeMap::new(),
+            base_sha: None,
+            head_sha: None,
         },
         entries,
     }
@@ -136,6 +157,15 @@ pub fn read_manifest(path: &Path) -> Result<Manifest> {
     })
 }
 
+/// Read the manifest written next to `bundle_path`, if there is one.
+pub fn read_sibling_manifest(bundle_path: &Path) -> Option<Manifest> {
+    let path = crate::utils::manifest_sibling_path(bundle_path);
+    if !path.exists() {
+        return None;
+    }
+    read_manifest(&path).ok()
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
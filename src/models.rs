@@ -0,0 +1,318 @@
+//! Model registry: context windows, output reserves and tokenizers.
+//!
+//! Ships with specs for common OpenAI and Anthropic models and can be
+//! extended (or overridden) through `[models.<name>]` tables in
+//! `contextsmith.toml`. Commands use the registry to pick a token
+//! estimator and, when only `--model` is given, to derive the budget.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::tokens::{self, CharEstimator, Encoding, ModelFamily, TokenEstimator};
+
+// ---------------------------------------------------------------------------
+// Public types
+// ---------------------------------------------------------------------------
+
+/// Tokenizer used to count a model's tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Tokenizer {
+    /// BPE with the `cl100k_base` vocabulary (heuristic if not installed).
+    Cl100kBase,
+    /// BPE with the `o200k_base` vocabulary (heuristic if not installed).
+    O200kBase,
+    /// Claude character heuristic (~3.5 chars/token).
+    Claude,
+    /// Generic character heuristic (~4 chars/token).
+    #[default]
+    Heuristic,
+}
+
+impl Tokenizer {
+    /// Build an estimator for this tokenizer.
+    pub fn estimator(self) -> Box<dyn TokenEstimator> {
+        let encoding = match self {
+            Self::Cl100kBase => Encoding::Cl100kBase,
+            Self::O200kBase => Encoding::O200kBase,
+            Self::Claude | Self::Heuristic => return Box::new(self.char_estimator()),
+        };
+        match tokens::bpe_estimator(encoding) {
+            Some(estimator) => Box::new(estimator),
+            None => Box::new(self.char_estimator()),
+        }
+    }
+
+    /// The character heuristic for this tokenizer, also used when a BPE
+    /// vocabulary is not installed.
+    pub fn char_estimator(self) -> CharEstimator {
+        match self {
+            Self::Cl100kBase | Self::O200kBase => CharEstimator::new(ModelFamily::Gpt4),
+            Self::Claude => CharEstimator::new(ModelFamily::Claude),
+            Self::Heuristic => CharEstimator::new(ModelFamily::Unknown),
+        }
+    }
+}
+
+/// Everything ContextSmith needs to know about a model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSpec {
+    /// Total context window in tokens (prompt + response).
+    pub context_window: usize,
+    /// Tokens reserved for the model's response.
+    pub output_reserve: usize,
+    /// Tokenizer used for estimation.
+    pub tokenizer: Tokenizer,
+}
+
+impl Default for ModelSpec {
+    fn default() -> Self {
+        Self {
+            context_window: 8192,
+            output_reserve: 1024,
+            tokenizer: Tokenizer::Heuristic,
+        }
+    }
+}
+
+/// Budget and response reserve after applying model defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetPlan {
+    /// Total token budget (recorded in the manifest).
+    pub budget: Option<usize>,
+    /// Tokens held back for the response.
+    pub reserve: usize,
+}
+
+impl BudgetPlan {
+    /// Tokens available for context: budget minus reserve.
+    pub fn effective(&self) -> Option<usize> {
+        self.budget.map(|b| b.saturating_sub(self.reserve))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Built-in model specs: (name, context window, output reserve, tokenizer).
+const BUILTIN_MODELS: &[(&str, usize, usize, Tokenizer)] = &[
+    ("gpt-3.5-turbo", 16_385, 4_096, Tokenizer::Cl100kBase),
+    ("gpt-4", 8_192, 1_024, Tokenizer::Cl100kBase),
+    ("gpt-4-turbo", 128_000, 4_096, Tokenizer::Cl100kBase),
+    ("gpt-4o", 128_000, 16_384, Tokenizer::O200kBase),
+    ("gpt-4o-mini", 128_000, 16_384, Tokenizer::O200kBase),
+    ("gpt-4.1", 1_047_576, 32_768, Tokenizer::O200kBase),
+    ("o1", 200_000, 100_000, Tokenizer::O200kBase),
+    ("o3", 200_000, 100_000, Tokenizer::O200kBase),
+    ("o3-mini", 200_000, 100_000, Tokenizer::O200kBase),
+    ("o4-mini", 200_000, 100_000, Tokenizer::O200kBase),
+    ("claude", 200_000, 8_192, Tokenizer::Claude),
+    ("claude-3-haiku", 200_000, 4_096, Tokenizer::Claude),
+    ("claude-3-opus", 200_000, 4_096, Tokenizer::Claude),
+    ("claude-3-5-haiku", 200_000, 8_192, Tokenizer::Claude),
+    ("claude-3-5-sonnet", 200_000, 8_192, Tokenizer::Claude),
+    ("claude-3-7-sonnet", 200_000, 64_000, Tokenizer::Claude),
+    ("claude-haiku", 200_000, 8_192, Tokenizer::Claude),
+    ("claude-opus", 200_000, 32_000, Tokenizer::Claude),
+    ("claude-sonnet", 200_000, 64_000, Tokenizer::Claude),
+];
+
+/// Known models, keyed by name.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    models: BTreeMap<String, ModelSpec>,
+}
+
+impl ModelRegistry {
+    /// Registry with only the built-in models.
+    pub fn builtin() -> Self {
+        let models = BUILTIN_MODELS
+            .iter()
+            .map(|&(name, context_window, output_reserve, tokenizer)| {
+                (
+                    name.to_string(),
+                    ModelSpec {
+                        context_window,
+                        output_reserve,
+                        tokenizer,
+                    },
+                )
+            })
+            .collect();
+        Self { models }
+    }
+
+    /// Built-in models plus (and overridden by) `[models.<name>]` config tables.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::builtin();
+        for (name, spec) in &config.models {
+            registry.models.insert(name.to_lowercase(), spec.clone());
+        }
+        registry
+    }
+
+    /// Look up a model by name.
+    ///
+    /// Exact names win; otherwise the longest registered name that prefixes
+    /// `name` is used, so dated or suffixed variants (`gpt-4o-2024-08-06`,
+    /// `claude-sonnet-4-5`) resolve to their family entry.
+    pub fn lookup(&self, name: &str) -> Option<&ModelSpec> {
+        let lower = name.to_lowercase();
+        if let Some(spec) = self.models.get(&lower) {
+            return Some(spec);
+        }
+        self.models
+            .iter()
+            .filter(|(key, _)| {
+                lower.starts_with(key.as_str())
+                    && lower[key.len()..].starts_with(['-', '@', ':', '.'])
+            })
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, spec)| spec)
+    }
+
+    /// Estimator for the named model: the registered tokenizer if known,
+    /// otherwise the best guess from the model name.
+    pub fn estimator(&self, name: &str) -> Box<dyn TokenEstimator> {
+        match self.lookup(name) {
+            Some(spec) => spec.tokenizer.estimator(),
+            None => tokens::estimator_for_model(name),
+        }
+    }
+
+    /// Token budget for a `--chars` budget of `chars` characters.
+    ///
+    /// Uses the model's fixed characters-per-token ratio rather than its
+    /// estimator: BPE merges repeated filler into a handful of tokens, so
+    /// estimating a run of placeholder text would shrink the budget.
+    pub fn chars_to_tokens(&self, name: &str, chars: usize) -> usize {
+        let estimator = match self.lookup(name) {
+            Some(spec) => spec.tokenizer.char_estimator(),
+            None => CharEstimator::new(tokens::parse_model(name)),
+        };
+        estimator.tokens_for_chars(chars)
+    }
+
+    /// Resolve budget and reserve from explicit flags, falling back to the
+    /// explicitly requested model's context window and output reserve.
+    ///
+    /// `model` should be the `--model` flag only (not the config default),
+    /// so commands stay unbudgeted unless the user asked for a model.
+    pub fn plan_budget(
+        &self,
+        budget: Option<usize>,
+        reserve: Option<usize>,
+        model: Option<&str>,
+    ) -> BudgetPlan {
+        if budget.is_some() {
+            return BudgetPlan {
+                budget,
+                reserve: reserve.unwrap_or(0),
+            };
+        }
+        match model.and_then(|m| self.lookup(m)) {
+            Some(spec) => BudgetPlan {
+                budget: Some(spec.context_window),
+                reserve: reserve.unwrap_or(spec.output_reserve),
+            },
+            None => BudgetPlan {
+                budget: None,
+                reserve: reserve.unwrap_or(0),
+            },
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chars_convert_at_the_fixed_ratio() {
+        let registry = ModelRegistry::builtin();
+        assert_eq!(registry.chars_to_tokens("gpt-4o", 4_000), 1_000);
+        assert_eq!(registry.chars_to_tokens("claude-sonnet-4-5", 3_500), 1_000);
+        assert_eq!(registry.chars_to_tokens("llama-70b", 4_000), 1_000);
+    }
+
+    #[test]
+    fn lookup_exact_and_prefixed_names() {
+        let registry = ModelRegistry::builtin();
+        assert_eq!(registry.lookup("gpt-4").unwrap().context_window, 8_192);
+        assert_eq!(
+            registry.lookup("gpt-4o-2024-08-06").unwrap().output_reserve,
+            16_384
+        );
+        assert_eq!(
+            registry.lookup("Claude-Sonnet-4-5").unwrap().tokenizer,
+            Tokenizer::Claude
+        );
+        // `gpt-4o` must not match the shorter `gpt-4` entry by accident.
+        assert_eq!(
+            registry.lookup("gpt-4o").unwrap().tokenizer,
+            Tokenizer::O200kBase
+        );
+        assert!(registry.lookup("gpt-4x").is_none());
+        assert!(registry.lookup("llama-70b").is_none());
+    }
+
+    #[test]
+    fn config_models_extend_and_override() {
+        let mut config = Config::default();
+        config.models.insert(
+            "llama-70b".into(),
+            ModelSpec {
+                context_window: 32_000,
+                output_reserve: 2_000,
+                tokenizer: Tokenizer::Heuristic,
+            },
+        );
+        config.models.insert(
+            "gpt-4".into(),
+            ModelSpec {
+                context_window: 32_768,
+                ..ModelSpec::default()
+            },
+        );
+
+        let registry = ModelRegistry::from_config(&config);
+        assert_eq!(registry.lookup("llama-70b").unwrap().context_window, 32_000);
+        assert_eq!(registry.lookup("gpt-4").unwrap().context_window, 32_768);
+    }
+
+    #[test]
+    fn plan_budget_prefers_explicit_flags() {
+        let registry = ModelRegistry::builtin();
+
+        let plan = registry.plan_budget(Some(1000), Some(100), Some("claude"));
+        assert_eq!(plan.budget, Some(1000));
+        assert_eq!(plan.effective(), Some(900));
+
+        let plan = registry.plan_budget(None, None, Some("claude-sonnet"));
+        assert_eq!(plan.budget, Some(200_000));
+        assert_eq!(plan.reserve, 64_000);
+        assert_eq!(plan.effective(), Some(136_000));
+
+        let plan = registry.plan_budget(None, Some(500), Some("gpt-4"));
+        assert_eq!(plan.effective(), Some(7_692));
+
+        let plan = registry.plan_budget(None, None, Some("llama-70b"));
+        assert_eq!(plan.effective(), None);
+        assert_eq!(registry.plan_budget(None, None, None).budget, None);
+    }
+
+    #[test]
+    fn estimator_falls_back_for_unknown_models() {
+        let registry = ModelRegistry::builtin();
+        assert_eq!(registry.estimator("claude-3-opus").estimate("abcdefg"), 2);
+        assert_eq!(registry.estimator("mystery-model").estimate("abcdefgh"), 2);
+    }
+}
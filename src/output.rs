@@ -185,6 +185,254 @@ fn escape_xml(s: &str) -> String {
         .replace('"', "&quot;")
 }
 
+// ---------------------------------------------------------------------------
+// Parsing
+// ---------------------------------------------------------------------------
+
+/// Guess the format of a rendered bundle from its path and content.
+///
+/// The file extension wins when it is recognised; otherwise the content
+/// is sniffed for the markers each formatter emits.
+pub fn detect_format(path: &Path, content: &str) -> Format {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "json" => return Format::Json,
+        "xml" => return Format::Xml,
+        "md" | "markdown" => return Format::Markdown,
+        _ => {}
+    }
+
+    let trimmed = content.trim_start();
+    if trimmed.starts_with('{') {
+        Format::Json
+    } else if trimmed.starts_with("<?xml") || trimmed.starts_with("<bundle>") {
+        Format::Xml
+    } else if trimmed.starts_with("# Context Bundle") {
+        Format::Markdown
+    } else {
+        Format::Plain
+    }
+}
+
+/// Parse a rendered bundle back into a [`Bundle`].
+///
+/// This is the inverse of [`format_bundle`]. Plain output does not carry
+/// languages or reasons, so those are inferred from the file path and
+/// left empty respectively.
+pub fn parse_bundle(content: &str, format: Format) -> Result<Bundle> {
+    match format {
+        Format::Json => serde_json::from_str(content).map_err(|e| {
+            ContextSmithError::config_with_source("failed to parse bundle as JSON", e)
+        }),
+        Format::Markdown => parse_markdown(content),
+        Format::Plain => Ok(parse_plain(content)),
+        Format::Xml => parse_xml(content),
+    }
+}
+
+/// Parse the output of [`format_markdown`].
+///
+/// A code fence only closes a section when it is followed by a blank line
+/// and then either the next section header or the end of input, so
+/// snippets that themselves contain fences survive the round trip.
+fn parse_markdown(content: &str) -> Result<Bundle> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut i = 0;
+
+    if lines.first().map(|l| l.trim()) != Some("# Context Bundle") {
+        return Err(ContextSmithError::config(
+            "markdown bundle must start with '# Context Bundle'",
+        ));
+    }
+    i += 1;
+    skip_blank(&lines, &mut i);
+
+    let mut summary = String::new();
+    if let Some(rest) = lines.get(i).and_then(|l| l.strip_prefix("> ")) {
+        summary = rest.to_string();
+        i += 1;
+    }
+
+    let mut sections = Vec::new();
+    loop {
+        skip_blank(&lines, &mut i);
+        let Some(header) = lines.get(i) else {
+            break;
+        };
+        let file_path = header
+            .strip_prefix("## `")
+            .and_then(|h| h.strip_suffix('`'))
+            .ok_or_else(|| {
+                ContextSmithError::config(format!(
+                    "expected section header on line {}, found '{header}'",
+                    i + 1
+                ))
+            })?
+            .to_string();
+        i += 1;
+
+        let mut reason = String::new();
+        if let Some(line) = lines.get(i) {
+            if line.len() >= 2 && line.starts_with('*') && line.ends_with('*') {
+                reason = line[1..line.len() - 1].to_string();
+                i += 1;
+            }
+        }
+
+        let language = lines
+            .get(i)
+            .and_then(|l| l.strip_prefix("```"))
+            .ok_or_else(|| {
+                ContextSmithError::config(format!(
+                    "expected code fence for '{file_path}' on line {}",
+                    i + 1
+                ))
+            })?
+            .to_string();
+        i += 1;
+
+        let body_start = i;
+        let close = (i..lines.len())
+            .find(|&j| {
+                lines[j] == "```"
+                    && match lines.get(j + 1) {
+                        None => true,
+                        Some(&"") => {
+                            let mut k = j + 1;
+                            skip_blank(&lines, &mut k);
+                            lines.get(k).is_none_or(|l| l.starts_with("## `"))
+                        }
+                        Some(_) => false,
+                    }
+            })
+            .ok_or_else(|| {
+                ContextSmithError::config(format!("unterminated code fence for '{file_path}'"))
+            })?;
+
+        let mut body = lines[body_start..close].join("\n");
+        if close > body_start {
+            body.push('\n');
+        }
+        i = close + 1;
+
+        sections.push(BundleSection {
+            file_path,
+            language,
+            content: body,
+            reason,
+        });
+    }
+
+    Ok(Bundle { summary, sections })
+}
+
+/// Parse the output of [`format_plain`].
+fn parse_plain(content: &str) -> Bundle {
+    let lines: Vec<&str> = content.lines().collect();
+    let is_header = |l: &str| l.starts_with("--- ") && l.ends_with(" ---") && l.len() > 8;
+
+    let first_header = lines.iter().position(|l| is_header(l));
+    let summary_end = first_header.unwrap_or(lines.len());
+    let summary = lines[..summary_end].join("\n").trim().to_string();
+
+    let mut sections = Vec::new();
+    let mut i = summary_end;
+    while i < lines.len() {
+        let file_path = lines[i][4..lines[i].len() - 4].to_string();
+        i += 1;
+        let body_start = i;
+        while i < lines.len() && !is_header(lines[i]) {
+            i += 1;
+        }
+        // The formatter separates sections with one blank line.
+        let mut body_end = i;
+        if body_end > body_start && lines[body_end - 1].is_empty() {
+            body_end -= 1;
+        }
+        let mut body = lines[body_start..body_end].join("\n");
+        if body_end > body_start {
+            body.push('\n');
+        }
+
+        sections.push(BundleSection {
+            language: crate::utils::infer_language(&file_path),
+            file_path,
+            content: body,
+            reason: String::new(),
+        });
+    }
+
+    Bundle { summary, sections }
+}
+
+/// Parse the output of [`format_xml`].
+fn parse_xml(content: &str) -> Result<Bundle> {
+    let summary = extract_tag(content, "summary")
+        .map(unescape_xml)
+        .unwrap_or_default();
+
+    let mut sections = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("<section>") {
+        let after = &rest[start + "<section>".len()..];
+        let end = after
+            .find("</section>")
+            .ok_or_else(|| ContextSmithError::config("unterminated <section> in XML bundle"))?;
+        let body = &after[..end];
+
+        let raw_content = extract_tag(body, "content").unwrap_or_default();
+        let content = raw_content
+            .strip_prefix("<![CDATA[")
+            .and_then(|c| c.strip_suffix("]]>"))
+            .map(str::to_string)
+            .unwrap_or_else(|| unescape_xml(raw_content));
+
+        sections.push(BundleSection {
+            file_path: extract_tag(body, "file_path")
+                .map(unescape_xml)
+                .unwrap_or_default(),
+            language: extract_tag(body, "language")
+                .map(unescape_xml)
+                .unwrap_or_default(),
+            content,
+            reason: extract_tag(body, "reason")
+                .map(unescape_xml)
+                .unwrap_or_default(),
+        });
+
+        rest = &after[end + "</section>".len()..];
+    }
+
+    Ok(Bundle { summary, sections })
+}
+
+/// Return the raw text between `<tag>` and the matching `</tag>`.
+fn extract_tag<'a>(content: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = content.find(&open)? + open.len();
+    let end = content[start..].rfind(&close)? + start;
+    Some(&content[start..end])
+}
+
+/// Reverse [`escape_xml`].
+fn unescape_xml(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+/// Advance `i` past any blank lines.
+fn skip_blank(lines: &[&str], i: &mut usize) {
+    while lines.get(*i).is_some_and(|l| l.trim().is_empty()) {
+        *i += 1;
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Output writing
 // ---------------------------------------------------------------------------
@@ -297,6 +545,64 @@ mod tests {
         assert_eq!(escaped, "x &lt; y &amp; z &gt; w");
     }
 
+    #[test]
+    fn markdown_roundtrip() {
+        let bundle = sample_bundle();
+        let parsed = parse_bundle(&format_markdown(&bundle), Format::Markdown).unwrap();
+        assert_eq!(parsed.summary, bundle.summary);
+        assert_eq!(parsed.sections.len(), 2);
+        assert_eq!(parsed.sections[0].file_path, "src/main.rs");
+        assert_eq!(parsed.sections[0].reason, "modified in diff");
+        assert_eq!(parsed.sections[0].content, "fn main() {}\n");
+        assert_eq!(format_markdown(&parsed), format_markdown(&bundle));
+    }
+
+    #[test]
+    fn markdown_parse_keeps_nested_fences() {
+        let bundle = Bundle {
+            summary: String::new(),
+            sections: vec![BundleSection {
+                file_path: "README.md".to_string(),
+                language: "markdown".to_string(),
+                content: "```rust\nfn x() {}\n```\ntext\n".to_string(),
+                reason: String::new(),
+            }],
+        };
+        let parsed = parse_bundle(&format_markdown(&bundle), Format::Markdown).unwrap();
+        assert_eq!(parsed.sections.len(), 1);
+        assert_eq!(parsed.sections[0].content, bundle.sections[0].content);
+    }
+
+    #[test]
+    fn plain_and_xml_roundtrip() {
+        let bundle = sample_bundle();
+
+        let plain = parse_bundle(&format_plain(&bundle), Format::Plain).unwrap();
+        assert_eq!(plain.summary, "2 files changed");
+        assert_eq!(plain.sections[1].file_path, "README.md");
+        assert_eq!(plain.sections[1].language, "markdown");
+        assert_eq!(plain.sections[0].content, "fn main() {}\n");
+
+        let xml = parse_bundle(&format_xml(&bundle), Format::Xml).unwrap();
+        assert_eq!(xml.summary, bundle.summary);
+        assert_eq!(xml.sections[0].reason, "modified in diff");
+        assert_eq!(xml.sections[1].content, "# Hello\n");
+    }
+
+    #[test]
+    fn detect_format_by_extension_and_content() {
+        assert_eq!(detect_format(Path::new("b.json"), ""), Format::Json);
+        assert_eq!(detect_format(Path::new("b.xml"), ""), Format::Xml);
+        assert_eq!(
+            detect_format(Path::new("b.txt"), "# Context Bundle\n"),
+            Format::Markdown
+        );
+        assert_eq!(
+            detect_format(Path::new("b"), "--- a.rs ---\n"),
+            Format::Plain
+        );
+    }
+
     #[test]
     fn write_to_file_creates_parents() {
         let dir = tempfile::tempdir().unwrap();
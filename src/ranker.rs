@@ -1,12 +1,31 @@
 //! Ranking and scoring for context snippets.
 //!
-//! Provides a TF-IDF–style scoring system with configurable weights for
-//! multiple signals. In Phase 2, only the `text` signal is active; other
-//! signals (diff, recency, proximity, test) are stubbed at 0.0 and will
-//! be populated in later phases.
+//! Provides a weighted scoring system with configurable weights for
+//! multiple signals:
+//!
+//! - **text**: BM25 relevance to the query over the scanned corpus (or the
+//!   match share when there is no query), normalised to the best section
+//! - **diff**: overlap with uncommitted (or diffed) changes
+//! - **recency**: how recently the file was committed
+//! - **proximity**: path distance to the query or changed files
+//! - **test**: whether the section lives in a test file or an inline
+//!   test module
+//!
+//! Repository-level inputs (changed lines, commit times, focus paths) are
+//! gathered once into a [`RankContext`] and shared by every section. The
+//! final order is decided by a [`RankingStrategy`].
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
 
-use crate::config::RankingWeights;
+use tracing::debug;
+
+use crate::config::{Config, RankingWeights};
+use crate::git::{self, DiffFile, DiffOptions};
+use crate::lexical::{self, Corpus};
 use crate::output::BundleSection;
+use crate::utils;
 
 // ---------------------------------------------------------------------------
 // Public types
@@ -18,15 +37,17 @@ use crate::output::BundleSection;
 /// particular relevance signal.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SignalScores {
-    /// Text relevance (TF-IDF style match score).
+    /// Text relevance (BM25 score, or match share without a query).
     pub text: f64,
-    /// Diff relevance (recently changed code). Stub = 0.0 in Phase 2.
+    /// Diff relevance: 1.0 when the section overlaps changed lines, 0.5
+    /// when only its file changed.
     pub diff: f64,
-    /// Recency (how recently the file was modified). Stub = 0.0.
+    /// Recency of the file's last commit relative to the other sections
+    /// (uncommitted changes count as newest).
     pub recency: f64,
-    /// Proximity (closeness to other relevant snippets). Stub = 0.0.
+    /// Path proximity to the query or changed files.
     pub proximity: f64,
-    /// Test relevance (is this a test file or tests affected code). Stub = 0.0.
+    /// Test relevance (1.0 for sections in test files).
     pub test: f64,
 }
 
@@ -42,26 +63,147 @@ impl Default for SignalScores {
     }
 }
 
+/// A section together with the per-section inputs used for ranking.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// The bundle section being ranked.
+    pub section: BundleSection,
+    /// First line covered (1-based), or 0 when unknown.
+    pub start_line: usize,
+    /// Last line covered (1-based, inclusive), or 0 when unknown.
+    pub end_line: usize,
+    /// Number of query matches inside the section.
+    pub match_count: usize,
+}
+
 /// A snippet annotated with a composite score and signal breakdown.
 #[derive(Debug, Clone)]
 pub struct ScoredSnippet {
     /// The original bundle section.
     pub section: BundleSection,
+    /// First line covered (1-based), or 0 when unknown.
+    pub start_line: usize,
+    /// Last line covered (1-based, inclusive), or 0 when unknown.
+    pub end_line: usize,
     /// Composite weighted score.
     pub score: f64,
     /// Individual signal breakdown.
     pub signals: SignalScores,
 }
 
+// ---------------------------------------------------------------------------
+// Ranking context
+// ---------------------------------------------------------------------------
+
+/// Repository state shared by every section being ranked.
+#[derive(Debug, Clone, Default)]
+pub struct RankContext {
+    /// Changed line ranges (new side, inclusive) per file.
+    pub changed_lines: HashMap<String, Vec<(usize, usize)>>,
+    /// Unix timestamp of the last commit touching each file.
+    pub commit_times: HashMap<String, i64>,
+    /// Paths proximity is measured against.
+    pub focus_paths: Vec<String>,
+    /// Query terms for BM25 text scoring.
+    pub query_terms: Vec<String>,
+    /// Document frequencies for BM25; without one, text falls back to
+    /// each candidate's match share.
+    pub corpus: Option<Corpus>,
+    /// Inline test module line ranges per file (e.g. Rust `#[cfg(test)]`).
+    pub test_ranges: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl RankContext {
+    /// Context from an already parsed diff: changed lines, with the
+    /// changed files as focus paths.
+    pub fn from_diff(diff_files: &[DiffFile]) -> Self {
+        let mut changed_lines: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for file in diff_files {
+            let ranges = changed_lines.entry(file.path.clone()).or_default();
+            for hunk in &file.hunks {
+                let end = hunk.new_start + hunk.new_count.max(1) - 1;
+                ranges.push((hunk.new_start, end));
+            }
+        }
+        let mut focus_paths: Vec<String> = diff_files.iter().map(|f| f.path.clone()).collect();
+        focus_paths.sort();
+
+        Self {
+            changed_lines,
+            focus_paths,
+            ..Self::default()
+        }
+    }
+
+    /// Best-effort context for a repository: uncommitted changes against
+    /// `HEAD` plus commit times. Outside a git repository (or before the
+    /// first commit) the git-derived signals are simply left empty.
+    pub fn for_repo(root: &Path) -> Self {
+        let diff = git::get_diff(&DiffOptions {
+            root: root.to_path_buf(),
+            rev_range: Some("HEAD".to_string()),
+            staged: false,
+            untracked: false,
+            since: None,
+            base: None,
+            worktree: false,
+        });
+        let context = match diff {
+            Ok(files) => Self::from_diff(&files),
+            Err(e) => {
+                debug!("no diff signal for {}: {e}", root.display());
+                Self::default()
+            }
+        };
+        context.with_commit_times(root)
+    }
+
+    /// Load last-commit times for the recency signal (best effort).
+    pub fn with_commit_times(mut self, root: &Path) -> Self {
+        match git::last_commit_times(root) {
+            Ok(times) => self.commit_times = times,
+            Err(e) => debug!("no recency signal for {}: {e}", root.display()),
+        }
+        self
+    }
+
+    /// Score text with BM25 for `query` against `corpus`.
+    pub fn with_query(mut self, query: &str, corpus: Corpus) -> Self {
+        self.query_terms = lexical::query_terms(query);
+        self.corpus = Some(corpus);
+        self
+    }
+
+    /// Treat these line ranges as test code for the test signal.
+    pub fn with_test_ranges(mut self, ranges: HashMap<String, Vec<(usize, usize)>>) -> Self {
+        self.test_ranges = ranges;
+        self
+    }
+
+    /// Add paths to measure proximity against.
+    pub fn with_focus<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for path in paths {
+            let path = path.into();
+            if !self.focus_paths.contains(&path) {
+                self.focus_paths.push(path);
+            }
+        }
+        self
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Scoring functions
 // ---------------------------------------------------------------------------
 
-/// Rank snippets by weighted signal scores.
+/// Rank snippets by text relevance alone.
 ///
-/// Computes a composite score for each snippet using the configured
-/// weights, sorts by score descending, and breaks ties deterministically
-/// by file path then line position.
+/// Only the text signal is computed; use [`rank_candidates`] to include
+/// the repository signals. Sorting and tie-breaking are the same.
 pub fn rank_snippets(
     sections: &[BundleSection],
     match_counts: &[usize],
@@ -75,53 +217,205 @@ pub fn rank_snippets(
         .map(|(section, &count)| {
             let signals = SignalScores {
                 text: text_score(count, total_matches, sections.len()),
-                diff: 0.0,
-                recency: 0.0,
-                proximity: 0.0,
-                test: 0.0,
+                ..SignalScores::default()
             };
             let score = weighted_score(&signals, weights);
             ScoredSnippet {
                 section: section.clone(),
+                start_line: 0,
+                end_line: 0,
                 score,
                 signals,
             }
         })
         .collect();
 
-    // Sort by score descending, tie-break on file path then reason (which
-    // encodes position info for grep matches).
-    scored.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| a.section.file_path.cmp(&b.section.file_path))
-            .then_with(|| a.section.reason.cmp(&b.section.reason))
-    });
+    sort_scored(&mut scored);
+    scored
+}
+
+/// Rank candidates by weighted signal scores.
+///
+/// Computes all five signals for each candidate, combines them with the
+/// configured weights into `score`, then orders the results with the
+/// given strategy.
+pub fn rank_candidates(
+    candidates: &[Candidate],
+    context: &RankContext,
+    weights: &RankingWeights,
+    strategy: &dyn RankingStrategy,
+) -> Vec<ScoredSnippet> {
+    let raw_text = raw_text_scores(candidates, context);
+    let max_text = raw_text.iter().cloned().fold(0.0, f64::max);
+
+    // Commit-time window across the files being ranked.
+    let times: Vec<i64> = candidates
+        .iter()
+        .filter_map(|c| context.commit_times.get(&c.section.file_path).copied())
+        .collect();
+    let oldest = times.iter().copied().min().unwrap_or(0);
+    let newest = times.iter().copied().max().unwrap_or(0);
+
+    let mut scored: Vec<ScoredSnippet> = candidates
+        .iter()
+        .zip(raw_text)
+        .map(|(candidate, text)| {
+            let path = candidate.section.file_path.as_str();
+            let signals = SignalScores {
+                text: if max_text > 0.0 { text / max_text } else { 0.0 },
+                diff: diff_score(candidate, context),
+                recency: recency_score(path, context, oldest, newest),
+                proximity: proximity_score(path, &context.focus_paths),
+                test: test_score(candidate, context),
+            };
+            let score = weighted_score(&signals, weights);
+            ScoredSnippet {
+                section: candidate.section.clone(),
+                start_line: candidate.start_line,
+                end_line: candidate.end_line,
+                score,
+                signals,
+            }
+        })
+        .collect();
 
+    scored.sort_by(|a, b| strategy.compare(a, b));
     scored
 }
 
-/// Compute the text relevance score for a snippet.
+/// Unnormalised text scores: BM25 when the context has a query and
+/// corpus, otherwise each candidate's share of the matches.
+fn raw_text_scores(candidates: &[Candidate], context: &RankContext) -> Vec<f64> {
+    match context.corpus {
+        Some(ref corpus) if !context.query_terms.is_empty() => {
+            let avg_len =
+                lexical::average_length(candidates.iter().map(|c| c.section.content.as_str()));
+            candidates
+                .iter()
+                .map(|c| corpus.bm25(&context.query_terms, &c.section.content, avg_len))
+                .collect()
+        }
+        _ => {
+            let total_matches: usize = candidates.iter().map(|c| c.match_count).sum();
+            candidates
+                .iter()
+                .map(|c| text_score(c.match_count, total_matches, candidates.len()))
+                .collect()
+        }
+    }
+}
+
+/// Sort by score descending, tie-break on file path then reason (which
+/// encodes position info for grep matches), then line position.
+fn sort_scored(scored: &mut [ScoredSnippet]) {
+    scored.sort_by(by_score);
+}
+
+/// Score descending, then the deterministic position tie-breaks.
+fn by_score(a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+    descending(a.score, b.score).then_with(|| by_position(a, b))
+}
+
+/// File path, then reason, then line position.
+fn by_position(a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+    a.section
+        .file_path
+        .cmp(&b.section.file_path)
+        .then_with(|| a.section.reason.cmp(&b.section.reason))
+        .then_with(|| a.start_line.cmp(&b.start_line))
+}
+
+/// Compare two floats so that the larger sorts first.
+fn descending(a: f64, b: f64) -> Ordering {
+    b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+}
+
+/// Diff signal: 1.0 if the candidate overlaps a changed range (or covers
+/// its whole file), 0.5 if only its file changed, 0.0 otherwise.
+pub fn diff_score(candidate: &Candidate, context: &RankContext) -> f64 {
+    let ranges = match context.changed_lines.get(&candidate.section.file_path) {
+        Some(r) => r,
+        None => return 0.0,
+    };
+    if candidate.start_line == 0 {
+        return 1.0;
+    }
+    let overlaps = ranges
+        .iter()
+        .any(|&(start, end)| start <= candidate.end_line && candidate.start_line <= end);
+    if overlaps {
+        1.0
+    } else {
+        0.5
+    }
+}
+
+/// Recency signal: files with uncommitted changes score 1.0; committed
+/// files scale linearly between the oldest (0.0) and newest (1.0) commit
+/// among the ranked files. Files with no history score 0.0.
+pub fn recency_score(path: &str, context: &RankContext, oldest: i64, newest: i64) -> f64 {
+    if context.changed_lines.contains_key(path) {
+        return 1.0;
+    }
+    match context.commit_times.get(path) {
+        Some(_) if newest == oldest => 1.0,
+        Some(&t) => (t - oldest) as f64 / (newest - oldest) as f64,
+        None => 0.0,
+    }
+}
+
+/// Proximity signal: 1.0 for a focus file itself, otherwise
+/// `1 / (2 + hops)` where `hops` counts directory steps between the
+/// file's directory and the nearest focus file's directory.
+pub fn proximity_score(path: &str, focus_paths: &[String]) -> f64 {
+    focus_paths
+        .iter()
+        .map(|focus| {
+            if focus == path {
+                return 1.0;
+            }
+            let a: Vec<&str> = parent_components(path);
+            let b: Vec<&str> = parent_components(focus);
+            let common = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+            let hops = (a.len() - common) + (b.len() - common);
+            1.0 / (2.0 + hops as f64)
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Test signal: 1.0 for sections in test files or starting inside an
+/// inline test module, 0.0 otherwise.
+pub fn test_score(candidate: &Candidate, context: &RankContext) -> f64 {
+    let path = candidate.section.file_path.as_str();
+    let in_test_module = context.test_ranges.get(path).is_some_and(|ranges| {
+        ranges
+            .iter()
+            .any(|&(start, end)| start <= candidate.start_line && candidate.start_line <= end)
+    });
+    if utils::is_test_path(path) || in_test_module {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Directory components of a relative path (the file name excluded).
+fn parent_components(path: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    parts.pop();
+    parts
+}
+
+/// Compute the fallback text score for a snippet: its share of all
+/// matches.
 ///
-/// Uses a TF-IDF–inspired formula: the snippet's match count divided by
-/// total matches, weighted by inverse document frequency (log of total
-/// sections / sections with matches).
+/// Used when there is no query to score with BM25 (explicit files, diff
+/// hunks). Term rarity is handled by [`Corpus::bm25`] instead.
 pub fn text_score(match_count: usize, total_matches: usize, total_sections: usize) -> f64 {
     if total_matches == 0 || total_sections == 0 {
         return 0.0;
     }
-
-    // Term frequency: proportion of matches in this snippet.
-    let tf = match_count as f64 / total_matches as f64;
-
-    // Inverse document frequency: log(total / matched).
-    // Since we know this snippet has matches, idf is at least log(1) = 0.
-    // We add 1 to avoid log(0) and to give non-zero score to single-match
-    // scenarios.
-    let idf = ((total_sections as f64) / (total_sections as f64).max(1.0)).ln() + 1.0;
-
-    tf * idf
+    match_count as f64 / total_matches as f64
 }
 
 /// Compute the weighted composite score from signal scores.
@@ -133,6 +427,121 @@ pub fn weighted_score(signals: &SignalScores, weights: &RankingWeights) -> f64 {
         + signals.test * weights.test
 }
 
+// ---------------------------------------------------------------------------
+// Strategies
+// ---------------------------------------------------------------------------
+
+/// Name of the strategy used when none is configured.
+pub const DEFAULT_STRATEGY: &str = "weighted";
+
+/// Names accepted by [`strategy_by_name`], in display order.
+pub const STRATEGY_NAMES: &[&str] = &[
+    "weighted",
+    "bm25",
+    "recency-first",
+    "diff-first",
+    "path-order",
+];
+
+/// Decides the final order of scored snippets.
+///
+/// Every strategy sees the same signals and weighted `score`; it only
+/// changes which sections come first (and so which survive the budget).
+pub trait RankingStrategy: Send + Sync {
+    /// Name recorded in the manifest.
+    fn name(&self) -> &'static str;
+
+    /// Order two snippets, most relevant first.
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering;
+}
+
+/// Weighted sum of all signals (the default).
+pub struct WeightedStrategy;
+
+impl RankingStrategy for WeightedStrategy {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+        by_score(a, b)
+    }
+}
+
+/// Lexical relevance first, weighted score as the tie-break.
+pub struct Bm25Strategy;
+
+impl RankingStrategy for Bm25Strategy {
+    fn name(&self) -> &'static str {
+        "bm25"
+    }
+
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+        descending(a.signals.text, b.signals.text).then_with(|| by_score(a, b))
+    }
+}
+
+/// Most recently changed files first.
+pub struct RecencyFirstStrategy;
+
+impl RankingStrategy for RecencyFirstStrategy {
+    fn name(&self) -> &'static str {
+        "recency-first"
+    }
+
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+        descending(a.signals.recency, b.signals.recency).then_with(|| by_score(a, b))
+    }
+}
+
+/// Sections overlapping changed lines first, then changed files.
+pub struct DiffFirstStrategy;
+
+impl RankingStrategy for DiffFirstStrategy {
+    fn name(&self) -> &'static str {
+        "diff-first"
+    }
+
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+        descending(a.signals.diff, b.signals.diff).then_with(|| by_score(a, b))
+    }
+}
+
+/// File path and line order, ignoring scores (the pre-ranking behaviour).
+pub struct PathOrderStrategy;
+
+impl RankingStrategy for PathOrderStrategy {
+    fn name(&self) -> &'static str {
+        "path-order"
+    }
+
+    fn compare(&self, a: &ScoredSnippet, b: &ScoredSnippet) -> Ordering {
+        a.section
+            .file_path
+            .cmp(&b.section.file_path)
+            .then_with(|| a.start_line.cmp(&b.start_line))
+            .then_with(|| a.section.reason.cmp(&b.section.reason))
+    }
+}
+
+/// Look up a built-in strategy by name (case-insensitive).
+pub fn strategy_by_name(name: &str) -> Option<Box<dyn RankingStrategy>> {
+    match name.to_lowercase().as_str() {
+        "weighted" => Some(Box::new(WeightedStrategy)),
+        "bm25" => Some(Box::new(Bm25Strategy)),
+        "recency-first" => Some(Box::new(RecencyFirstStrategy)),
+        "diff-first" => Some(Box::new(DiffFirstStrategy)),
+        "path-order" => Some(Box::new(PathOrderStrategy)),
+        _ => None,
+    }
+}
+
+/// The strategy named by `ranking.strategy`, or the default if the name
+/// is unknown (config validation normally rejects those first).
+pub fn configured_strategy(config: &Config) -> Box<dyn RankingStrategy> {
+    strategy_by_name(&config.ranking.strategy).unwrap_or_else(|| Box::new(WeightedStrategy))
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -247,6 +656,174 @@ mod tests {
         assert!(ranked.is_empty());
     }
 
+    fn candidate(path: &str, start: usize, end: usize, matches: usize) -> Candidate {
+        Candidate {
+            section: BundleSection {
+                file_path: path.to_string(),
+                language: "rust".to_string(),
+                content: String::new(),
+                reason: "match".to_string(),
+            },
+            start_line: start,
+            end_line: end,
+            match_count: matches,
+        }
+    }
+
+    #[test]
+    fn diff_score_checks_line_overlap() {
+        let mut context = RankContext::default();
+        context
+            .changed_lines
+            .insert("src/lib.rs".to_string(), vec![(10, 14)]);
+
+        assert_eq!(
+            diff_score(&candidate("src/lib.rs", 12, 20, 0), &context),
+            1.0
+        );
+        assert_eq!(
+            diff_score(&candidate("src/lib.rs", 30, 40, 0), &context),
+            0.5
+        );
+        assert_eq!(diff_score(&candidate("src/lib.rs", 0, 0, 0), &context), 1.0);
+        assert_eq!(
+            diff_score(&candidate("src/main.rs", 1, 5, 0), &context),
+            0.0
+        );
+    }
+
+    #[test]
+    fn proximity_decays_with_directory_distance() {
+        let focus = vec!["src/commands/diff.rs".to_string()];
+        assert_eq!(proximity_score("src/commands/diff.rs", &focus), 1.0);
+        assert_eq!(proximity_score("src/commands/pack.rs", &focus), 0.5);
+        assert!((proximity_score("src/ranker.rs", &focus) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(proximity_score("tests/cli.rs", &focus), 0.2);
+        assert_eq!(proximity_score("src/ranker.rs", &[]), 0.0);
+    }
+
+    #[test]
+    fn recency_scales_between_oldest_and_newest() {
+        let mut context = RankContext::default();
+        context.commit_times.insert("old.rs".to_string(), 100);
+        context.commit_times.insert("new.rs".to_string(), 200);
+        context.changed_lines.insert("dirty.rs".to_string(), vec![]);
+
+        assert_eq!(recency_score("old.rs", &context, 100, 200), 0.0);
+        assert_eq!(recency_score("new.rs", &context, 100, 200), 1.0);
+        assert_eq!(recency_score("dirty.rs", &context, 100, 200), 1.0);
+        assert_eq!(recency_score("unknown.rs", &context, 100, 200), 0.0);
+    }
+
+    #[test]
+    fn rank_candidates_uses_repository_signals() {
+        let candidates = vec![
+            candidate("src/a.rs", 1, 5, 1),
+            candidate("src/b.rs", 1, 5, 1),
+            candidate("docs/c.md", 1, 5, 1),
+        ];
+        let mut context = RankContext::default().with_focus(["src/b.rs"]);
+        context
+            .changed_lines
+            .insert("src/b.rs".to_string(), vec![(3, 3)]);
+
+        let ranked = rank_candidates(
+            &candidates,
+            &context,
+            &RankingWeights::default(),
+            &WeightedStrategy,
+        );
+        let order: Vec<&str> = ranked
+            .iter()
+            .map(|r| r.section.file_path.as_str())
+            .collect();
+        assert_eq!(order, vec!["src/b.rs", "src/a.rs", "docs/c.md"]);
+        assert_eq!(ranked[0].signals.diff, 1.0);
+        assert_eq!(ranked[0].signals.text, 1.0);
+        assert_eq!(ranked[0].start_line, 1);
+    }
+
+    #[test]
+    fn test_score_covers_inline_test_modules() {
+        let context = RankContext::default()
+            .with_test_ranges(HashMap::from([("src/a.rs".to_string(), vec![(40, 60)])]));
+        assert_eq!(test_score(&candidate("src/a.rs", 45, 50, 0), &context), 1.0);
+        assert_eq!(test_score(&candidate("src/a.rs", 1, 10, 0), &context), 0.0);
+        assert_eq!(test_score(&candidate("tests/a.rs", 1, 2, 0), &context), 1.0);
+    }
+
+    #[test]
+    fn rank_candidates_flags_test_files() {
+        let candidates = vec![
+            candidate("src/a.rs", 1, 2, 0),
+            candidate("tests/a.rs", 1, 2, 0),
+        ];
+        let ranked = rank_candidates(
+            &candidates,
+            &RankContext::default(),
+            &RankingWeights::default(),
+            &WeightedStrategy,
+        );
+        assert_eq!(ranked[0].section.file_path, "tests/a.rs");
+        assert_eq!(ranked[0].signals.test, 1.0);
+        assert_eq!(ranked[1].signals.test, 0.0);
+    }
+
+    #[test]
+    fn context_from_diff_records_changed_ranges() {
+        let files = crate::git::parse_unified_diff(
+            "diff --git a/src/x.rs b/src/x.rs\n--- a/src/x.rs\n+++ b/src/x.rs\n@@ -3,2 +3,3 @@\n a\n+b\n c\n",
+        );
+        let context = RankContext::from_diff(&files);
+        assert_eq!(context.changed_lines["src/x.rs"], vec![(3, 5)]);
+        assert_eq!(context.focus_paths, vec!["src/x.rs".to_string()]);
+    }
+
+    #[test]
+    fn strategies_reorder_the_same_signals() {
+        let candidates = vec![
+            candidate("src/a.rs", 1, 5, 3),
+            candidate("src/b.rs", 1, 5, 1),
+            candidate("src/c.rs", 1, 5, 0),
+        ];
+        let mut context = RankContext::default();
+        context
+            .changed_lines
+            .insert("src/c.rs".to_string(), vec![(1, 1)]);
+        context.commit_times.insert("src/a.rs".to_string(), 100);
+        context.commit_times.insert("src/b.rs".to_string(), 200);
+        let weights = RankingWeights::default();
+
+        let order = |name: &str| -> Vec<String> {
+            let strategy = strategy_by_name(name).unwrap();
+            rank_candidates(&candidates, &context, &weights, strategy.as_ref())
+                .into_iter()
+                .map(|r| r.section.file_path)
+                .collect()
+        };
+
+        assert_eq!(order("bm25"), vec!["src/a.rs", "src/b.rs", "src/c.rs"]);
+        assert_eq!(order("diff-first")[0], "src/c.rs");
+        assert_eq!(
+            order("recency-first"),
+            vec!["src/c.rs", "src/b.rs", "src/a.rs"]
+        );
+        assert_eq!(
+            order("path-order"),
+            vec!["src/a.rs", "src/b.rs", "src/c.rs"]
+        );
+    }
+
+    #[test]
+    fn strategy_lookup_covers_all_names() {
+        for name in STRATEGY_NAMES {
+            assert_eq!(strategy_by_name(name).unwrap().name(), *name);
+        }
+        assert_eq!(strategy_by_name("Diff-First").unwrap().name(), "diff-first");
+        assert!(strategy_by_name("random").is_none());
+        assert!(STRATEGY_NAMES.contains(&DEFAULT_STRATEGY));
+    }
+
     #[test]
     fn signal_scores_default_is_zero() {
         let s = SignalScores::default();
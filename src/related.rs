@@ -0,0 +1,966 @@
+//! Related-code discovery for diff and collect bundles.
+//!
+//! Given the files changed in a diff, finds code that a reviewer would
+//! want next to the hunks:
+//!
+//! - **Callers**: references to functions and types defined or modified
+//!   in the diff, found with [`indexer::search_files`]
+//! - **Callees**: definitions of functions called from changed lines,
+//!   found with [`RegexSymbolFinder`]
+//! - **Tests**: test files named after a changed source file
+//!
+//! For `collect --include-defs`, [`find_referenced_definitions`] resolves
+//! the identifiers used inside collected snippets to their definitions,
+//! and for `collect --tests`, [`find_tests_for`] locates the tests of the
+//! files that contributed snippets.
+//!
+//! Results are plain [`Snippet`]s so the commands can rank and budget
+//! them alongside the snippets they were derived from.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::cache::IndexCache;
+use crate::error::Result;
+use crate::git::{DiffFile, LineKind};
+use crate::indexer;
+use crate::scanner::ScannedFile;
+use crate::slicer::Snippet;
+use crate::symbols::{self, RegexSymbolFinder, SymbolFinder};
+
+/// Most files pulled in as callers (or definitions) for one symbol.
+const MAX_FILES_PER_SYMBOL: usize = 5;
+
+/// Longest definition body included for a callee.
+const MAX_DEFINITION_LINES: usize = 40;
+
+/// Identifiers too short or too common to be worth chasing.
+const MIN_SYMBOL_LEN: usize = 3;
+
+/// Most distinct identifiers resolved by [`find_referenced_definitions`].
+const MAX_REFERENCED_SYMBOLS: usize = 40;
+
+// ---------------------------------------------------------------------------
+// Public types
+// ---------------------------------------------------------------------------
+
+/// Controls how much related code is gathered.
+#[derive(Debug, Clone)]
+pub struct RelatedOptions {
+    /// Lines of context around each caller reference.
+    pub context_lines: usize,
+}
+
+/// Identifiers a diff touches in one file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangedSymbols {
+    /// Functions and types defined on changed lines or enclosing a hunk.
+    pub defined: BTreeSet<String>,
+    /// Functions called from changed lines (excluding `defined`).
+    pub called: BTreeSet<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Core logic
+// ---------------------------------------------------------------------------
+
+/// Collect the identifiers defined or called in a file's hunks.
+///
+/// A changed line counts as a change to its enclosing definition: the
+/// closest definition above it in the hunk, or failing that the hunk
+/// header's function context (`@@ ... @@ fn main()`).
+pub fn changed_symbols(file: &DiffFile) -> ChangedSymbols {
+    let mut changed = ChangedSymbols::default();
+    let mut changed_lines = String::new();
+
+    for hunk in &file.hunks {
+        let context = hunk.header.rsplit("@@").next().unwrap_or("");
+        let mut enclosing = symbols::extract_symbols(context.trim())
+            .pop()
+            .map(|s| s.name);
+
+        for line in &hunk.lines {
+            if let Some(symbol) = symbols::extract_symbols(&line.content).pop() {
+                enclosing = Some(symbol.name);
+            }
+            if line.kind == LineKind::Context {
+                continue;
+            }
+            if let Some(ref name) = enclosing {
+                changed.defined.insert(name.clone());
+            }
+            changed_lines.push_str(&line.content);
+            changed_lines.push('\n');
+        }
+    }
+
+    for caps in call_regex().captures_iter(&changed_lines) {
+        let name = &caps[1];
+        if is_interesting(name) && !changed.defined.contains(name) {
+            changed.called.insert(name.to_string());
+        }
+    }
+    changed.defined.retain(|name| is_interesting(name));
+
+    changed
+}
+
+/// Find callers, callees and tests related to the changed files.
+///
+/// `files` is the scanned project; `existing` holds snippets already in
+/// the bundle, which related snippets never duplicate. Snippets are
+/// returned in a stable order: per changed file, callers, then callees,
+/// then tests.
+pub fn find_related(
+    diff_files: &[DiffFile],
+    files: &[ScannedFile],
+    existing: &[Snippet],
+    options: &RelatedOptions,
+) -> Result<Vec<Snippet>> {
+    let mut collector = Collector::new(files, existing);
+
+    for diff_file in diff_files {
+        let changed = changed_symbols(diff_file);
+        let others: Vec<ScannedFile> = files
+            .iter()
+            .filter(|f| f.rel_path != diff_file.path)
+            .cloned()
+            .collect();
+
+        for symbol in &changed.defined {
+            find_callers(&mut collector, &others, symbol, &diff_file.path, options)?;
+        }
+        for symbol in &changed.called {
+            find_callees(&mut collector, &others, symbol, &diff_file.path)?;
+        }
+        find_tests(&mut collector, &others, &diff_file.path);
+    }
+
+    Ok(collector.snippets)
+}
+
+/// Identifiers a snippet refers to, mapped to the line of their first use.
+///
+/// Call sites (`name(`) and capitalised type names (`Config`,
+/// `Config::load`, `: Config`) count as references. Comment lines, string
+/// literals, the language's builtin types and names defined inside the
+/// snippet itself are skipped.
+pub fn referenced_identifiers(snippet: &Snippet, language: &str) -> BTreeMap<String, usize> {
+    let defined: BTreeSet<String> = symbols::extract_symbols(&snippet.content)
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    let builtins = builtin_names(language);
+
+    let mut refs = BTreeMap::new();
+    for (idx, code) in symbols::mask_code(&snippet.content, language)
+        .iter()
+        .enumerate()
+    {
+        let calls = call_regex()
+            .captures_iter(code)
+            .filter_map(|caps| caps.get(1))
+            .map(|m| m.as_str().to_string());
+        let types = type_regex().find_iter(code).map(|m| m.as_str().to_string());
+        for name in calls.chain(types) {
+            if is_interesting(&name)
+                && !builtins.contains(&name.as_str())
+                && !defined.contains(&name)
+            {
+                refs.entry(name).or_insert(snippet.start_line + idx);
+            }
+        }
+    }
+    refs
+}
+
+/// Find definitions of the identifiers referenced by `snippets`.
+///
+/// Each identifier is resolved once, attributed to the first snippet
+/// that uses it, and at most [`MAX_REFERENCED_SYMBOLS`] are resolved in
+/// total. Files whose cached terms cannot contain an identifier are not
+/// read for it. Definitions whose first line is already in the bundle
+/// are skipped.
+pub fn find_referenced_definitions(
+    snippets: &[Snippet],
+    files: &[ScannedFile],
+    cache: &mut IndexCache,
+) -> Result<Vec<Snippet>> {
+    let mut collector = Collector::new(files, snippets);
+    let mut resolved = BTreeSet::new();
+
+    for snippet in snippets {
+        let language = crate::utils::infer_language(&snippet.file_path);
+        for (name, line) in referenced_identifiers(snippet, &language) {
+            if resolved.len() >= MAX_REFERENCED_SYMBOLS {
+                return Ok(collector.snippets);
+            }
+            if !resolved.insert(name.clone()) {
+                continue;
+            }
+
+            let reason = format!(
+                "definition of `{name}` referenced from {}:{line}",
+                snippet.file_path
+            );
+            for def in RegexSymbolFinder
+                .find_definitions_cached(files, &name, cache)?
+                .iter()
+                .take(MAX_FILES_PER_SYMBOL)
+            {
+                if collector.covers(&def.file_path, def.line_number) {
+                    continue;
+                }
+                let end = collector.definition_end(&def.file_path, def.line_number);
+                collector.push(&def.file_path, def.line_number, end, &reason);
+            }
+        }
+    }
+
+    Ok(collector.snippets)
+}
+
+/// Find the tests for `sources`, the files that contributed snippets.
+///
+/// Per source file, in order:
+/// - test files named after it (`tests/<stem>.rs`, `<stem>_test.go`,
+///   `test_<stem>.py`, `<stem>.test.ts`, `__tests__/<stem>.js`, ...)
+/// - for Rust, windows around the lines of integration tests under
+///   `tests/` that mention one of its top-level items, then its inline
+///   `#[cfg(test)]` module
+///
+/// Sections already covered by `existing` are skipped.
+pub fn find_tests_for(
+    sources: &[String],
+    files: &[ScannedFile],
+    existing: &[Snippet],
+    options: &RelatedOptions,
+) -> Vec<Snippet> {
+    let mut collector = Collector::new(files, existing);
+
+    for source in sources {
+        find_tests(&mut collector, files, source);
+
+        if crate::utils::infer_language(source) != "rust" {
+            continue;
+        }
+        let content = collector.file_lines(source).join("\n");
+        let reason = format!("test for {source}");
+        if let Some(items) = item_regex(&content) {
+            let tests = files
+                .iter()
+                .filter(|f| &f.rel_path != source && is_rust_integration_test(f));
+            for file in tests {
+                let ranges: Vec<(usize, usize)> = collector
+                    .file_lines(&file.rel_path)
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| items.is_match(line))
+                    .map(|(i, _)| {
+                        (
+                            (i + 1).saturating_sub(options.context_lines).max(1),
+                            i + 1 + options.context_lines,
+                        )
+                    })
+                    .collect();
+                for (start, end) in merge_ranges(ranges) {
+                    collector.push(&file.rel_path, start, end, &reason);
+                }
+            }
+        }
+        for (start, end) in inline_test_ranges(&content, "rust") {
+            collector.push(source, start, end, &reason);
+        }
+    }
+
+    collector.snippets
+}
+
+/// Line ranges of the inline test modules in `content`.
+///
+/// Only Rust has them: a `#[cfg(test)]` attribute followed by
+/// `mod name { ... }`, up to its closing brace. Braces in comments and
+/// strings are ignored.
+pub fn inline_test_ranges(content: &str, language: &str) -> Vec<(usize, usize)> {
+    if language != "rust" {
+        return Vec::new();
+    }
+    static MOD_RE: OnceLock<Regex> = OnceLock::new();
+    let mod_re = MOD_RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+[A-Za-z_][A-Za-z0-9_]*\s*\{")
+            .expect("mod regex is valid")
+    });
+
+    let masked = symbols::mask_code(content, language);
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < masked.len() {
+        if masked[i].trim() != "#[cfg(test)]" {
+            i += 1;
+            continue;
+        }
+        let mut module = i + 1;
+        while module < masked.len() && masked[module].trim_start().starts_with("#[") {
+            module += 1;
+        }
+        if module >= masked.len() || !mod_re.is_match(&masked[module]) {
+            i += 1;
+            continue;
+        }
+
+        let mut depth = 0usize;
+        let mut end = masked.len() - 1;
+        'lines: for (idx, line) in masked.iter().enumerate().skip(module) {
+            for c in line.chars() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth = depth.saturating_sub(1);
+                        if depth == 0 {
+                            end = idx;
+                            break 'lines;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ranges.push((i + 1, end + 1));
+        i = end + 1;
+    }
+    ranges
+}
+
+/// Whether a scanned file is a Rust integration test (under `tests/`).
+fn is_rust_integration_test(file: &ScannedFile) -> bool {
+    file.language == "rust"
+        && Path::new(&file.rel_path)
+            .components()
+            .any(|c| c.as_os_str() == "tests")
+}
+
+/// Word-boundary regex for a Rust file's top-level items, or `None` if
+/// it defines nothing worth searching for.
+fn item_regex(content: &str) -> Option<Regex> {
+    let items: BTreeSet<String> = symbols::extract_symbols(content)
+        .into_iter()
+        .filter(|s| s.indent == 0 && s.kind != "impl" && s.kind != "mod" && is_interesting(&s.name))
+        .map(|s| regex::escape(&s.name))
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+    let alternation: Vec<String> = items.into_iter().collect();
+    Regex::new(&format!(r"\b(?:{})\b", alternation.join("|"))).ok()
+}
+
+/// Add windows around references to `symbol` outside its changed file.
+fn find_callers(
+    collector: &mut Collector,
+    files: &[ScannedFile],
+    symbol: &str,
+    changed_path: &str,
+    options: &RelatedOptions,
+) -> Result<()> {
+    let pattern = format!(r"\b{}\b", regex::escape(symbol));
+    let definition = indexer::compile_pattern(&symbols::build_symbol_pattern(symbol))?;
+    let result = indexer::search_files(files, &pattern)?;
+
+    let mut by_file: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for m in &result.matches {
+        if !definition.is_match(&m.line_content) {
+            by_file.entry(&m.file_path).or_default().push(m.line_number);
+        }
+    }
+
+    let reason = format!("caller of `{symbol}` (changed in {changed_path})");
+    for (path, lines) in by_file.into_iter().take(MAX_FILES_PER_SYMBOL) {
+        let ranges: Vec<(usize, usize)> = lines
+            .iter()
+            .map(|&line| {
+                (
+                    line.saturating_sub(options.context_lines).max(1),
+                    line + options.context_lines,
+                )
+            })
+            .collect();
+        for (start, end) in merge_ranges(ranges) {
+            collector.push(path, start, end, &reason);
+        }
+    }
+    Ok(())
+}
+
+/// Add the definitions of `symbol` found outside its calling file.
+fn find_callees(
+    collector: &mut Collector,
+    files: &[ScannedFile],
+    symbol: &str,
+    changed_path: &str,
+) -> Result<()> {
+    let definitions = RegexSymbolFinder.find_definitions(files, symbol)?;
+
+    let reason = format!("definition of `{symbol}` (called in {changed_path})");
+    for def in definitions.iter().take(MAX_FILES_PER_SYMBOL) {
+        let end = collector.definition_end(&def.file_path, def.line_number);
+        collector.push(&def.file_path, def.line_number, end, &reason);
+    }
+    Ok(())
+}
+
+/// Add whole test files whose names match the changed file (other than
+/// the file itself).
+fn find_tests(collector: &mut Collector, files: &[ScannedFile], changed_path: &str) {
+    let stem = match Path::new(changed_path).file_stem() {
+        Some(s) => s.to_string_lossy().to_string(),
+        None => return,
+    };
+
+    let reason = format!("test for {changed_path}");
+    let tests = files
+        .iter()
+        .filter(|f| f.rel_path != changed_path && is_test_for(&f.rel_path, &stem));
+    for file in tests {
+        collector.push(&file.rel_path, 1, usize::MAX, &reason);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Snippet collection
+// ---------------------------------------------------------------------------
+
+/// Accumulates related snippets, reading each file at most once and
+/// skipping ranges already covered by the bundle.
+struct Collector<'a> {
+    files: &'a [ScannedFile],
+    lines: BTreeMap<String, Vec<String>>,
+    covered: BTreeMap<String, Vec<(usize, usize)>>,
+    snippets: Vec<Snippet>,
+}
+
+impl<'a> Collector<'a> {
+    fn new(files: &'a [ScannedFile], existing: &[Snippet]) -> Self {
+        let mut covered: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+        for s in existing {
+            covered
+                .entry(s.file_path.clone())
+                .or_default()
+                .push((s.start_line, s.end_line));
+        }
+        Self {
+            files,
+            lines: BTreeMap::new(),
+            covered,
+            snippets: Vec::new(),
+        }
+    }
+
+    /// Lines of a scanned file (empty if it cannot be read).
+    fn file_lines(&mut self, path: &str) -> &[String] {
+        if !self.lines.contains_key(path) {
+            let lines = self
+                .files
+                .iter()
+                .find(|f| f.rel_path == path)
+                .and_then(|f| std::fs::read_to_string(&f.abs_path).ok())
+                .map(|c| c.lines().map(String::from).collect())
+                .unwrap_or_default();
+            self.lines.insert(path.to_string(), lines);
+        }
+        &self.lines[path]
+    }
+
+    /// Last line of the definition starting at `line`: just before the
+    /// next definition at the same or shallower indentation, capped at
+    /// [`MAX_DEFINITION_LINES`].
+    fn definition_end(&mut self, path: &str, line: usize) -> usize {
+        let lines = self.file_lines(path);
+        let content = lines.join("\n");
+        let outline = symbols::extract_symbols(&content);
+        let indent = outline
+            .iter()
+            .find(|s| s.line == line)
+            .map_or(0, |s| s.indent);
+        let next = outline
+            .iter()
+            .find(|s| s.line > line && s.indent <= indent)
+            .map_or(lines.len(), |s| s.line - 1);
+        next.min(line + MAX_DEFINITION_LINES - 1)
+    }
+
+    /// Whether `line` of `path` is inside a collected or existing snippet.
+    fn covers(&self, path: &str, line: usize) -> bool {
+        self.covered
+            .get(path)
+            .is_some_and(|ranges| ranges.iter().any(|&(s, e)| s <= line && line <= e))
+    }
+
+    /// Add `path[start..=end]` unless it is already covered.
+    fn push(&mut self, path: &str, start: usize, end: usize, reason: &str) {
+        let total = self.file_lines(path).len();
+        let end = end.min(total);
+        if total == 0 || start > end {
+            return;
+        }
+        let ranges = self.covered.entry(path.to_string()).or_default();
+        if ranges.iter().any(|&(s, e)| s <= start && end <= e) {
+            return;
+        }
+        ranges.push((start, end));
+
+        let content = self.lines[path][start - 1..end].join("\n");
+        self.snippets.push(Snippet {
+            file_path: path.to_string(),
+            start_line: start,
+            end_line: end,
+            content,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Whether `path` looks like a test file for a source file named `stem`.
+///
+/// Recognises `tests/<stem>.rs`, `<stem>_test.go`, `test_<stem>.py`,
+/// `<stem>_test.py`, `<stem>.test.ts`, `<stem>.spec.js` and files under
+/// `__tests__/`.
+fn is_test_for(path: &str, stem: &str) -> bool {
+    let p = Path::new(path);
+    let name = match p.file_stem() {
+        Some(n) => n.to_string_lossy(),
+        None => return false,
+    };
+    let in_test_dir = p.components().any(|c| {
+        let c = c.as_os_str().to_string_lossy();
+        c == "tests" || c == "test" || c == "__tests__"
+    });
+
+    let candidates = [
+        format!("{stem}_test"),
+        format!("{stem}_tests"),
+        format!("test_{stem}"),
+        format!("{stem}.test"),
+        format!("{stem}.spec"),
+    ];
+    candidates.iter().any(|c| *c == name) || (in_test_dir && name == stem)
+}
+
+/// Whether an identifier is worth searching for.
+fn is_interesting(name: &str) -> bool {
+    name.len() >= MIN_SYMBOL_LEN && !KEYWORDS.contains(&name)
+}
+
+/// Merge sorted-or-unsorted ranges, combining overlapping or adjacent ones.
+fn merge_ranges(mut ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        if let Some(last) = merged.last_mut() {
+            if start <= last.1 + 1 {
+                last.1 = last.1.max(end);
+                continue;
+            }
+        }
+        merged.push((start, end));
+    }
+    merged
+}
+
+/// Matches `name(` call sites.
+fn call_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)\s*\(").expect("call regex is valid"))
+}
+
+/// Matches capitalised identifiers with at least one lowercase letter
+/// (type names, not `ALL_CAPS` constants or macros).
+fn type_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b[A-Z][A-Za-z0-9_]*[a-z][A-Za-z0-9_]*\b").expect("type regex is valid")
+    })
+}
+
+/// Builtin and standard-library type names not worth resolving.
+fn builtin_names(language: &str) -> &'static [&'static str] {
+    match language {
+        "rust" => &[
+            "Arc", "BTreeMap", "BTreeSet", "Box", "Clone", "Debug", "Default", "HashMap",
+            "HashSet", "None", "Option", "Path", "PathBuf", "Rc", "Result", "String", "Vec",
+        ],
+        "python" => &[
+            "Dict",
+            "Exception",
+            "False",
+            "List",
+            "None",
+            "Optional",
+            "True",
+        ],
+        "typescript" | "javascript" => &[
+            "Array", "Boolean", "Date", "Error", "Map", "Math", "Number", "Object", "Promise",
+            "Set", "String",
+        ],
+        "java" | "kotlin" => &[
+            "Exception",
+            "Integer",
+            "List",
+            "Map",
+            "Object",
+            "Override",
+            "Set",
+            "String",
+        ],
+        "go" => &["Errorf", "Printf", "Println", "Sprintf"],
+        _ => &[],
+    }
+}
+
+/// Keywords and ubiquitous builtins that look like calls but are not
+/// worth resolving.
+const KEYWORDS: &[&str] = &[
+    "and",
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "async",
+    "await",
+    "catch",
+    "class",
+    "def",
+    "elif",
+    "else",
+    "enum",
+    "for",
+    "format",
+    "func",
+    "function",
+    "if",
+    "impl",
+    "len",
+    "let",
+    "loop",
+    "match",
+    "new",
+    "not",
+    "print",
+    "println",
+    "eprintln",
+    "return",
+    "self",
+    "Self",
+    "some",
+    "Some",
+    "Ok",
+    "Err",
+    "super",
+    "switch",
+    "typeof",
+    "unwrap",
+    "vec",
+    "while",
+    "with",
+    "yield",
+    "range",
+    "str",
+    "int",
+    "console",
+    "require",
+    "import",
+    "from",
+    "clone",
+    "into",
+    "iter",
+    "map",
+    "to_string",
+    "push",
+    "get",
+];
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{DiffHunk, DiffLine, FileStatus};
+
+    fn scanned(root: &Path, rel: &str, content: &str) -> ScannedFile {
+        let abs = root.join(rel);
+        std::fs::create_dir_all(abs.parent().unwrap()).unwrap();
+        std::fs::write(&abs, content).unwrap();
+        ScannedFile {
+            rel_path: rel.to_string(),
+            abs_path: abs,
+            language: crate::utils::infer_language(rel),
+            is_generated: false,
+            size: content.len() as u64,
+        }
+    }
+
+    fn diff_file(path: &str, header: &str, added: &[&str]) -> DiffFile {
+        DiffFile {
+            path: path.to_string(),
+            old_path: None,
+            status: FileStatus::Modified,
+            hunks: vec![DiffHunk {
+                old_start: 1,
+                old_count: 1,
+                new_start: 1,
+                new_count: added.len(),
+                header: header.to_string(),
+                lines: added
+                    .iter()
+                    .enumerate()
+                    .map(|(i, l)| DiffLine {
+                        kind: LineKind::Added,
+                        content: l.to_string(),
+                        old_lineno: None,
+                        new_lineno: Some(i + 1),
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn changed_symbols_from_lines_and_header() {
+        let file = diff_file(
+            "src/lib.rs",
+            "@@ -10,2 +10,3 @@ pub fn render(items: &[Item]) {",
+            &[
+                "    log_start();",
+                "}",
+                "fn helper() {",
+                "    let x = compute_total(items);",
+            ],
+        );
+        let changed = changed_symbols(&file);
+        assert_eq!(
+            changed.defined.iter().collect::<Vec<_>>(),
+            vec!["helper", "render"]
+        );
+        assert_eq!(
+            changed.called.iter().collect::<Vec<_>>(),
+            vec!["compute_total", "log_start"]
+        );
+    }
+
+    #[test]
+    fn finds_callers_callees_and_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let files = vec![
+            scanned(
+                root,
+                "src/greet.rs",
+                "pub fn greet(name: &str) -> String {\n    shout(name)\n}\n",
+            ),
+            scanned(
+                root,
+                "src/main.rs",
+                "fn main() {\n    let msg = greet(\"bob\");\n    println!(\"{msg}\");\n}\n",
+            ),
+            scanned(
+                root,
+                "src/util.rs",
+                "pub fn shout(s: &str) -> String {\n    s.to_uppercase()\n}\n\npub fn other() {}\n",
+            ),
+            scanned(root, "tests/greet.rs", "#[test]\nfn works() {}\n"),
+        ];
+        let diff = vec![diff_file(
+            "src/greet.rs",
+            "@@ -1,3 +1,3 @@",
+            &["pub fn greet(name: &str) -> String {", "    shout(name)"],
+        )];
+
+        let related =
+            find_related(&diff, &files, &[], &RelatedOptions { context_lines: 1 }).unwrap();
+        let reasons: Vec<(&str, &str)> = related
+            .iter()
+            .map(|s| (s.file_path.as_str(), s.reason.as_str()))
+            .collect();
+
+        assert_eq!(
+            reasons,
+            vec![
+                ("src/main.rs", "caller of `greet` (changed in src/greet.rs)"),
+                (
+                    "src/util.rs",
+                    "definition of `shout` (called in src/greet.rs)"
+                ),
+                ("tests/greet.rs", "test for src/greet.rs"),
+            ]
+        );
+        // The callee body stops before the next top-level definition.
+        assert_eq!((related[1].start_line, related[1].end_line), (1, 4));
+        assert_eq!((related[0].start_line, related[0].end_line), (1, 3));
+    }
+
+    #[test]
+    fn existing_snippets_are_not_duplicated() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            scanned(dir.path(), "a.py", "def run():\n    pass\n"),
+            scanned(dir.path(), "b.py", "from a import run\nrun()\n"),
+        ];
+        let diff = vec![diff_file("a.py", "@@ -1 +1 @@", &["def run():"])];
+        let existing = vec![Snippet {
+            file_path: "b.py".into(),
+            start_line: 1,
+            end_line: 2,
+            content: String::new(),
+            reason: "modified in diff".into(),
+        }];
+
+        let related = find_related(
+            &diff,
+            &files,
+            &existing,
+            &RelatedOptions { context_lines: 3 },
+        )
+        .unwrap();
+        assert!(related.is_empty());
+    }
+
+    fn snippet(path: &str, start_line: usize, content: &str) -> Snippet {
+        Snippet {
+            file_path: path.to_string(),
+            start_line,
+            end_line: start_line + content.lines().count() - 1,
+            content: content.to_string(),
+            reason: "grep match".into(),
+        }
+    }
+
+    #[test]
+    fn referenced_identifiers_skip_noise() {
+        let s = snippet(
+            "src/pack.rs",
+            10,
+            "fn pack(cfg: &Config) -> Vec<String> {\n    // Budget(old)\n    let plan = plan_budget(cfg, \"Ignored\");\n    Bundle::new(plan)\n}",
+        );
+        let refs = referenced_identifiers(&s, "rust");
+        assert_eq!(
+            refs.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Bundle".to_string(), 13),
+                ("Config".to_string(), 10),
+                ("plan_budget".to_string(), 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_referenced_definitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            scanned(
+                dir.path(),
+                "src/config.rs",
+                "pub struct Config {\n    pub budget: usize,\n}\n\npub fn other() {}\n",
+            ),
+            scanned(
+                dir.path(),
+                "src/pack.rs",
+                "use crate::config::Config;\n\npub fn pack(cfg: &Config) -> usize {\n    cfg.budget\n}\n",
+            ),
+        ];
+        let matched = vec![snippet(
+            "src/pack.rs",
+            3,
+            "pub fn pack(cfg: &Config) -> usize {\n    cfg.budget\n}",
+        )];
+
+        let mut cache = IndexCache::open(
+            dir.path(),
+            &crate::config::CacheConfig {
+                enabled: true,
+                dir: None,
+            },
+        );
+        let defs = find_referenced_definitions(&matched, &files, &mut cache).unwrap();
+        assert!(!cache.is_empty());
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].file_path, "src/config.rs");
+        assert_eq!((defs[0].start_line, defs[0].end_line), (1, 4));
+        assert_eq!(
+            defs[0].reason,
+            "definition of `Config` referenced from src/pack.rs:3"
+        );
+
+        // Definitions already in the bundle are not added again.
+        let mut with_def = matched.clone();
+        with_def.push(snippet(
+            "src/config.rs",
+            1,
+            "pub struct Config {\n    pub budget: usize,\n}\n",
+        ));
+        assert!(find_referenced_definitions(&with_def, &files, &mut cache)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn inline_test_ranges_follow_braces() {
+        let content = "fn a() {}\n\n#[cfg(test)]\nmod tests {\n    fn b() { let s = \"}\"; }\n}\n\nfn c() {}\n";
+        assert_eq!(inline_test_ranges(content, "rust"), vec![(3, 6)]);
+        assert!(inline_test_ranges(content, "python").is_empty());
+    }
+
+    #[test]
+    fn finds_tests_for_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            scanned(
+                dir.path(),
+                "src/pack.rs",
+                "pub fn pack_items() {}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn packs() {}\n}\n",
+            ),
+            scanned(
+                dir.path(),
+                "tests/cli.rs",
+                &format!(
+                    "{}#[test]\nfn cli() {{ mycrate::pack_items(); }}\n",
+                    "// filler\n".repeat(10)
+                ),
+            ),
+            scanned(dir.path(), "tests/other.rs", "#[test]\nfn other() {}\n"),
+            scanned(dir.path(), "web/app.ts", "export const app = 1;\n"),
+            scanned(dir.path(), "web/__tests__/app.ts", "test('app', () => {});\n"),
+        ];
+        let sources = vec!["src/pack.rs".to_string(), "web/app.ts".to_string()];
+
+        let tests = find_tests_for(&sources, &files, &[], &RelatedOptions { context_lines: 1 });
+        let found: Vec<(&str, usize, &str)> = tests
+            .iter()
+            .map(|s| (s.file_path.as_str(), s.start_line, s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("tests/cli.rs", 11, "test for src/pack.rs"),
+                ("src/pack.rs", 3, "test for src/pack.rs"),
+                ("web/__tests__/app.ts", 1, "test for web/app.ts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_name_heuristics() {
+        assert!(is_test_for("pkg/server_test.go", "server"));
+        assert!(is_test_for("tests/test_parser.py", "parser"));
+        assert!(is_test_for("web/app.spec.ts", "app"));
+        assert!(is_test_for("tests/ranker.rs", "ranker"));
+        assert!(!is_test_for("src/ranker.rs", "ranker"));
+        assert!(!is_test_for("tests/other.rs", "ranker"));
+    }
+}
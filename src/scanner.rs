@@ -5,10 +5,12 @@
 //! filters from `contextsmith.toml` (ignore patterns, generated file
 //! patterns, language filters).
 
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
 use crate::config::Config;
 use crate::error::{ContextSmithError, Result};
+use crate::imports;
 use crate::utils;
 
 // ---------------------------------------------------------------------------
@@ -65,6 +67,8 @@ pub fn scan(options: &ScanOptions) -> Result<Vec<ScannedFile>> {
 
     let mut builder = ignore::WalkBuilder::new(&root);
     builder.hidden(false).git_ignore(true).git_global(true);
+    // Hidden files are scanned, but git's own metadata never is.
+    builder.filter_entry(|entry| entry.file_name() != ".git");
 
     // Add config ignore patterns as custom globs.
     for pattern in &options.ignore_patterns {
@@ -147,6 +151,86 @@ pub fn scan_options_from_config(config: &Config, root: &Path) -> ScanOptions {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Scoping
+// ---------------------------------------------------------------------------
+
+/// Resolve a `--scope` value to the relative paths of the files it covers.
+///
+/// The scope is tried, in order, as a file, a directory, a path without
+/// extension (`src/util` → `src/util.ts`) and a language-level module
+/// path (`crate::commands::pack`, `app.services.billing`). Absolute paths
+/// under `root` are accepted. A scope that matches nothing is an error.
+pub fn resolve_scope(scope: &str, root: &Path, files: &[ScannedFile]) -> Result<BTreeSet<String>> {
+    let known: BTreeSet<String> = files.iter().map(|f| f.rel_path.clone()).collect();
+    let path = relative_to_root(scope, root);
+
+    if path.is_empty() {
+        return Ok(known);
+    }
+    if known.contains(&path) {
+        return Ok(BTreeSet::from([path]));
+    }
+
+    let prefix = format!("{path}/");
+    let in_dir: BTreeSet<String> = known
+        .iter()
+        .filter(|f| f.starts_with(&prefix))
+        .cloned()
+        .collect();
+    if !in_dir.is_empty() {
+        return Ok(in_dir);
+    }
+
+    let by_stem: BTreeSet<String> = known
+        .iter()
+        .filter(|f| f.rsplit_once('.').is_some_and(|(stem, _)| stem == path))
+        .cloned()
+        .collect();
+    if !by_stem.is_empty() {
+        return Ok(by_stem);
+    }
+
+    let module = imports::module_files(&path, &known);
+    if !module.is_empty() {
+        return Ok(module);
+    }
+
+    Err(ContextSmithError::validation(
+        "scope",
+        format!("'{scope}' matches no file, directory or module"),
+    ))
+}
+
+/// Normalise a path to be relative to `root`, without `./` or a trailing `/`.
+///
+/// The root itself becomes the empty string.
+pub fn relative_to_root(path: &str, root: &Path) -> String {
+    let scope = path.trim();
+    let as_path = Path::new(scope);
+    let relative = if as_path.is_absolute() {
+        let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        as_path
+            .strip_prefix(&canonical)
+            .or_else(|_| as_path.strip_prefix(root))
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| scope.to_string())
+    } else {
+        scope.to_string()
+    };
+
+    let mut path = relative.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    let path = path.trim_end_matches('/');
+    if path == "." {
+        String::new()
+    } else {
+        path.to_string()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Generated file detection
 // ---------------------------------------------------------------------------
@@ -311,6 +395,28 @@ mod tests {
         assert!(files.iter().any(|f| f.rel_path.contains("helper.rs")));
     }
 
+    #[test]
+    fn scan_skips_git_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        std::fs::write(dir.path().join(".env.example"), "KEY=value").unwrap();
+
+        let options = ScanOptions {
+            root: dir.path().to_path_buf(),
+            ignore_patterns: vec![],
+            generated_patterns: vec![],
+            lang_filter: None,
+            path_filter: None,
+            exclude_patterns: vec![],
+        };
+
+        let files = scan(&options).unwrap();
+        assert!(files.iter().all(|f| !f.rel_path.starts_with(".git/")));
+        assert!(files.iter().any(|f| f.rel_path == ".env.example"));
+    }
+
     #[test]
     fn scan_respects_language_filter() {
         let dir = tempfile::tempdir().unwrap();
@@ -375,6 +481,58 @@ mod tests {
         assert!(!regular.unwrap().is_generated);
     }
 
+    #[test]
+    fn resolve_scope_handles_files_dirs_and_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/commands")).unwrap();
+        for name in [
+            "src/lib.rs",
+            "src/commands/pack.rs",
+            "src/commands/trim.rs",
+            "web/util.ts",
+        ] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let files = scan(&ScanOptions {
+            root: dir.path().to_path_buf(),
+            ignore_patterns: vec![],
+            generated_patterns: vec![],
+            lang_filter: None,
+            path_filter: None,
+            exclude_patterns: vec![],
+        })
+        .unwrap();
+        let resolve = |scope: &str| -> Vec<String> {
+            resolve_scope(scope, dir.path(), &files)
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+
+        assert_eq!(resolve("src/lib.rs"), vec!["src/lib.rs"]);
+        assert_eq!(
+            resolve("./src/commands/"),
+            vec!["src/commands/pack.rs", "src/commands/trim.rs"]
+        );
+        assert_eq!(resolve("web/util"), vec!["web/util.ts"]);
+        assert_eq!(
+            resolve("crate::commands::pack"),
+            vec!["src/commands/pack.rs"]
+        );
+        assert_eq!(
+            resolve(&dir.path().join("src/commands/trim.rs").to_string_lossy()),
+            vec!["src/commands/trim.rs"]
+        );
+        assert_eq!(resolve(".").len(), 4);
+
+        let err = resolve_scope("crate::missing", dir.path(), &files).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("matches no file, directory or module"));
+    }
+
     #[test]
     fn scan_options_from_config_uses_defaults() {
         let config = Config::default();
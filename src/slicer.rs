@@ -4,8 +4,10 @@
 //! minimal spans around each changed region. It handles:
 //!
 //! - **Context expansion**: adding configurable lines above/below hunks
+//! - **Function context**: growing hunks to their enclosing function
 //! - **Overlap merging**: combining adjacent snippets from the same file
 //! - **Hunks-only mode**: emitting raw hunk content without reading files
+//! - **Before/after pairs**: the old file's view of each changed region
 //!
 //! The output is a vector of [`Snippet`] values that downstream code
 //! (the diff command, output formatter) can consume directly.
@@ -14,6 +16,8 @@ use std::path::{Path, PathBuf};
 
 use crate::error::{ContextSmithError, Result};
 use crate::git::{DiffFile, DiffHunk, FileStatus, LineKind};
+use crate::units::UnitFinder;
+use crate::utils;
 
 // ---------------------------------------------------------------------------
 // Public types
@@ -26,6 +30,9 @@ pub struct SliceOptions {
     pub context_lines: usize,
     /// If true, emit only the raw hunk lines without reading the full file.
     pub hunks_only: bool,
+    /// If true, grow each hunk to its enclosing function or method
+    /// (`context_lines` still applies where there is none).
+    pub function_context: bool,
     /// Repository root — source files are resolved relative to this.
     pub root: PathBuf,
 }
@@ -129,11 +136,23 @@ fn slice_with_context(file: &DiffFile, options: &SliceOptions) -> Result<Vec<Sni
     }
 
     // Compute expanded ranges from all hunks, then merge overlaps.
-    let ranges = compute_merged_ranges(&file.hunks, options.context_lines, total_lines);
+    let ranges = if options.function_context {
+        compute_unit_ranges(
+            &file.hunks,
+            &file_lines.join("\n"),
+            &utils::infer_language(&file.path),
+            options.context_lines,
+        )
+    } else {
+        compute_merged_ranges(&file.hunks, options.context_lines, total_lines)
+            .into_iter()
+            .map(|(start, end)| (start, end, Vec::new()))
+            .collect()
+    };
 
     let snippets = ranges
         .into_iter()
-        .filter_map(|(start, end)| {
+        .filter_map(|(start, end, units)| {
             // Clamp to valid file bounds (1-based → 0-based indexing).
             let clamped_start = start.max(1);
             let clamped_end = end.min(total_lines);
@@ -147,7 +166,11 @@ fn slice_with_context(file: &DiffFile, options: &SliceOptions) -> Result<Vec<Sni
                 start_line: clamped_start,
                 end_line: clamped_end,
                 content,
-                reason: status_reason(file.status),
+                reason: if units.is_empty() {
+                    status_reason(file.status)
+                } else {
+                    unit_reason(file.status, &units)
+                },
             })
         })
         .collect();
@@ -155,6 +178,96 @@ fn slice_with_context(file: &DiffFile, options: &SliceOptions) -> Result<Vec<Sni
     Ok(snippets)
 }
 
+// ---------------------------------------------------------------------------
+// Before/after views
+// ---------------------------------------------------------------------------
+
+/// Pair a new-side snippet with the matching lines of the old file.
+///
+/// `old_content` is the file before the change (read from its old path
+/// for renames). Returns `(before, after)`; reasons are prefixed with
+/// "before"/"after", naming both paths when the file was renamed.
+pub fn pair_with_old(file: &DiffFile, after: Snippet, old_content: &str) -> (Snippet, Snippet) {
+    let old_lines: Vec<&str> = old_content.lines().collect();
+    let (start, end) = old_side_range(&file.hunks, after.start_line, after.end_line);
+    let start = start.clamp(1, old_lines.len().max(1));
+    let end = end.clamp(start, old_lines.len().max(start));
+    let old_path = file.old_path.as_deref().unwrap_or(&file.path);
+
+    let (before_label, after_label) = if old_path != file.path {
+        (
+            format!("before (`{old_path}`)"),
+            format!("after (`{}`)", file.path),
+        )
+    } else {
+        ("before".to_string(), "after".to_string())
+    };
+    let before = Snippet {
+        file_path: old_path.to_string(),
+        start_line: start,
+        end_line: end,
+        content: old_lines
+            .get(start - 1..end.min(old_lines.len()))
+            .unwrap_or_default()
+            .join("\n"),
+        reason: format!("{before_label}: {}", after.reason),
+    };
+    let after = Snippet {
+        reason: format!("{after_label}: {}", after.reason),
+        ..after
+    };
+    (before, after)
+}
+
+/// The whole old file of a deleted file, as one "before" snippet.
+pub fn deleted_file_snippet(file: &DiffFile, old_content: &str) -> Snippet {
+    let total_lines = old_content.lines().count();
+    Snippet {
+        file_path: file.path.clone(),
+        start_line: 1,
+        end_line: total_lines.max(1),
+        content: old_content.trim_end_matches('\n').to_string(),
+        reason: format!("before: {}", status_reason(file.status)),
+    }
+}
+
+/// Map a new-side line range to the old file through the hunks.
+fn old_side_range(hunks: &[DiffHunk], start: usize, end: usize) -> (usize, usize) {
+    let old_start = old_line(hunks, start, false);
+    let old_end = old_line(hunks, end, true);
+    (old_start, old_end.max(old_start))
+}
+
+/// The old line matching new line `line`.
+///
+/// Context lines map directly. An added line has no old counterpart, so a
+/// range start maps to the old line after it and a range end to the one
+/// before, keeping removals between two mapped lines inside the range.
+/// Lines outside hunks shift by the line-count change of the hunks above.
+fn old_line(hunks: &[DiffHunk], line: usize, is_end: bool) -> usize {
+    let mut shift: isize = 0;
+    for h in hunks {
+        if line < h.new_start {
+            break;
+        }
+        if line < h.new_start + h.new_count {
+            let mut old_cursor = h.old_start.saturating_sub(1);
+            for dl in &h.lines {
+                if dl.new_lineno == Some(line) {
+                    return match (dl.kind, dl.old_lineno) {
+                        (LineKind::Context, Some(old)) => old,
+                        _ if is_end => old_cursor.max(1),
+                        _ => old_cursor + 1,
+                    };
+                }
+                old_cursor = dl.old_lineno.unwrap_or(old_cursor);
+            }
+        }
+        shift += h.new_count as isize - h.old_count as isize;
+    }
+    (line as isize - shift).max(1) as usize
+}
+
 /// Compute line ranges for all hunks, expand by context, and merge overlaps.
 ///
 /// Uses the actual changed (added/removed) line numbers within each hunk
@@ -200,6 +313,99 @@ fn compute_merged_ranges(
     merge_overlapping_ranges(ranges)
 }
 
+/// Grow each hunk's changed lines to their enclosing units, and merge.
+///
+/// Changes are located on the new side (a removal sits where the next
+/// new line would be), and both ends of a hunk are looked up, so a hunk
+/// spanning two functions takes both. Ends outside any unit keep
+/// `context_lines` of context instead. Overlapping ranges are merged and
+/// keep every unit label, in line order.
+fn compute_unit_ranges(
+    hunks: &[DiffHunk],
+    content: &str,
+    language: &str,
+    context_lines: usize,
+) -> Vec<(usize, usize, Vec<String>)> {
+    let total_lines = content.lines().count();
+    let finder = UnitFinder::new(content, language);
+
+    let mut ranges: Vec<(usize, usize, Vec<String>)> = hunks
+        .iter()
+        .map(|h| {
+            let (change_start, change_end) = new_side_change(h, total_lines);
+            let mut start = change_start.saturating_sub(context_lines).max(1);
+            let mut end = (change_end + context_lines).min(total_lines);
+            let mut units = Vec::new();
+
+            for (i, line) in [change_start, change_end].into_iter().enumerate() {
+                let Some(unit) = finder.enclosing(line) else {
+                    continue;
+                };
+                if i == 0 {
+                    start = unit.start_line;
+                } else {
+                    end = unit.end_line;
+                }
+                if let Some(label) = unit.label {
+                    if !units.contains(&label) {
+                        units.push(label);
+                    }
+                }
+            }
+            (
+                start.min(change_start),
+                end.max(change_end.min(total_lines)),
+                units,
+            )
+        })
+        .collect();
+
+    ranges.sort_by_key(|&(s, _, _)| s);
+    let mut merged: Vec<(usize, usize, Vec<String>)> = Vec::new();
+    for (start, end, units) in ranges {
+        if let Some(last) = merged.last_mut() {
+            if start <= last.1 + 1 {
+                last.1 = last.1.max(end);
+                for unit in units {
+                    if !last.2.contains(&unit) {
+                        last.2.push(unit);
+                    }
+                }
+                continue;
+            }
+        }
+        merged.push((start, end, units));
+    }
+    merged
+}
+
+/// First and last changed line of a hunk in the new file.
+///
+/// Removed lines have no new line number, so each is placed just after
+/// the last new-side line seen before it.
+fn new_side_change(hunk: &DiffHunk, total_lines: usize) -> (usize, usize) {
+    let mut cursor = hunk.new_start.saturating_sub(1);
+    let mut changed = Vec::new();
+    for line in &hunk.lines {
+        match line.kind {
+            LineKind::Context => cursor = line.new_lineno.unwrap_or(cursor),
+            LineKind::Added => {
+                cursor = line.new_lineno.unwrap_or(cursor + 1);
+                changed.push(cursor);
+            }
+            LineKind::Removed => changed.push(cursor + 1),
+        }
+    }
+    let clamp = |line: usize| line.clamp(1, total_lines.max(1));
+    match (changed.iter().min(), changed.iter().max()) {
+        (Some(&min), Some(&max)) => (clamp(min), clamp(max)),
+        _ => (
+            clamp(hunk.new_start),
+            clamp(hunk.new_start + hunk.new_count.saturating_sub(1)),
+        ),
+    }
+}
+
 /// Merge a sorted list of ranges, combining any that overlap or are adjacent.
 fn merge_overlapping_ranges(sorted: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
     let mut merged: Vec<(usize, usize)> = Vec::new();
@@ -225,6 +431,17 @@ fn read_file_lines(path: &Path) -> Result<Vec<String>> {
     Ok(content.lines().map(String::from).collect())
 }
 
+/// Reason for a snippet grown to whole units: "modified in fn `greedy_pack`".
+fn unit_reason(status: FileStatus, units: &[String]) -> String {
+    let verb = match status {
+        FileStatus::Added => "added",
+        FileStatus::Modified => "modified",
+        FileStatus::Deleted => "deleted",
+        FileStatus::Renamed => "renamed, modified",
+    };
+    format!("{verb} in {}", units.join(", "))
+}
+
 /// Map a [`FileStatus`] to a human-readable reason string.
 fn status_reason(status: FileStatus) -> String {
     match status {
@@ -289,6 +506,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 2,
             hunks_only: false,
+            function_context: false,
             root,
         };
 
@@ -302,6 +520,144 @@ mod tests {
         drop(dir);
     }
 
+    #[test]
+    fn function_context_grows_hunks_to_enclosing_units() {
+        let mut lines = vec!["use std::fmt;".to_string(), String::new()];
+        lines.push("fn greedy_pack(budget: usize) -> usize {".to_string());
+        lines.extend((1..=20).map(|i| format!("    let step{i} = {i};")));
+        lines.push("    budget".to_string());
+        lines.push("}".to_string());
+        lines.push(String::new());
+        lines.push("fn helper() {}".to_string());
+        let (dir, root) = setup_source_file("pack.rs", &lines.join("\n"));
+
+        // Two one-line changes deep inside greedy_pack, plus one in helper.
+        let mut diff = make_diff_file("pack.rs", 15, 1);
+        let mut second = diff.hunks[0].clone();
+        second.new_start = 20;
+        second.lines[0].new_lineno = Some(20);
+        let mut third = diff.hunks[0].clone();
+        third.new_start = 27;
+        third.lines[0].new_lineno = Some(27);
+        diff.hunks.extend([second, third]);
+
+        let options = SliceOptions {
+            context_lines: 1,
+            hunks_only: false,
+            function_context: true,
+            root,
+        };
+        let snippets = slice_diff_hunks(&[diff], &options).unwrap();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!((snippets[0].start_line, snippets[0].end_line), (3, 25));
+        assert_eq!(snippets[0].reason, "modified in fn `greedy_pack`");
+        assert!(snippets[0].content.starts_with("fn greedy_pack"));
+        assert_eq!((snippets[1].start_line, snippets[1].end_line), (27, 27));
+        assert_eq!(snippets[1].reason, "modified in fn `helper`");
+
+        drop(dir);
+    }
+
+    #[test]
+    fn function_context_places_removals_on_the_new_side() {
+        let hunk = DiffHunk {
+            old_start: 4,
+            old_count: 3,
+            new_start: 4,
+            new_count: 2,
+            header: "@@ -4,3 +4,2 @@".to_string(),
+            lines: vec![
+                DiffLine {
+                    kind: LineKind::Context,
+                    content: "a".to_string(),
+                    old_lineno: Some(4),
+                    new_lineno: Some(4),
+                },
+                DiffLine {
+                    kind: LineKind::Removed,
+                    content: "b".to_string(),
+                    old_lineno: Some(5),
+                    new_lineno: None,
+                },
+                DiffLine {
+                    kind: LineKind::Context,
+                    content: "c".to_string(),
+                    old_lineno: Some(6),
+                    new_lineno: Some(5),
+                },
+            ],
+        };
+        assert_eq!(new_side_change(&hunk, 10), (5, 5));
+        assert_eq!(new_side_change(&hunk, 4), (4, 4));
+    }
+
+    #[test]
+    fn pair_with_old_maps_range_through_hunks() {
+        let line = |kind, content: &str, old, new| DiffLine {
+            kind,
+            content: content.to_string(),
+            old_lineno: old,
+            new_lineno: new,
+        };
+        // Old: a, b, x, c, d. New: inserted, a, b, y, z, c, d.
+        let file = DiffFile {
+            path: "new.rs".to_string(),
+            old_path: Some("old.rs".to_string()),
+            status: FileStatus::Renamed,
+            hunks: vec![
+                DiffHunk {
+                    old_start: 1,
+                    old_count: 0,
+                    new_start: 1,
+                    new_count: 1,
+                    header: "@@ -0,0 +1,1 @@".to_string(),
+                    lines: vec![line(LineKind::Added, "inserted", None, Some(1))],
+                },
+                DiffHunk {
+                    old_start: 2,
+                    old_count: 3,
+                    new_start: 3,
+                    new_count: 4,
+                    header: "@@ -2,3 +3,4 @@".to_string(),
+                    lines: vec![
+                        line(LineKind::Context, "b", Some(2), Some(3)),
+                        line(LineKind::Removed, "x", Some(3), None),
+                        line(LineKind::Added, "y", None, Some(4)),
+                        line(LineKind::Added, "z", None, Some(5)),
+                        line(LineKind::Context, "c", Some(4), Some(6)),
+                    ],
+                },
+            ],
+        };
+        let after = Snippet {
+            file_path: "new.rs".to_string(),
+            start_line: 3,
+            end_line: 6,
+            content: "b\ny\nz\nc".to_string(),
+            reason: "renamed".to_string(),
+        };
+
+        let (before, after) = pair_with_old(&file, after, "a\nb\nx\nc\nd\n");
+        assert_eq!(before.file_path, "old.rs");
+        assert_eq!((before.start_line, before.end_line), (2, 4));
+        assert_eq!(before.content, "b\nx\nc");
+        assert_eq!(before.reason, "before (`old.rs`): renamed");
+        assert_eq!(after.reason, "after (`new.rs`): renamed");
+
+        // Past every hunk, lines shift by the net line-count change.
+        assert_eq!(old_side_range(&file.hunks, 7, 7), (5, 5));
+    }
+
+    #[test]
+    fn deleted_file_snippet_holds_the_whole_old_file() {
+        let mut file = make_diff_file("gone.rs", 1, 1);
+        file.status = FileStatus::Deleted;
+        let snippet = deleted_file_snippet(&file, "fn a() {}\nfn b() {}\n");
+        assert_eq!((snippet.start_line, snippet.end_line), (1, 2));
+        assert_eq!(snippet.content, "fn a() {}\nfn b() {}");
+        assert_eq!(snippet.reason, "before: deleted");
+    }
+
     #[test]
     fn overlapping_hunks_are_merged() {
         let source = (1..=20)
@@ -348,6 +704,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 3,
             hunks_only: false,
+            function_context: false,
             root,
         };
 
@@ -366,6 +723,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 3,
             hunks_only: true,
+            function_context: false,
             root: PathBuf::from("/unused"),
         };
 
@@ -406,6 +764,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 3,
             hunks_only: false,
+            function_context: false,
             root: PathBuf::from("/unused"),
         };
 
@@ -421,6 +780,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 3,
             hunks_only: false,
+            function_context: false,
             root: PathBuf::from("/tmp/empty_dir_that_should_not_exist"),
         };
 
@@ -452,6 +812,7 @@ mod tests {
         let options = SliceOptions {
             context_lines: 5,
             hunks_only: false,
+            function_context: false,
             root,
         };
 
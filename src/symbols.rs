@@ -1,11 +1,16 @@
-//! Symbol search abstraction for finding definitions in source code.
+//! Symbol search abstraction for finding definitions and references in
+//! source code.
 //!
 //! Provides a trait-based design so regex-based search (Phase 2) can be
 //! swapped for tree-sitter–based search (Phase 3) without changing
 //! downstream code.
 
+use std::sync::OnceLock;
+
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
+use crate::cache::IndexCache;
 use crate::error::{ContextSmithError, Result};
 use crate::indexer::{self, TextMatch};
 use crate::scanner::ScannedFile;
@@ -25,6 +30,16 @@ pub trait SymbolFinder: Send + Sync {
     fn find_definitions(&self, files: &[ScannedFile], symbol: &str) -> Result<Vec<TextMatch>>;
 }
 
+/// Finds references (uses) of a symbol across source files.
+///
+/// The counterpart to [`SymbolFinder`]: where that answers "where is this
+/// defined?", this answers "who uses it?".
+pub trait ReferenceFinder: Send + Sync {
+    /// Find whole-word uses of `symbol`, one per line, skipping comments,
+    /// string literals and the definition sites themselves.
+    fn find_references(&self, files: &[ScannedFile], symbol: &str) -> Result<Vec<TextMatch>>;
+}
+
 // ---------------------------------------------------------------------------
 // Regex-based implementation
 // ---------------------------------------------------------------------------
@@ -39,6 +54,25 @@ pub struct RegexSymbolFinder;
 
 impl SymbolFinder for RegexSymbolFinder {
     fn find_definitions(&self, files: &[ScannedFile], symbol: &str) -> Result<Vec<TextMatch>> {
+        self.find_definitions_cached(files, symbol, &mut IndexCache::disabled())
+    }
+}
+
+impl ReferenceFinder for RegexSymbolFinder {
+    fn find_references(&self, files: &[ScannedFile], symbol: &str) -> Result<Vec<TextMatch>> {
+        self.find_references_cached(files, symbol, &mut IndexCache::disabled())
+    }
+}
+
+impl RegexSymbolFinder {
+    /// Like [`SymbolFinder::find_definitions`], but skips files whose
+    /// cached terms cannot contain `symbol` and re-indexes every file read.
+    pub fn find_definitions_cached(
+        &self,
+        files: &[ScannedFile],
+        symbol: &str,
+        cache: &mut IndexCache,
+    ) -> Result<Vec<TextMatch>> {
         let pattern = build_symbol_pattern(symbol);
         let re = Regex::new(&pattern)
             .map_err(|e| ContextSmithError::pattern(&pattern, e.to_string()))?;
@@ -46,10 +80,14 @@ impl SymbolFinder for RegexSymbolFinder {
         let mut all_matches = Vec::new();
 
         for file in files {
+            if !cache.may_contain(file, symbol) {
+                continue;
+            }
             let content = match std::fs::read_to_string(&file.abs_path) {
                 Ok(c) => c,
                 Err(_) => continue,
             };
+            cache.update(file, &content);
 
             let matches = indexer::search_content(&re, &content, &file.rel_path);
             all_matches.extend(matches);
@@ -57,6 +95,53 @@ impl SymbolFinder for RegexSymbolFinder {
 
         Ok(all_matches)
     }
+
+    /// Like [`ReferenceFinder::find_references`], but skips files whose
+    /// cached terms cannot contain `symbol` and re-indexes every file read.
+    pub fn find_references_cached(
+        &self,
+        files: &[ScannedFile],
+        symbol: &str,
+        cache: &mut IndexCache,
+    ) -> Result<Vec<TextMatch>> {
+        let word_pattern = format!(r"\b{}\b", regex::escape(symbol));
+        let word = Regex::new(&word_pattern)
+            .map_err(|e| ContextSmithError::pattern(&word_pattern, e.to_string()))?;
+        let definition_pattern = build_symbol_pattern(symbol);
+        let definition = Regex::new(&definition_pattern)
+            .map_err(|e| ContextSmithError::pattern(&definition_pattern, e.to_string()))?;
+
+        let mut all_matches = Vec::new();
+
+        for file in files {
+            if !cache.may_contain(file, symbol) {
+                continue;
+            }
+            let content = match std::fs::read_to_string(&file.abs_path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            cache.update(file, &content);
+
+            let masked = mask_code(&content, &file.language);
+            for (idx, (line, code)) in content.lines().zip(&masked).enumerate() {
+                if definition.is_match(code) {
+                    continue;
+                }
+                if let Some(m) = word.find(code) {
+                    all_matches.push(TextMatch {
+                        file_path: file.rel_path.clone(),
+                        line_number: idx + 1,
+                        line_content: line.to_string(),
+                        column: m.start(),
+                        match_length: m.len(),
+                    });
+                }
+            }
+        }
+
+        Ok(all_matches)
+    }
 }
 
 /// Build a regex pattern that matches common definition forms for a symbol.
@@ -81,6 +166,152 @@ pub fn build_symbol_pattern(symbol: &str) -> String {
     )
 }
 
+// ---------------------------------------------------------------------------
+// Comment and string masking
+// ---------------------------------------------------------------------------
+
+/// Blank out comments and string-literal contents, keeping byte offsets.
+///
+/// Returns one line per input line with masked bytes replaced by spaces;
+/// quote characters are kept, so `"Config"` becomes `"      "`. Line
+/// comments use the language's marker (`//`, `#` or `--`), and `/* */`
+/// block comments may span lines in languages that have them.
+pub fn mask_code(content: &str, language: &str) -> Vec<String> {
+    let line_comment = line_comment_marker(language);
+    let block_comments = line_comment != "#";
+    let quotes = string_quotes(language);
+    let mut in_block = false;
+    let mut masked_lines = Vec::new();
+
+    for line in content.lines() {
+        let mut masked = String::with_capacity(line.len());
+        let mut quote: Option<char> = None;
+        let mut chars = line.char_indices();
+
+        while let Some((idx, c)) = chars.next() {
+            let rest = &line[idx..];
+            if in_block {
+                if rest.starts_with("*/") {
+                    in_block = false;
+                    chars.next();
+                    masked.push_str("  ");
+                } else {
+                    blank(&mut masked, c);
+                }
+            } else if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                    masked.push(c);
+                } else {
+                    blank(&mut masked, c);
+                    if c == '\\' {
+                        if let Some((_, escaped)) = chars.next() {
+                            blank(&mut masked, escaped);
+                        }
+                    }
+                }
+            } else if rest.starts_with(line_comment) {
+                rest.chars().for_each(|c| blank(&mut masked, c));
+                break;
+            } else if block_comments && rest.starts_with("/*") {
+                in_block = true;
+                chars.next();
+                masked.push_str("  ");
+            } else {
+                if quotes.contains(&c) {
+                    quote = Some(c);
+                }
+                masked.push(c);
+            }
+        }
+        masked_lines.push(masked);
+    }
+
+    masked_lines
+}
+
+/// Replace `c` with as many spaces as it has bytes.
+fn blank(masked: &mut String, c: char) {
+    masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+}
+
+/// Line comment marker for a language.
+fn line_comment_marker(language: &str) -> &'static str {
+    match language {
+        "python" | "ruby" | "bash" | "yaml" | "toml" | "makefile" | "dockerfile" => "#",
+        "sql" => "--",
+        _ => "//",
+    }
+}
+
+/// Characters that open a string literal in a language. Rust's `'` is
+/// left out because it also starts lifetimes.
+fn string_quotes(language: &str) -> &'static [char] {
+    match language {
+        "python" | "ruby" | "bash" => &['"', '\''],
+        "javascript" | "typescript" => &['"', '\'', '`'],
+        "go" => &['"', '`'],
+        _ => &['"'],
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Symbol outlines
+// ---------------------------------------------------------------------------
+
+/// A definition found while outlining a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    /// Symbol name as written after the keyword.
+    pub name: String,
+    /// Definition keyword as written (`fn`, `struct`, `class`, `def`, ...).
+    pub kind: String,
+    /// Line number of the definition (1-based).
+    pub line: usize,
+    /// Leading whitespace width, used to show nesting.
+    pub indent: usize,
+}
+
+/// Extract a flat outline of the definitions in `content`.
+///
+/// Uses the same keyword heuristics as [`build_symbol_pattern`], but
+/// anchored at the start of a line so references in expressions are not
+/// picked up. Variable bindings (`let`, `var`, `const`) are skipped to
+/// keep the outline compact.
+pub fn extract_symbols(content: &str) -> Vec<SymbolInfo> {
+    let re = outline_regex();
+    let mut symbols = Vec::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        if let Some(caps) = re.captures(line) {
+            let kind = caps.get(2).map_or("", |m| m.as_str()).to_string();
+            let name = caps.get(3).map_or("", |m| m.as_str()).to_string();
+            if name.is_empty() {
+                continue;
+            }
+            symbols.push(SymbolInfo {
+                name,
+                kind,
+                line: idx + 1,
+                indent: caps.get(1).map_or(0, |m| m.as_str().len()),
+            });
+        }
+    }
+
+    symbols
+}
+
+/// Regex used by [`extract_symbols`], compiled once.
+fn outline_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(\s*)(?:pub(?:\([^)]*\))?\s+|export\s+(?:default\s+)?|public\s+|private\s+|protected\s+|abstract\s+|static\s+|final\s+)*(?:(?:async|unsafe|extern(?:\s+\x22[^\x22]*\x22)?|data|sealed)\s+)*(fn|struct|enum|trait|type|mod|impl|def|class|function|func|interface|module)\b(?:<[^>]*>)?\s*(?:\([^)]*\)\s*)?([A-Za-z_][A-Za-z0-9_]*)",
+        )
+        .expect("outline regex is valid")
+    })
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -116,6 +347,73 @@ mod tests {
         assert!(re.is_match("async def process(data):"));
     }
 
+    #[test]
+    fn extract_symbols_outlines_common_languages() {
+        let rust =
+            "pub struct Config {\n}\n\nimpl<T> Display for Config {\n    pub fn fmt(&self) {}\n}\n";
+        let symbols = extract_symbols(rust);
+        let names: Vec<(&str, &str)> = symbols
+            .iter()
+            .map(|s| (s.kind.as_str(), s.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("struct", "Config"), ("impl", "Display"), ("fn", "fmt")]
+        );
+        assert_eq!(symbols[2].line, 5);
+        assert_eq!(symbols[2].indent, 4);
+
+        let python = "class Repo:\n    async def fetch(self):\n        pass\n";
+        let symbols = extract_symbols(python);
+        assert_eq!(symbols[0].name, "Repo");
+        assert_eq!(symbols[1].name, "fetch");
+
+        let go = "func (s *Server) Start() error {\n}\nfunc main() {}\n";
+        let symbols = extract_symbols(go);
+        assert_eq!(symbols[0].name, "Start");
+        assert_eq!(symbols[1].name, "main");
+    }
+
+    #[test]
+    fn extract_symbols_ignores_calls() {
+        let content = "let x = run();\nfoo(function_name);\n";
+        assert!(extract_symbols(content).is_empty());
+    }
+
+    #[test]
+    fn mask_code_blanks_comments_and_strings() {
+        let rust = "let a = run(\"run\"); // run\n/* run\n run */ run();\n";
+        assert_eq!(
+            mask_code(rust, "rust"),
+            vec!["let a = run(\"   \");       ", "      ", "        run();"]
+        );
+
+        let python = "x = 'a # b'  # run\n";
+        assert_eq!(mask_code(python, "python"), vec!["x = '     '       "]);
+    }
+
+    #[test]
+    fn regex_reference_finder_skips_noise_and_definitions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.rs"),
+            "pub fn run() {}\n\nfn main() {\n    // run later\n    run();\n    log(\"run\");\n    run_tests(); run();\n}\n",
+        )
+        .unwrap();
+        let files = vec![ScannedFile {
+            rel_path: "a.rs".to_string(),
+            abs_path: dir.path().join("a.rs"),
+            language: "rust".to_string(),
+            is_generated: false,
+            size: 0,
+        }];
+
+        let matches = RegexSymbolFinder.find_references(&files, "run").unwrap();
+        let lines: Vec<usize> = matches.iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, vec![5, 7]);
+        assert_eq!(matches[1].column, 17);
+    }
+
     #[test]
     fn regex_symbol_finder_across_files() {
         let dir = tempfile::tempdir().unwrap();
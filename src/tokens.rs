@@ -1,9 +1,29 @@
 //! Token estimation for context budgeting.
 //!
-//! Provides a trait-based architecture for token counting, with a built-in
-//! character heuristic as the default implementation. Real tokenizers
-//! (tiktoken-rs, custom BPE, etc.) can be plugged in by implementing
-//! the [`TokenEstimator`] trait.
+//! Provides a trait-based architecture for token counting. Two
+//! implementations ship built in:
+//!
+//! - [`BpeEstimator`] — exact byte-pair encoding against an OpenAI-style
+//!   vocabulary (`cl100k_base`, `o200k_base`) loaded from a local
+//!   `.tiktoken` file. Fully offline; vocabularies are never downloaded.
+//! - [`CharEstimator`] — a character heuristic, used when no vocabulary
+//!   is available for the requested model.
+//!
+//! [`estimator_for_model`] picks the best available estimator.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use directories::ProjectDirs;
+
+use crate::error::{ContextSmithError, Result};
+
+/// Model assumed when none is requested.
+pub const DEFAULT_MODEL: &str = "gpt-4";
+
+/// Environment variable naming a directory that holds `.tiktoken` files.
+pub const TOKENIZER_DIR_ENV: &str = "CONTEXTSMITH_TOKENIZER_DIR";
 
 // ---------------------------------------------------------------------------
 // Trait (extensibility point)
@@ -19,6 +39,26 @@ pub trait TokenEstimator: Send + Sync {
 
     /// Human-readable name of the model this estimator targets.
     fn model_name(&self) -> &str;
+
+    /// Identifies how counts are produced, so the index cache never
+    /// reuses counts from a different estimator for the same model.
+    fn cache_key(&self) -> String {
+        self.model_name().to_string()
+    }
+}
+
+impl<T: TokenEstimator + ?Sized> TokenEstimator for Box<T> {
+    fn estimate(&self, text: &str) -> usize {
+        (**self).estimate(text)
+    }
+
+    fn model_name(&self) -> &str {
+        (**self).model_name()
+    }
+
+    fn cache_key(&self) -> String {
+        (**self).cache_key()
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -70,15 +110,17 @@ impl CharEstimator {
             model,
         }
     }
+
+    /// Tokens in `chars` characters of text, at this family's fixed
+    /// characters-per-token ratio.
+    pub fn tokens_for_chars(&self, chars: usize) -> usize {
+        (chars as f64 / self.chars_per_token).ceil() as usize
+    }
 }
 
 impl TokenEstimator for CharEstimator {
     fn estimate(&self, text: &str) -> usize {
-        if text.is_empty() {
-            return 0;
-        }
-        let chars = text.len() as f64;
-        (chars / self.chars_per_token).ceil() as usize
+        self.tokens_for_chars(text.len())
     }
 
     fn model_name(&self) -> &str {
@@ -91,6 +133,336 @@ impl TokenEstimator for CharEstimator {
     }
 }
 
+// ---------------------------------------------------------------------------
+// BPE encodings
+// ---------------------------------------------------------------------------
+
+/// BPE vocabularies understood by [`BpeEstimator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// GPT-4, GPT-3.5 Turbo and the `text-embedding-3` family.
+    Cl100kBase,
+    /// GPT-4o, GPT-4.1 and the o-series reasoning models.
+    O200kBase,
+}
+
+impl Encoding {
+    /// Encoding name as used by tiktoken.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cl100kBase => "cl100k_base",
+            Self::O200kBase => "o200k_base",
+        }
+    }
+
+    /// File name of the vocabulary (`<name>.tiktoken`).
+    pub fn file_name(self) -> String {
+        format!("{}.tiktoken", self.name())
+    }
+
+    /// The encoding used by a model, if it has a public vocabulary.
+    pub fn for_model(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        let o_series = ["o1", "o3", "o4"]
+            .iter()
+            .any(|p| lower == *p || lower.starts_with(&format!("{p}-")));
+        if lower.contains("gpt-4o")
+            || lower.contains("gpt4o")
+            || lower.contains("gpt-4.1")
+            || o_series
+        {
+            Some(Self::O200kBase)
+        } else if matches!(parse_model(&lower), ModelFamily::Gpt4 | ModelFamily::Gpt35)
+            || lower.starts_with("text-embedding-3")
+        {
+            Some(Self::Cl100kBase)
+        } else {
+            None
+        }
+    }
+}
+
+/// Merge ranks of a BPE vocabulary: token bytes → rank.
+type Ranks = HashMap<Vec<u8>, u32>;
+
+/// Pieces longer than this are split before merging to bound the
+/// quadratic merge loop on pathological input (minified files, long runs
+/// of punctuation). The effect on counts is negligible.
+const MAX_PIECE_BYTES: usize = 512;
+
+// ---------------------------------------------------------------------------
+// BPE estimator
+// ---------------------------------------------------------------------------
+
+/// Token estimator that runs byte-pair encoding against a real vocabulary.
+///
+/// Text is split with a hand-written equivalent of the cl100k
+/// pre-tokenizer, then each piece is merged by rank exactly as tiktoken
+/// does. Counts match tiktoken for typical code and prose; `o200k_base`
+/// uses the same pre-tokenizer, which can differ by a token or two on
+/// mixed-case identifiers.
+#[derive(Debug, Clone)]
+pub struct BpeEstimator {
+    model: String,
+    ranks: Arc<Ranks>,
+}
+
+impl BpeEstimator {
+    /// Build an estimator from already-parsed merge ranks.
+    pub fn from_ranks(model: impl Into<String>, ranks: HashMap<Vec<u8>, u32>) -> Self {
+        Self {
+            model: model.into(),
+            ranks: Arc::new(ranks),
+        }
+    }
+
+    /// Load a tiktoken-format vocabulary file (`<base64 token> <rank>` per line).
+    pub fn load(model: impl Into<String>, path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ContextSmithError::io(
+                format!("reading tokenizer vocabulary '{}'", path.display()),
+                e,
+            )
+        })?;
+        Ok(Self::from_ranks(model, parse_tiktoken(&content)?))
+    }
+
+    /// Number of BPE tokens in a single pre-tokenized piece.
+    fn count_piece(&self, piece: &[u8]) -> usize {
+        if piece.len() <= 1 || self.ranks.contains_key(piece) {
+            return 1;
+        }
+
+        // Token boundaries; repeatedly merge the adjacent pair with the
+        // lowest rank until no pair is in the vocabulary.
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        while bounds.len() > 2 {
+            let mut best: Option<(u32, usize)> = None;
+            for i in 0..bounds.len() - 2 {
+                if let Some(&rank) = self.ranks.get(&piece[bounds[i]..bounds[i + 2]]) {
+                    if best.is_none_or(|(r, _)| rank < r) {
+                        best = Some((rank, i));
+                    }
+                }
+            }
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => break,
+            }
+        }
+        bounds.len() - 1
+    }
+}
+
+impl TokenEstimator for BpeEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        pretokenize(text)
+            .into_iter()
+            .flat_map(|piece| piece.as_bytes().chunks(MAX_PIECE_BYTES))
+            .map(|chunk| self.count_piece(chunk))
+            .sum()
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}+bpe{}", self.model, self.ranks.len())
+    }
+}
+
+/// Parse the tiktoken vocabulary format: one `<base64 bytes> <rank>` pair
+/// per line.
+pub fn parse_tiktoken(content: &str) -> Result<HashMap<Vec<u8>, u32>> {
+    let mut ranks = HashMap::new();
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let malformed = || ContextSmithError::Tokenization {
+            message: format!("malformed vocabulary line {}: '{}'", idx + 1, line),
+        };
+        let (token, rank) = line.split_once(' ').ok_or_else(malformed)?;
+        let bytes = decode_base64(token).ok_or_else(malformed)?;
+        let rank: u32 = rank.trim().parse().map_err(|_| malformed())?;
+        ranks.insert(bytes, rank);
+    }
+    Ok(ranks)
+}
+
+/// Decode standard (padded) base64.
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let trimmed = input.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(trimmed.len() * 3 / 4);
+    for chunk in trimmed.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut acc = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            acc |= value(c)? << (18 - 6 * i);
+        }
+        out.push((acc >> 16) as u8);
+        if chunk.len() > 2 {
+            out.push((acc >> 8) as u8);
+        }
+        if chunk.len() > 3 {
+            out.push(acc as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Split text into pieces the way the cl100k pre-tokenizer does:
+/// contractions, optionally-prefixed letter runs, 1–3 digit groups,
+/// punctuation runs with an optional leading space, newline runs, and
+/// whitespace (the last space before a word is attached to that word).
+pub fn pretokenize(text: &str) -> Vec<&str> {
+    const CONTRACTIONS: [&str; 7] = ["'s", "'t", "'re", "'ve", "'m", "'ll", "'d"];
+    let is_newline = |c: char| c == '\r' || c == '\n';
+    let is_punct = |c: char| !c.is_whitespace() && !c.is_alphabetic() && !c.is_numeric();
+
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(text.len(), |&(b, _)| b);
+    let char_at = |i: usize| chars.get(i).map(|&(_, c)| c);
+
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let start = i;
+
+        if c == '\'' {
+            let rest = &text[byte_at(i)..];
+            if let Some(con) = CONTRACTIONS.iter().find(|con| {
+                rest.len() >= con.len()
+                    && rest.is_char_boundary(con.len())
+                    && rest[..con.len()].eq_ignore_ascii_case(con)
+            }) {
+                i += con.chars().count();
+                pieces.push(&text[byte_at(start)..byte_at(i)]);
+                continue;
+            }
+        }
+
+        // Letter run, optionally prefixed by one non-letter, non-digit,
+        // non-newline character (a space, `_`, `.`, `$`, ...).
+        let prefixed = !is_newline(c)
+            && !c.is_alphabetic()
+            && !c.is_numeric()
+            && char_at(i + 1).is_some_and(|n| n.is_alphabetic());
+        if c.is_alphabetic() || prefixed {
+            i += 1;
+            while char_at(i).is_some_and(|n| n.is_alphabetic()) {
+                i += 1;
+            }
+            pieces.push(&text[byte_at(start)..byte_at(i)]);
+            continue;
+        }
+
+        if c.is_numeric() {
+            while i - start < 3 && char_at(i).is_some_and(|n| n.is_numeric()) {
+                i += 1;
+            }
+            pieces.push(&text[byte_at(start)..byte_at(i)]);
+            continue;
+        }
+
+        // Punctuation run with an optional leading space, plus any newlines.
+        let spaced_punct = c == ' ' && char_at(i + 1).is_some_and(is_punct);
+        if is_punct(c) || spaced_punct {
+            i += 1;
+            while char_at(i).is_some_and(is_punct) {
+                i += 1;
+            }
+            while char_at(i).is_some_and(is_newline) {
+                i += 1;
+            }
+            pieces.push(&text[byte_at(start)..byte_at(i)]);
+            continue;
+        }
+
+        // Whitespace: up to the last newline in the run if it has one;
+        // otherwise the whole run except the space that prefixes the
+        // next token.
+        let mut end = i;
+        while char_at(end).is_some_and(char::is_whitespace) {
+            end += 1;
+        }
+        let last_newline = (i..end).rev().find(|&k| is_newline(chars[k].1));
+        i = match last_newline {
+            Some(k) => k + 1,
+            None if end == chars.len() || end - i == 1 => end,
+            None => end - 1,
+        };
+        pieces.push(&text[byte_at(start)..byte_at(i)]);
+    }
+    pieces
+}
+
+// ---------------------------------------------------------------------------
+// Vocabulary discovery
+// ---------------------------------------------------------------------------
+
+/// Directories searched for `.tiktoken` vocabulary files, in order:
+/// `$CONTEXTSMITH_TOKENIZER_DIR`, then the per-user data directory
+/// (e.g. `~/.local/share/contextsmith/tokenizers`).
+pub fn tokenizer_search_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::var_os(TOKENIZER_DIR_ENV) {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(proj_dirs) = ProjectDirs::from("", "", "contextsmith") {
+        dirs.push(proj_dirs.data_dir().join("tokenizers"));
+    }
+    dirs
+}
+
+/// Load (once per process) the merge ranks for `encoding`.
+///
+/// Returns `None` when no vocabulary file is installed or it fails to
+/// parse; callers then fall back to the character heuristic.
+fn load_ranks(encoding: Encoding) -> Option<Arc<Ranks>> {
+    static CL100K: OnceLock<Option<Arc<Ranks>>> = OnceLock::new();
+    static O200K: OnceLock<Option<Arc<Ranks>>> = OnceLock::new();
+    let cell = match encoding {
+        Encoding::Cl100kBase => &CL100K,
+        Encoding::O200kBase => &O200K,
+    };
+
+    cell.get_or_init(|| {
+        let path = tokenizer_search_dirs()
+            .into_iter()
+            .map(|dir| dir.join(encoding.file_name()))
+            .find(|p| p.is_file())?;
+        match BpeEstimator::load(encoding.name(), &path) {
+            Ok(estimator) => {
+                tracing::debug!("loaded {} from {}", encoding.name(), path.display());
+                Some(estimator.ranks)
+            }
+            Err(err) => {
+                tracing::warn!("ignoring tokenizer vocabulary: {err}");
+                None
+            }
+        }
+    })
+    .clone()
+}
+
 // ---------------------------------------------------------------------------
 // Convenience functions
 // ---------------------------------------------------------------------------
@@ -117,9 +489,31 @@ pub fn default_estimator() -> CharEstimator {
     CharEstimator::new(ModelFamily::Gpt4)
 }
 
-/// Create an estimator for the named model.
-pub fn estimator_for_model(name: &str) -> CharEstimator {
-    CharEstimator::new(parse_model(name))
+/// Create the most accurate estimator available for the named model.
+///
+/// Uses a [`BpeEstimator`] when the model's vocabulary is installed
+/// (see [`tokenizer_search_dirs`]) and falls back to the model family's
+/// [`CharEstimator`] otherwise.
+pub fn estimator_for_model(name: &str) -> Box<dyn TokenEstimator> {
+    let fallback = CharEstimator::new(parse_model(name));
+    match Encoding::for_model(name).and_then(bpe_estimator) {
+        Some(estimator) => Box::new(BpeEstimator {
+            model: fallback.model_name().to_string(),
+            ..estimator
+        }),
+        None => {
+            tracing::debug!("no BPE vocabulary for '{name}'; using character heuristic");
+            Box::new(fallback)
+        }
+    }
+}
+
+/// BPE estimator for `encoding`, if its vocabulary is installed.
+pub fn bpe_estimator(encoding: Encoding) -> Option<BpeEstimator> {
+    load_ranks(encoding).map(|ranks| BpeEstimator {
+        model: encoding.name().to_string(),
+        ranks,
+    })
 }
 
 /// Estimate token count using the given model family's heuristic.
@@ -199,6 +593,85 @@ mod tests {
         assert_eq!(estimator_for_model("claude-3-opus").model_name(), "claude");
     }
 
+    /// Tiny vocabulary: bytes `a`, `b`, `c`, ` ` plus merges `ab`, `abc`, ` ab`.
+    fn tiny_vocab() -> HashMap<Vec<u8>, u32> {
+        parse_tiktoken("YQ== 0\nYg== 1\nYw== 2\nIA== 3\nYWI= 4\nYWJj 5\nIGFi 6\n").unwrap()
+    }
+
+    #[test]
+    fn base64_decodes_padded_and_unpadded() {
+        assert_eq!(decode_base64("YWJj").unwrap(), b"abc");
+        assert_eq!(decode_base64("YWI=").unwrap(), b"ab");
+        assert_eq!(decode_base64("IA==").unwrap(), b" ");
+        assert!(decode_base64("Y").is_none());
+        assert!(decode_base64("Y!==").is_none());
+    }
+
+    #[test]
+    fn parse_tiktoken_rejects_malformed_lines() {
+        assert_eq!(tiny_vocab().get(b"abc".as_slice()), Some(&5));
+        assert!(parse_tiktoken("YWJj five\n").is_err());
+        assert!(parse_tiktoken("YWJj\n").is_err());
+    }
+
+    #[test]
+    fn pretokenize_matches_cl100k_splits() {
+        assert_eq!(
+            pretokenize("Hello world's  foo123456\n\n  bar();"),
+            vec!["Hello", " world", "'s", " ", " foo", "123", "456", "\n\n", " ", " bar", "();"]
+        );
+        assert_eq!(
+            pretokenize("self.rank_of(x)"),
+            vec!["self", ".rank", "_of", "(x", ")"]
+        );
+        assert_eq!(pretokenize("a  "), vec!["a", "  "]);
+        assert_eq!(pretokenize("über 5"), vec!["über", " ", "5"]);
+    }
+
+    #[test]
+    fn bpe_merges_by_rank() {
+        let estimator = BpeEstimator::from_ranks("test", tiny_vocab());
+        // "abcab" -> "abc" + "ab"
+        assert_eq!(estimator.estimate("abcab"), 2);
+        // " ab" is a single token, " c" is not.
+        assert_eq!(estimator.estimate(" ab"), 1);
+        assert_eq!(estimator.estimate(" c"), 2);
+        assert_eq!(estimator.estimate(""), 0);
+        assert_eq!(estimator.model_name(), "test");
+        assert_ne!(
+            estimator.cache_key(),
+            CharEstimator::new(ModelFamily::Gpt4).cache_key()
+        );
+    }
+
+    #[test]
+    fn bpe_loads_vocabulary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cl100k_base.tiktoken");
+        std::fs::write(&path, "YQ== 0\nYg== 1\nYWI= 2\n").unwrap();
+
+        let estimator = BpeEstimator::load("gpt-4", &path).unwrap();
+        assert_eq!(estimator.estimate("abab"), 2);
+        assert!(BpeEstimator::load("gpt-4", &dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn encoding_for_model() {
+        assert_eq!(Encoding::for_model("gpt-4"), Some(Encoding::Cl100kBase));
+        assert_eq!(
+            Encoding::for_model("gpt-3.5-turbo"),
+            Some(Encoding::Cl100kBase)
+        );
+        assert_eq!(
+            Encoding::for_model("gpt-4o-mini"),
+            Some(Encoding::O200kBase)
+        );
+        assert_eq!(Encoding::for_model("o3-mini"), Some(Encoding::O200kBase));
+        assert_eq!(Encoding::for_model("claude-3-opus"), None);
+        assert_eq!(Encoding::for_model("llama-70b"), None);
+        assert_eq!(Encoding::O200kBase.file_name(), "o200k_base.tiktoken");
+    }
+
     #[test]
     fn trait_object_works() {
         let estimator: Box<dyn TokenEstimator> = Box::new(default_estimator());
@@ -0,0 +1,433 @@
+//! Syntactic units: the function, impl/class or block around a line.
+//!
+//! A [`UnitFinder`] is built once per file and answers "which unit
+//! encloses line N?". Brace languages are matched on `{`/`}` (with
+//! comments and strings masked out); Python and YAML use indentation.
+//! Anything else, and units too large to be useful, yield `None` so
+//! callers can fall back to fixed line windows.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::symbols;
+
+/// Units longer than this are not worth sending whole.
+pub const MAX_UNIT_LINES: usize = 200;
+
+/// Most lines a brace-language header (signature) may span.
+const MAX_HEADER_LINES: usize = 10;
+
+/// A syntactic unit, as 1-based inclusive line numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unit {
+    /// First line, including leading attributes, decorators and doc comments.
+    pub start_line: usize,
+    /// Last line (the closing brace, or the last indented line).
+    pub end_line: usize,
+    /// What the unit declares, e.g. "fn `greedy_pack`" or "class `Cache`";
+    /// `None` for anonymous blocks.
+    pub label: Option<String>,
+}
+
+/// How a language delimits its units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Braces,
+    Python,
+    Yaml,
+}
+
+/// Finds enclosing units in one file.
+pub struct UnitFinder {
+    lines: Vec<String>,
+    masked: Vec<String>,
+    syntax: Option<Syntax>,
+    /// Brace blocks as 0-based (open line, close line), in open order.
+    blocks: Vec<(usize, usize)>,
+}
+
+impl UnitFinder {
+    /// Prepare `content` (written in `language`) for unit lookups.
+    pub fn new(content: &str, language: &str) -> Self {
+        let syntax = match language {
+            "rust" | "c" | "cpp" | "java" | "javascript" | "typescript" | "go" | "swift"
+            | "kotlin" | "css" | "hcl" | "protobuf" | "graphql" => Some(Syntax::Braces),
+            "python" => Some(Syntax::Python),
+            "yaml" => Some(Syntax::Yaml),
+            _ => None,
+        };
+        let masked = match syntax {
+            Some(_) => symbols::mask_code(content, language),
+            None => Vec::new(),
+        };
+        let blocks = match syntax {
+            Some(Syntax::Braces) => brace_blocks(&masked),
+            _ => Vec::new(),
+        };
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            masked,
+            syntax,
+            blocks,
+        }
+    }
+
+    /// The unit enclosing 1-based `line`, or `None` when the language is
+    /// not supported, the line is outside any unit, or the unit is longer
+    /// than [`MAX_UNIT_LINES`].
+    pub fn enclosing(&self, line: usize) -> Option<Unit> {
+        let idx = line.checked_sub(1).filter(|&i| i < self.masked.len())?;
+        let syntax = self.syntax?;
+        let (start, end, header) = match syntax {
+            Syntax::Braces => self.brace_unit(idx)?,
+            Syntax::Python => self.python_unit(idx)?,
+            Syntax::Yaml => self.yaml_unit(idx)?,
+        };
+        if end - start + 1 > MAX_UNIT_LINES {
+            return None;
+        }
+        Some(Unit {
+            start_line: start + 1,
+            end_line: end + 1,
+            label: unit_label(&header, syntax),
+        })
+    }
+
+    /// Innermost definition-like block around `idx`, else the outermost
+    /// block (a CSS rule, a Terraform resource). Returns the range and the
+    /// block's header text.
+    fn brace_unit(&self, idx: usize) -> Option<(usize, usize, String)> {
+        let mut outermost = None;
+        let mut innermost_def = None;
+        for &(open, close) in &self.blocks {
+            let start = self.header_start(open);
+            if start > idx || close < idx {
+                continue;
+            }
+            let header = self.masked[start..=open].join(" ");
+            if is_definition(&header) {
+                innermost_def = Some((start, close, header));
+            } else if outermost.is_none() {
+                outermost = Some((start, close, header));
+            }
+        }
+        let (start, end, header) = innermost_def.or(outermost)?;
+        Some((self.leading_attributes(start), end, header))
+    }
+
+    /// First line of the statement whose `{` is on line `open`: walk back
+    /// over a multi-line signature until a line ends a statement.
+    fn header_start(&self, open: usize) -> usize {
+        let mut start = open;
+        while start > 0 && open - start + 1 < MAX_HEADER_LINES {
+            let prev = self.masked[start - 1].trim();
+            if prev.is_empty() || prev.ends_with([';', '{', '}']) {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    /// Extend `start` upward over attributes, decorators and doc comments.
+    fn leading_attributes(&self, mut start: usize) -> usize {
+        while start > 0 {
+            let prev = self.lines[start - 1].trim_start();
+            let attached = ["#[", "@", "///", "/**", "* ", "*/"]
+                .iter()
+                .any(|p| prev.starts_with(p))
+                || prev == "*";
+            if !attached {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    /// Innermost `def` or `class` whose body contains `idx`.
+    fn python_unit(&self, idx: usize) -> Option<(usize, usize, String)> {
+        static DEF_RE: OnceLock<Regex> = OnceLock::new();
+        let def_re = DEF_RE.get_or_init(|| {
+            Regex::new(r"^\s*(?:async\s+def|def|class)\s").expect("def regex is valid")
+        });
+
+        let mut limit = indent_of(&self.masked[idx]).map_or(usize::MAX, |i| i + 1);
+        for i in (0..=idx).rev() {
+            let Some(indent) = indent_of(&self.masked[i]) else {
+                continue;
+            };
+            if indent >= limit {
+                continue;
+            }
+            if def_re.is_match(&self.masked[i]) {
+                let mut start = i;
+                while start > 0 && self.masked[start - 1].trim_start().starts_with('@') {
+                    start -= 1;
+                }
+                let header = self.masked[i].clone();
+                return Some((start, self.indented_end(i, indent), header));
+            }
+            limit = indent;
+        }
+        None
+    }
+
+    /// The mapping entry or list item that `idx` belongs to: its parent
+    /// key, or the line itself when it is at the top level.
+    fn yaml_unit(&self, idx: usize) -> Option<(usize, usize, String)> {
+        let indent = indent_of(&self.masked[idx])?;
+        let parent = (0..idx)
+            .rev()
+            .find(|&i| indent_of(&self.masked[i]).is_some_and(|p| p < indent))
+            .unwrap_or(idx);
+        let parent_indent = indent_of(&self.masked[parent])?;
+        let header = self.masked[parent].clone();
+        Some((parent, self.indented_end(parent, parent_indent), header))
+    }
+
+    /// Last non-blank line after `header` indented deeper than `indent`.
+    fn indented_end(&self, header: usize, indent: usize) -> usize {
+        let mut end = header;
+        for (i, line) in self.masked.iter().enumerate().skip(header + 1) {
+            match indent_of(line) {
+                Some(i_indent) if i_indent <= indent => break,
+                Some(_) => end = i,
+                None => {}
+            }
+        }
+        end
+    }
+}
+
+/// Match every `{` with its `}` in masked lines. Unclosed blocks are
+/// dropped.
+fn brace_blocks(masked: &[String]) -> Vec<(usize, usize)> {
+    let mut open: Vec<usize> = Vec::new();
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for (idx, line) in masked.iter().enumerate() {
+        for c in line.chars() {
+            match c {
+                '{' => open.push(idx),
+                '}' => {
+                    if let Some(start) = open.pop() {
+                        blocks.push((start, idx));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    // Blocks close inside-out; put them in open order so the outermost
+    // block containing a line comes first.
+    blocks.sort_by_key(|&(start, close)| (start, std::cmp::Reverse(close)));
+    blocks
+}
+
+/// Whether a block header declares something (a function, type, impl or
+/// module) rather than opening a control-flow or literal block.
+fn is_definition(header: &str) -> bool {
+    static KEYWORD_RE: OnceLock<Regex> = OnceLock::new();
+    static SIGNATURE_RE: OnceLock<Regex> = OnceLock::new();
+    let keyword_re = KEYWORD_RE.get_or_init(|| {
+        Regex::new(
+            r"\b(?:fn|impl|trait|struct|enum|union|mod|class|interface|func|function|fun|namespace|object|extension|protocol|record|message|service)\b|=>",
+        )
+        .expect("keyword regex is valid")
+    });
+    let signature_re = SIGNATURE_RE.get_or_init(|| {
+        Regex::new(r"^[\w\s<>\[\],.*&:?]*?\b[A-Za-z_]\w*\s*\([^=;]*\)[^=;]*\{")
+            .expect("signature regex is valid")
+    });
+
+    let header = header.trim();
+    let first_word = header
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or("");
+    const CONTROL: &[&str] = &[
+        "if", "else", "for", "while", "switch", "match", "loop", "do", "try", "catch", "finally",
+        "return", "let", "const", "var", "unsafe", "async",
+    ];
+    if CONTROL.contains(&first_word) && !keyword_re.is_match(header) {
+        return false;
+    }
+    keyword_re.is_match(header) || signature_re.is_match(header)
+}
+
+/// Name a unit from its header: "fn `plan`", "impl `Display for Budget`",
+/// "class `Cache`", a bare "`size`" for keyword-less signatures, or the
+/// key of a YAML entry.
+fn unit_label(header: &str, syntax: Syntax) -> Option<String> {
+    static IMPL_RE: OnceLock<Regex> = OnceLock::new();
+    static KEYWORD_RE: OnceLock<Regex> = OnceLock::new();
+    static ASSIGN_RE: OnceLock<Regex> = OnceLock::new();
+    static CALL_RE: OnceLock<Regex> = OnceLock::new();
+
+    if syntax == Syntax::Yaml {
+        let key = header
+            .trim()
+            .trim_start_matches("- ")
+            .split(':')
+            .next()?
+            .trim();
+        return (!key.is_empty()).then(|| format!("`{key}`"));
+    }
+
+    let impl_re = IMPL_RE.get_or_init(|| {
+        Regex::new(r"\bimpl\b\s*(?:<[^{]*?>\s*)?([^{]+?)\s*(?:\bwhere\b|\{|$)")
+            .expect("impl regex is valid")
+    });
+    if let Some(caps) = impl_re.captures(header) {
+        return Some(format!("impl `{}`", &caps[1]));
+    }
+    let keyword_re = KEYWORD_RE.get_or_init(|| {
+        Regex::new(
+            r"\b(fn|trait|struct|enum|union|mod|class|interface|func|function|fun|def|namespace|object|extension|protocol|record|message|service)\b\s*(?:\([^)]*\)\s*)?([A-Za-z_$][\w$]*)",
+        )
+        .expect("keyword regex is valid")
+    });
+    if let Some(caps) = keyword_re.captures(header) {
+        return Some(format!("{} `{}`", &caps[1], &caps[2]));
+    }
+    let assign_re = ASSIGN_RE.get_or_init(|| {
+        Regex::new(r"([A-Za-z_$][\w$]*)\s*=\s*(?:async\s*)?(?:function\b|\()")
+            .expect("assignment regex is valid")
+    });
+    let call_re =
+        CALL_RE.get_or_init(|| Regex::new(r"\b([A-Za-z_]\w*)\s*\(").expect("call regex is valid"));
+    assign_re
+        .captures(header)
+        .or_else(|| call_re.captures(header))
+        .map(|caps| format!("`{}`", &caps[1]))
+}
+
+/// Indentation width of a line, or `None` for a blank line.
+fn indent_of(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    (!trimmed.is_empty()).then(|| line.len() - trimmed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(content: &str, language: &str, line: usize) -> Option<(usize, usize)> {
+        UnitFinder::new(content, language)
+            .enclosing(line)
+            .map(|u| (u.start_line, u.end_line))
+    }
+
+    #[test]
+    fn rust_match_grows_to_enclosing_fn() {
+        let content = "\
+use std::fmt;
+
+impl Budget {
+    /// Plan the bundle.
+    #[inline]
+    pub fn plan(
+        &self,
+        limit: usize,
+    ) -> usize {
+        if limit > 0 {
+            return limit; // {
+        }
+        0
+    }
+}
+";
+        assert_eq!(unit(content, "rust", 11), Some((4, 14)));
+        assert_eq!(unit(content, "rust", 7), Some((4, 14)));
+        assert_eq!(unit(content, "rust", 3), Some((3, 15)));
+        assert_eq!(unit(content, "rust", 1), None);
+    }
+
+    #[test]
+    fn control_blocks_are_not_units() {
+        let content = "\
+function load(path) {
+  if (path) {
+    for (const p of path) {
+      read(p);
+    }
+  }
+}
+";
+        assert_eq!(unit(content, "javascript", 4), Some((1, 7)));
+        assert!(!is_definition("if (path) {"));
+        assert!(is_definition("public int size() {"));
+        assert!(is_definition("const f = (a) => {"));
+    }
+
+    #[test]
+    fn python_match_grows_to_innermost_def() {
+        let content = "\
+import os
+
+class Cache:
+    @property
+    def size(self):
+        total = 0
+
+        return total
+
+    def clear(self):
+        pass
+";
+        assert_eq!(unit(content, "python", 6), Some((4, 8)));
+        assert_eq!(unit(content, "python", 11), Some((10, 11)));
+        assert_eq!(unit(content, "python", 3), Some((3, 11)));
+        assert_eq!(unit(content, "python", 1), None);
+    }
+
+    #[test]
+    fn yaml_match_grows_to_parent_entry() {
+        let content = "\
+name: ci
+jobs:
+  build:
+    runs-on: ubuntu
+  test:
+    runs-on: macos
+";
+        assert_eq!(unit(content, "yaml", 4), Some((3, 4)));
+        assert_eq!(unit(content, "yaml", 3), Some((2, 6)));
+        assert_eq!(unit(content, "yaml", 1), Some((1, 1)));
+    }
+
+    #[test]
+    fn units_are_labelled_from_their_header() {
+        let label = |content: &str, language: &str, line: usize| {
+            UnitFinder::new(content, language)
+                .enclosing(line)
+                .and_then(|u| u.label)
+        };
+        let rust = "impl<T> Display for Budget<T> {\n    fn fmt(&self) {\n        x();\n    }\n}\n";
+        assert_eq!(label(rust, "rust", 3).as_deref(), Some("fn `fmt`"));
+        assert_eq!(
+            label(rust, "rust", 1).as_deref(),
+            Some("impl `Display for Budget<T>`")
+        );
+        let go = "func (b *Budget) Plan() int {\n\treturn 0\n}\n";
+        assert_eq!(label(go, "go", 2).as_deref(), Some("func `Plan`"));
+        let java = "public int size() {\n  return n;\n}\n";
+        assert_eq!(label(java, "java", 2).as_deref(), Some("`size`"));
+        let python = "class Cache:\n    def clear(self):\n        pass\n";
+        assert_eq!(label(python, "python", 3).as_deref(), Some("def `clear`"));
+        let yaml = "jobs:\n  - build: x\n    runs: y\n";
+        assert_eq!(label(yaml, "yaml", 3).as_deref(), Some("`build`"));
+        assert_eq!(label(".btn {\n  color: red;\n}\n", "css", 2), None);
+    }
+
+    #[test]
+    fn unsupported_or_oversized_units_fall_back() {
+        assert_eq!(unit("key = 1\n", "toml", 1), None);
+
+        let mut body = vec!["fn huge() {".to_string()];
+        body.extend((0..MAX_UNIT_LINES).map(|i| format!("    let x{i} = {i};")));
+        body.push("}".to_string());
+        assert_eq!(unit(&body.join("\n"), "rust", 5), None);
+    }
+}
@@ -1,8 +1,8 @@
 //! Shared utility functions used across multiple commands.
 //!
 //! Centralises helpers that were previously duplicated in `diff.rs` and
-//! `pack.rs`: language inference, CLI format mapping, and manifest path
-//! computation.
+//! `pack.rs`: language inference, test-file detection, CLI format mapping,
+//! and manifest path computation.
 
 use std::path::Path;
 
@@ -69,6 +69,31 @@ pub fn infer_language(path: &str) -> String {
     .to_string()
 }
 
+/// Whether a path looks like a test file.
+///
+/// Recognises test directories (`tests/`, `test/`, `__tests__/`, `spec/`)
+/// and the usual file naming conventions: `test_*.py`, `*_test.go`,
+/// `*_tests.rs`, `*.test.ts`, `*.spec.js`, `*Test.java`.
+pub fn is_test_path(path: &str) -> bool {
+    let mut parts: Vec<&str> = path.split(['/', '\\']).collect();
+    let filename = parts.pop().unwrap_or(path);
+    if parts
+        .iter()
+        .any(|d| matches!(*d, "tests" | "test" | "__tests__" | "spec"))
+    {
+        return true;
+    }
+
+    let stem = filename.split('.').next().unwrap_or(filename);
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_tests")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || filename.contains(".test.")
+        || filename.contains(".spec.")
+}
+
 // ---------------------------------------------------------------------------
 // Format mapping
 // ---------------------------------------------------------------------------
@@ -129,6 +154,19 @@ mod tests {
         assert_eq!(infer_language("data.bin"), "");
     }
 
+    #[test]
+    fn is_test_path_conventions() {
+        assert!(is_test_path("tests/cli_tests.rs"));
+        assert!(is_test_path("pkg/server_test.go"));
+        assert!(is_test_path("app/test_models.py"));
+        assert!(is_test_path("web/src/app.spec.ts"));
+        assert!(is_test_path("src/__tests__/button.jsx"));
+        assert!(is_test_path("src/main/java/FooTest.java"));
+        assert!(!is_test_path("src/ranker.rs"));
+        assert!(!is_test_path("src/testing.rs"));
+        assert!(!is_test_path("latest.py"));
+    }
+
     #[test]
     fn cli_format_mapping() {
         assert_eq!(
@@ -50,6 +50,20 @@ fn git(dir: &std::path::Path, args: &[&str]) {
     assert!(status.success(), "git {args:?} failed in {}", dir.display());
 }
 
+fn git_output(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("git command failed to start");
+    assert!(
+        output.status.success(),
+        "git {args:?} failed in {}",
+        dir.display()
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
 // -----------------------------------------------------------------------
 // General CLI tests
 // -----------------------------------------------------------------------
@@ -70,16 +84,6 @@ fn help_shows_all_commands() {
         .stdout(predicate::str::contains("explain"));
 }
 
-#[test]
-fn unimplemented_command_shows_error() {
-    // `trim` is still stubbed — verify it reports not-implemented.
-    cmd()
-        .arg("trim")
-        .assert()
-        .failure()
-        .stderr(predicate::str::contains("not yet implemented"));
-}
-
 // -----------------------------------------------------------------------
 // Init command tests
 // -----------------------------------------------------------------------
@@ -336,6 +340,31 @@ fn diff_budget_creates_manifest_sibling() {
     assert!(parsed["entries"].is_array());
 }
 
+#[test]
+fn diff_model_flag_is_recorded_in_manifest() {
+    let dir = setup_git_repo();
+    let out_file = dir.path().join("ctx.md");
+
+    cmd()
+        .args([
+            "diff",
+            "--model",
+            "claude-3-opus",
+            "--budget",
+            "5000",
+            "--root",
+        ])
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out_file)
+        .assert()
+        .success();
+
+    let content = std::fs::read_to_string(dir.path().join("ctx.manifest.json")).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(parsed["summary"]["model"], "claude-3-opus");
+}
+
 #[test]
 fn diff_out_prints_manifest_and_summary_to_stderr() {
     let dir = setup_git_repo();
@@ -397,6 +426,293 @@ fn diff_small_budget_still_includes_one_snippet() {
         .stdout(predicate::str::contains("hello"));
 }
 
+#[test]
+fn diff_include_related_adds_callers() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+
+    std::fs::write(
+        root.join("greet.rs"),
+        "pub fn greet() -> String {\n    \"hi\".into()\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("main.rs"),
+        "fn main() {\n    println!(\"{}\", greet());\n}\n",
+    )
+    .unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+
+    std::fs::write(
+        root.join("greet.rs"),
+        "pub fn greet() -> String {\n    \"hello\".into()\n}\n",
+    )
+    .unwrap();
+
+    cmd()
+        .args([
+            "diff",
+            "--root",
+            root.to_str().unwrap(),
+            "--include-related",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "caller of `greet` (changed in greet.rs)",
+        ))
+        .stdout(predicate::str::contains("greet());"));
+}
+
+#[test]
+fn diff_function_context_includes_enclosing_function() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+
+    let body = |last: &str| {
+        let mut lines = vec!["pub fn greedy_pack(budget: usize) -> usize {".to_string()];
+        lines.extend((1..=12).map(|i| format!("    let step{i} = {i};")));
+        lines.push(format!("    {last}"));
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    };
+    std::fs::write(root.join("pack.rs"), body("budget")).unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    std::fs::write(root.join("pack.rs"), body("budget + 1")).unwrap();
+
+    cmd()
+        .args([
+            "diff",
+            "--root",
+            root.to_str().unwrap(),
+            "--function-context",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("modified in fn `greedy_pack`"))
+        .stdout(predicate::str::contains(
+            "pub fn greedy_pack(budget: usize)",
+        ))
+        .stdout(predicate::str::contains("let step1 = 1;"));
+}
+
+#[test]
+fn diff_show_old_pairs_before_and_after() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    std::fs::write(root.join("keep.rs"), "fn keep() {\n    1\n}\n").unwrap();
+    std::fs::write(root.join("gone.rs"), "fn gone() {}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    std::fs::write(root.join("keep.rs"), "fn keep() {\n    2\n}\n").unwrap();
+    git(root, &["rm", "-q", "gone.rs"]);
+    git(root, &["commit", "-am", "change"]);
+
+    let output = cmd()
+        .args([
+            "diff",
+            "HEAD~1..HEAD",
+            "--root",
+            root.to_str().unwrap(),
+            "--show-old",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+
+    let before = stdout.find("*before: modified in diff*").unwrap();
+    let after = stdout.find("*after: modified in diff*").unwrap();
+    assert!(before < after);
+    assert!(stdout[before..after].contains("    1"));
+    assert!(stdout[after..].contains("    2"));
+    assert!(stdout.contains("*before: deleted*"));
+    assert!(stdout.contains("fn gone() {}"));
+}
+
+#[test]
+fn diff_base_diffs_from_merge_base_and_records_shas() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init", "-b", "main"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    std::fs::write(root.join("lib.rs"), "fn lib() {}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    let fork = git_output(root, &["rev-parse", "HEAD"]);
+
+    git(root, &["checkout", "-b", "feature"]);
+    std::fs::write(root.join("feature.rs"), "fn feature() {}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "feature"]);
+    let head = git_output(root, &["rev-parse", "HEAD"]);
+
+    // main moves on after the fork; its new commit must not show up.
+    git(root, &["checkout", "main"]);
+    std::fs::write(root.join("main_only.rs"), "fn main_only() {}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "main moves"]);
+    git(root, &["checkout", "feature"]);
+    std::fs::write(root.join("lib.rs"), "fn lib() { wip(); }\n").unwrap();
+
+    let out = tempdir().unwrap();
+    let out_path = out.path().join("out.md");
+    let run = |extra: &[&str]| {
+        cmd()
+            .args(["diff", "--base", "main", "--root", root.to_str().unwrap()])
+            .args(extra)
+            .args(["--out", out_path.to_str().unwrap(), "--quiet"])
+            .assert()
+            .success();
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(out.path().join("out.manifest.json")).unwrap(),
+        )
+        .unwrap();
+        manifest
+    };
+
+    let manifest = run(&[]);
+    assert_eq!(manifest["summary"]["base_sha"], fork.as_str());
+    assert_eq!(manifest["summary"]["head_sha"], head.as_str());
+    let paths: Vec<&str> = manifest["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["file_path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, vec!["feature.rs"]);
+
+    // --worktree adds the uncommitted edit on top.
+    let manifest = run(&["--worktree"]);
+    let mut paths: Vec<&str> = manifest["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["file_path"].as_str().unwrap())
+        .collect();
+    paths.sort();
+    assert_eq!(paths, vec!["feature.rs", "lib.rs"]);
+}
+
+#[test]
+fn diff_commits_adds_messages_and_groups_hunks() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    let write = |a: &str, b: &str| {
+        let filler = "\n".repeat(8);
+        std::fs::write(
+            root.join("m.rs"),
+            format!("fn a() {{\n    {a}\n}}\n{filler}fn b() {{\n    {b}\n}}\n"),
+        )
+        .unwrap();
+    };
+    write("1", "2");
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    write("10", "2");
+    git(
+        root,
+        &[
+            "commit",
+            "-am",
+            "Bump a",
+            "-m",
+            "It was too small.",
+            "-m",
+            "Fixes: #7",
+        ],
+    );
+    write("10", "20");
+    git(root, &["commit", "-am", "Bump b"]);
+
+    let output = cmd()
+        .args([
+            "diff",
+            "HEAD~2..HEAD",
+            "--root",
+            root.to_str().unwrap(),
+            "--commits",
+            "--group-by-commit",
+            "--context",
+            "1",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+
+    assert!(stdout.contains("2 commits, 2 snippets"));
+    let bump_a = stdout
+        .find("Bump a\n\nIt was too small.\n\nFixes: #7")
+        .unwrap();
+    let hunk_a = stdout.find("    10").unwrap();
+    let bump_b = stdout.find("Bump b").unwrap();
+    let hunk_b = stdout.find("    20").unwrap();
+    assert!(bump_a < hunk_a && hunk_a < bump_b && bump_b < hunk_b);
+
+    // Lines changed again after the range still belong to the range's
+    // commits, since blame runs at the range's end.
+    let sha_a = git_output(root, &["rev-parse", "--short=7", "HEAD~1"]);
+    let sha_b = git_output(root, &["rev-parse", "--short=7", "HEAD"]);
+    write("30", "40");
+    git(root, &["commit", "-am", "Bump both"]);
+    cmd()
+        .args([
+            "diff",
+            "HEAD~3..HEAD~1",
+            "--root",
+            root.to_str().unwrap(),
+            "--commits",
+            "--group-by-commit",
+            "--context",
+            "1",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "(commit {})",
+            sha_a.trim()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "(commit {})",
+            sha_b.trim()
+        )));
+}
+
+#[test]
+fn diff_commits_needs_a_range() {
+    let dir = setup_git_repo();
+    cmd()
+        .args(["diff", "--root", dir.path().to_str().unwrap(), "--commits"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("needs a revision range"));
+}
+
 // -----------------------------------------------------------------------
 // Pack command tests
 // -----------------------------------------------------------------------
@@ -450,6 +766,67 @@ fn pack_with_budget_limits_output() {
         .stdout(predicate::str::contains("hello"));
 }
 
+#[test]
+fn pack_chars_budget_ignores_bpe_merges() {
+    let dir = tempdir().unwrap();
+    let sections: Vec<serde_json::Value> = ["a.rs", "b.rs", "c.rs"]
+        .iter()
+        .map(|path| {
+            serde_json::json!({
+                "file_path": path,
+                "language": "rust",
+                "content": "y".repeat(20),
+                "reason": "test",
+            })
+        })
+        .collect();
+    let bundle_path = dir.path().join("bundle.json");
+    std::fs::write(
+        &bundle_path,
+        serde_json::json!({ "summary": "s", "sections": sections }).to_string(),
+    )
+    .unwrap();
+    // A vocabulary that merges runs of `x` (16 to a token) but knows no
+    // `y`: each section is 20 tokens, and 400 `x`s would be only 25.
+    let vocab_dir = tempdir().unwrap();
+    let vocab: String = (1..=4)
+        .map(|i| {
+            let token = "x".repeat(1 << i);
+            format!("{} {}\n", base64_encode(token.as_bytes()), i - 1)
+        })
+        .collect();
+    std::fs::write(vocab_dir.path().join("cl100k_base.tiktoken"), vocab).unwrap();
+
+    cmd()
+        .env("CONTEXTSMITH_TOKENIZER_DIR", vocab_dir.path())
+        .args(["pack", bundle_path.to_str().unwrap(), "--model", "gpt-4"])
+        .args(["--chars", "400", "--out"])
+        .arg(dir.path().join("out.md"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("3 of 3 sections included"));
+}
+
+/// Standard base64 (with padding), as used by tiktoken vocabularies.
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
 #[test]
 fn pack_with_output_file_creates_manifest() {
     let dir = setup_git_repo();
@@ -476,6 +853,62 @@ fn pack_with_output_file_creates_manifest() {
     );
 }
 
+#[test]
+fn pack_model_alone_derives_budget_from_registry() {
+    let dir = setup_git_repo();
+    let bundle_path = create_json_bundle(&dir);
+    let out_path = dir.path().join("packed.md");
+
+    cmd()
+        .args(["pack", bundle_path.to_str().unwrap(), "--model", "gpt-4"])
+        .arg("--out")
+        .arg(&out_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(budget: 7168)"));
+
+    let content = std::fs::read_to_string(dir.path().join("packed.manifest.json")).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(parsed["summary"]["budget"], 8192);
+    assert_eq!(parsed["summary"]["reserve_tokens"], 1024);
+}
+
+#[test]
+fn collect_model_from_config_table_sets_budget() {
+    let dir = setup_git_repo();
+    let config_path = dir.path().join("contextsmith.toml");
+    std::fs::write(
+        &config_path,
+        "[models.local-llm]\ncontext_window = 4000\noutput_reserve = 1000\n",
+    )
+    .unwrap();
+    let out_path = dir.path().join("collected.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "hello.rs",
+            "--model",
+            "local-llm",
+            "--root",
+        ])
+        .arg(dir.path())
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--out")
+        .arg(&out_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(budget: 3000)"));
+
+    let content = std::fs::read_to_string(dir.path().join("collected.manifest.json")).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(parsed["summary"]["model"], "local-llm");
+    assert_eq!(parsed["summary"]["budget"], 4000);
+    assert_eq!(parsed["summary"]["reserve_tokens"], 1000);
+}
+
 #[test]
 fn pack_with_output_prints_manifest_and_summary_to_stderr() {
     let dir = setup_git_repo();
@@ -540,56 +973,282 @@ fn pack_without_bundle_reports_validation_field() {
 }
 
 // -----------------------------------------------------------------------
-// Explain command tests
+// Trim command tests
 // -----------------------------------------------------------------------
 
-/// Helper: create a manifest by running diff --out with --budget.
-fn create_manifest(dir: &tempfile::TempDir) -> std::path::PathBuf {
-    let out_path = dir.path().join("ctx.md");
+/// Helper: write a markdown bundle with one long section.
+fn create_long_markdown_bundle(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let body: String = (1..=200)
+        .map(|i| format!("    let value_{i} = compute({i});\n"))
+        .collect();
+    std::fs::write(
+        dir.path().join("long.rs"),
+        format!("fn main() {{\n{body}}}\n"),
+    )
+    .unwrap();
+    let bundle_path = dir.path().join("long.md");
     cmd()
         .args([
-            "diff",
+            "collect",
+            "--files",
+            "long.rs",
             "--root",
             dir.path().to_str().unwrap(),
-            "--budget",
-            "5000",
             "--out",
-            out_path.to_str().unwrap(),
+            bundle_path.to_str().unwrap(),
         ])
         .assert()
         .success();
-    dir.path().join("ctx.manifest.json")
+    bundle_path
 }
 
 #[test]
-fn explain_reads_manifest() {
+fn trim_markdown_bundle_to_budget() {
     let dir = setup_git_repo();
-    let manifest_path = create_manifest(&dir);
+    let bundle_path = create_long_markdown_bundle(&dir);
 
-    cmd()
-        .args(["explain", manifest_path.to_str().unwrap()])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("summary:"))
-        .stdout(predicate::str::contains("included"));
+    let output = cmd()
+        .args([
+            "trim",
+            bundle_path.to_str().unwrap(),
+            "--budget",
+            "100",
+            "--stdout",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Context Bundle"));
+    assert!(stdout.contains("## `long.rs`"));
+    assert!(stdout.contains("let value_1 = compute(1);"));
+    assert!(!stdout.contains("let value_200 = compute(200);"));
+    assert!(stdout.contains("... [trimmed"));
 }
 
 #[test]
-fn explain_top_limits_entries() {
+fn trim_json_bundle_keeps_format_and_writes_manifest() {
     let dir = setup_git_repo();
-    let manifest_path = create_manifest(&dir);
+    let bundle_path = create_json_bundle(&dir);
+    let out_path = dir.path().join("trimmed.json");
 
     cmd()
-        .args(["explain", manifest_path.to_str().unwrap(), "--top", "1"])
+        .args([
+            "trim",
+            bundle_path.to_str().unwrap(),
+            "--budget",
+            "5000",
+            "--keep-manifest",
+            "--out",
+            out_path.to_str().unwrap(),
+        ])
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello.rs"));
+        .stderr(predicate::str::contains("manifest written to"))
+        .stderr(predicate::str::contains("trim:"));
+
+    let content = std::fs::read_to_string(&out_path).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+    let sections = parsed["sections"].as_array().unwrap();
+    assert_eq!(sections[0]["file_path"], "TABLE OF CONTENTS");
+    assert!(sections.iter().any(|s| s["file_path"] == "hello.rs"));
+    assert!(dir.path().join("trimmed.manifest.json").exists());
 }
 
 #[test]
-fn explain_show_weights() {
+fn trim_keep_head_preserves_first_lines() {
     let dir = setup_git_repo();
-    let manifest_path = create_manifest(&dir);
+    let bundle_path = create_long_markdown_bundle(&dir);
+
+    cmd()
+        .args([
+            "trim",
+            bundle_path.to_str().unwrap(),
+            "--budget",
+            "1",
+            "--keep-head",
+            "3",
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("let value_2 = compute(2);"))
+        .stdout(predicate::str::contains("let value_3 = compute(3);").not());
+}
+
+#[test]
+fn trim_without_budget_reports_validation_field() {
+    let dir = setup_git_repo();
+    let bundle_path = create_json_bundle(&dir);
+
+    cmd()
+        .args(["trim", bundle_path.to_str().unwrap(), "--stdout"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("validation error on 'budget'"));
+}
+
+#[test]
+fn trim_known_model_supplies_budget() {
+    let dir = setup_git_repo();
+    let bundle_path = create_json_bundle(&dir);
+
+    cmd()
+        .args([
+            "trim",
+            bundle_path.to_str().unwrap(),
+            "--model",
+            "claude-sonnet",
+        ])
+        .arg("--stdout")
+        .assert()
+        .success();
+}
+
+// -----------------------------------------------------------------------
+// Map command tests
+// -----------------------------------------------------------------------
+
+/// Helper: create a small Rust project with one internal import.
+fn create_map_project(dir: &tempfile::TempDir) {
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(
+        root.join("src/main.rs"),
+        "mod util;\n\nfn main() {\n    util::greet();\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("src/util.rs"),
+        "pub struct Greeter;\n\npub fn greet() {\n    println!(\"hi\");\n}\n",
+    )
+    .unwrap();
+}
+
+#[test]
+fn map_prints_token_annotated_tree() {
+    let dir = tempdir().unwrap();
+    create_map_project(&dir);
+
+    cmd()
+        .args(["map", "--root"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("PROJECT TREE"))
+        .stdout(predicate::str::contains("src/ (~"))
+        .stdout(predicate::str::contains("└── util.rs (~"));
+}
+
+#[test]
+fn map_symbols_and_graph_sections() {
+    let dir = tempdir().unwrap();
+    create_map_project(&dir);
+
+    cmd()
+        .args(["map", "--symbols", "--graph", "--root"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("symbol outline"))
+        .stdout(predicate::str::contains("struct Greeter (line 1)"))
+        .stdout(predicate::str::contains("fn greet (line 3)"))
+        .stdout(predicate::str::contains("DEPENDENCY GRAPH"))
+        .stdout(predicate::str::contains("src/main.rs\n  -> src/util.rs"));
+}
+
+#[test]
+fn map_json_format_is_valid_bundle() {
+    let dir = tempdir().unwrap();
+    create_map_project(&dir);
+
+    let output = cmd()
+        .args(["map", "--full", "--format", "json", "--root"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let sections = parsed["sections"].as_array().unwrap();
+    assert_eq!(sections[0]["file_path"], "PROJECT TREE");
+    assert!(sections
+        .iter()
+        .any(|s| s["file_path"] == "src/util.rs" && s["language"] == "rust"));
+}
+
+#[test]
+fn map_text_writes_plain_output_file() {
+    let dir = tempdir().unwrap();
+    create_map_project(&dir);
+    let out = dir.path().join("map.txt");
+
+    cmd()
+        .args(["map", "--text", "--root"])
+        .arg(dir.path())
+        .arg("--out")
+        .arg(&out)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("map:"));
+
+    let content = std::fs::read_to_string(&out).unwrap();
+    assert!(content.contains("--- PROJECT TREE ---"));
+    assert!(!content.contains("```"));
+}
+
+// -----------------------------------------------------------------------
+// Explain command tests
+// -----------------------------------------------------------------------
+
+/// Helper: create a manifest by running diff --out with --budget.
+fn create_manifest(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let out_path = dir.path().join("ctx.md");
+    cmd()
+        .args([
+            "diff",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--budget",
+            "5000",
+            "--out",
+            out_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    dir.path().join("ctx.manifest.json")
+}
+
+#[test]
+fn explain_reads_manifest() {
+    let dir = setup_git_repo();
+    let manifest_path = create_manifest(&dir);
+
+    cmd()
+        .args(["explain", manifest_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("summary:"))
+        .stdout(predicate::str::contains("included"));
+}
+
+#[test]
+fn explain_top_limits_entries() {
+    let dir = setup_git_repo();
+    let manifest_path = create_manifest(&dir);
+
+    cmd()
+        .args(["explain", manifest_path.to_str().unwrap(), "--top", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello.rs"));
+}
+
+#[test]
+fn explain_show_weights() {
+    let dir = setup_git_repo();
+    let manifest_path = create_manifest(&dir);
 
     cmd()
         .args(["explain", manifest_path.to_str().unwrap(), "--show-weights"])
@@ -644,7 +1303,7 @@ fn collect_no_mode_shows_error() {
         .assert()
         .failure()
         .stderr(predicate::str::contains(
-            "<query>, --files, --grep, or --symbol",
+            "<query>, --files, --grep, --symbol, or --scope",
         ));
 }
 
@@ -721,6 +1380,79 @@ fn collect_grep_finds_matches() {
         .stdout(predicate::str::contains("println"));
 }
 
+#[test]
+fn collect_ranks_changed_files_first_and_records_scores() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    std::fs::write(root.join("a.rs"), "fn a() { needle(); }\n").unwrap();
+    std::fs::write(root.join("b.rs"), "fn b() { needle(); }\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    // Uncommitted change: b.rs should outrank a.rs on the diff signal.
+    std::fs::write(root.join("b.rs"), "fn b() { needle(1); }\n").unwrap();
+
+    let out_path = root.join("out.md");
+    cmd()
+        .args([
+            "collect",
+            "needle",
+            "--root",
+            root.to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(root.join("out.manifest.json")).unwrap())
+            .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries[0]["file_path"], "b.rs");
+    assert_eq!(entries[1]["file_path"], "a.rs");
+    assert!(entries[0]["score"].as_f64().unwrap() > entries[1]["score"].as_f64().unwrap());
+    assert_eq!(entries[0]["start_line"], 1);
+}
+
+#[test]
+fn collect_bm25_prefers_rare_query_terms() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    for name in ["a.rs", "b.rs", "c.rs"] {
+        std::fs::write(root.join(name), "fn load() { let config = 1; }\n").unwrap();
+    }
+    // `budget` appears in one file only, so it carries more weight than `config`.
+    std::fs::write(root.join("z.rs"), "fn plan() { let budget = 2; }\n").unwrap();
+
+    let out_path = root.join("out.md");
+    cmd()
+        .args([
+            "collect",
+            "--grep",
+            "config|budget",
+            "--rank",
+            "bm25",
+            "--root",
+            root.to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(root.join("out.manifest.json")).unwrap())
+            .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0]["file_path"], "z.rs");
+}
+
 #[test]
 fn collect_grep_no_matches_shows_message() {
     let dir = setup_git_repo();
@@ -784,6 +1516,44 @@ fn collect_files_output_creates_manifest() {
     );
 }
 
+#[test]
+fn collect_uses_config_default_model() {
+    let dir = setup_git_repo();
+    let config_path = dir.path().join("contextsmith.toml");
+    std::fs::write(&config_path, "default_model = \"claude\"\n").unwrap();
+    let out_path = dir.path().join("collected.md");
+
+    cmd()
+        .args(["collect", "--files", "hello.rs", "--root"])
+        .arg(dir.path())
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--out")
+        .arg(&out_path)
+        .assert()
+        .success();
+
+    let content = std::fs::read_to_string(dir.path().join("collected.manifest.json")).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(parsed["summary"]["model"], "claude");
+}
+
+#[test]
+fn empty_default_model_in_config_is_rejected() {
+    let dir = setup_git_repo();
+    let config_path = dir.path().join("contextsmith.toml");
+    std::fs::write(&config_path, "default_model = \"\"\n").unwrap();
+
+    cmd()
+        .args(["collect", "--files", "hello.rs", "--root"])
+        .arg(dir.path())
+        .arg("--config")
+        .arg(&config_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("default_model"));
+}
+
 #[test]
 fn collect_files_output_prints_manifest_and_summary_to_stderr() {
     let dir = setup_git_repo();
@@ -891,7 +1661,7 @@ fn collect_grep_with_lang_filter() {
 }
 
 #[test]
-fn collect_positional_query_acts_as_grep() {
+fn collect_positional_query_matches_terms() {
     let dir = setup_git_repo();
     cmd()
         .args([
@@ -907,9 +1677,9 @@ fn collect_positional_query_acts_as_grep() {
 }
 
 #[test]
-fn collect_explicit_grep_overrides_query() {
+fn collect_query_and_grep_both_run() {
     let dir = setup_git_repo();
-    // Positional query is "nonexistent", but --grep "hello" should take precedence.
+    // The query finds nothing, but --grep "hello" still contributes sections.
     cmd()
         .args([
             "collect",
@@ -926,72 +1696,540 @@ fn collect_explicit_grep_overrides_query() {
 }
 
 #[test]
-fn collect_warns_when_ignored_flags_are_used() {
-    let dir = setup_git_repo();
+fn collect_query_ranks_files_by_term_coverage() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    std::fs::write(root.join("a.rs"), "fn backoff() {}\n").unwrap();
+    std::fs::write(
+        root.join("b.rs"),
+        "fn retry_backoff(client: HttpClient) {\n    // retries\n}\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("c.rs"), "fn unrelated() {}\n").unwrap();
+
+    let out = tempdir().unwrap();
+    let out_path = out.path().join("out.md");
     cmd()
         .args([
             "collect",
-            "--grep",
-            "hello",
-            "--scope",
-            "src",
-            "--rank",
-            "hybrid",
+            "how does the HTTP client handle retries with backoff?",
             "--root",
-            dir.path().to_str().unwrap(),
-            "--stdout",
+            root.to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
         ])
         .assert()
-        .success()
-        .stderr(predicate::str::contains(
-            "warn: collect currently ignores --scope, --rank",
-        ));
+        .success();
+
+    let bundle = std::fs::read_to_string(&out_path).unwrap();
+    assert!(bundle.contains("(5 terms: http, client, handle, retri, backoff)"));
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(out.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["file_path"], "b.rs");
+    assert_eq!(entries[1]["file_path"], "a.rs");
+    assert_eq!(
+        entries[0]["reason"],
+        "query terms http, client, retri, backoff"
+    );
 }
 
 #[test]
-fn collect_quiet_suppresses_ignored_flag_warning() {
-    let dir = setup_git_repo();
+fn collect_query_finds_plurals_written_only_as_plurals() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    std::fs::write(root.join("a.rs"), "// gives up after three retries\n").unwrap();
+    std::fs::write(root.join("b.rs"), "fn unrelated() {}\n").unwrap();
+
+    for query in ["retries", "retried"] {
+        cmd()
+            .args([
+                "collect",
+                query,
+                "--root",
+                root.to_str().unwrap(),
+                "--stdout",
+            ])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("three retries"))
+            .stdout(predicate::str::contains("unrelated").not());
+    }
+}
+
+#[test]
+fn collect_query_does_not_match_inside_unrelated_words() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    std::fs::write(root.join("a.rs"), "struct Foo;\nfn destroy() {}\n").unwrap();
+    std::fs::write(root.join("b.rs"), "fn name() -> String { x.to_string() }\n").unwrap();
+
     cmd()
         .args([
             "collect",
-            "--grep",
-            "hello",
-            "--scope",
-            "src",
+            "string",
             "--root",
-            dir.path().to_str().unwrap(),
+            root.to_str().unwrap(),
             "--stdout",
-            "--quiet",
         ])
         .assert()
         .success()
-        .stderr(predicate::str::is_empty());
+        .stdout(predicate::str::contains("1 term: string"))
+        .stdout(predicate::str::contains("to_string"))
+        .stdout(predicate::str::contains("struct Foo").not())
+        .stdout(predicate::str::contains("destroy").not());
 }
 
-// -----------------------------------------------------------------------
-// Stats command tests
-// -----------------------------------------------------------------------
-
 #[test]
-fn stats_repo_scan_shows_file_count() {
+fn collect_query_of_only_stopwords_fails() {
     let dir = setup_git_repo();
     cmd()
-        .args(["stats", "--root", dir.path().to_str().unwrap()])
+        .args([
+            "collect",
+            "how is the",
+            "--root",
+            dir.path().to_str().unwrap(),
+        ])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("files:"));
+        .failure()
+        .stderr(predicate::str::contains("no searchable terms"));
 }
 
 #[test]
-fn stats_repo_scan_with_tokens() {
-    let dir = setup_git_repo();
+fn collect_context_unit_returns_enclosing_function() {
+    let dir = tempdir().unwrap();
+    let mut lines = vec!["fn first() {}".to_string(), String::new()];
+    lines.push("pub fn plan(limit: usize) -> usize {".to_string());
+    lines.extend((1..=10).map(|i| format!("    let step{i} = {i};")));
+    lines.push("    needle(limit)".to_string());
+    lines.push("}".to_string());
+    std::fs::write(dir.path().join("a.rs"), lines.join("\n") + "\n").unwrap();
+
     cmd()
-        .args(["stats", "--root", dir.path().to_str().unwrap(), "--tokens"])
-        .assert()
+        .args([
+            "collect",
+            "--grep",
+            "needle",
+            "--context",
+            "unit",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "pub fn plan(limit: usize) -> usize {",
+        ))
+        .stdout(predicate::str::contains("let step1 = 1;"))
+        .stdout(predicate::str::contains("fn first()").not());
+}
+
+#[test]
+fn collect_rejects_bad_context() {
+    let dir = setup_git_repo();
+    cmd()
+        .args([
+            "collect",
+            "--grep",
+            "hello",
+            "--context",
+            "function",
+            "--root",
+            dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected a line count or 'unit'"));
+}
+
+#[test]
+fn collect_max_snippets_caps_windows_per_file() {
+    let dir = tempdir().unwrap();
+    // Three separate hit clusters in a.rs: one dense, two sparse.
+    let mut lines: Vec<String> = (1..=60).map(|i| format!("filler {i}")).collect();
+    for i in [5, 30, 31, 32, 55] {
+        lines[i - 1] = format!("needle {i}");
+    }
+    std::fs::write(dir.path().join("a.rs"), lines.join("\n")).unwrap();
+    std::fs::write(dir.path().join("b.rs"), "needle in b\n").unwrap();
+    let out_path = dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "needle",
+            "--max-snippets",
+            "1",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(output.contains("needle 31"));
+    assert!(!output.contains("needle 5\n"));
+    assert!(output.contains("needle in b"));
+
+    let manifest_path = dir.path().join("out.manifest.json");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    assert_eq!(manifest["entries"].as_array().unwrap().len(), 2);
+    assert_eq!(manifest["summary"]["dropped_windows"]["a.rs"], 2);
+    assert!(manifest["summary"]["dropped_windows"].get("b.rs").is_none());
+
+    cmd()
+        .args(["explain", manifest_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dropped windows: a.rs (2)"));
+}
+
+/// A small Rust crate with two command modules that both mention `budget`.
+fn setup_scoped_crate() -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src/commands")).unwrap();
+    std::fs::write(
+        root.join("src/lib.rs"),
+        "pub mod commands;
+",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("src/commands/mod.rs"),
+        "pub mod pack;
+pub mod trim;
+",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("src/commands/pack.rs"),
+        "pub fn pack_budget() -> usize {\n    1\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("src/commands/trim.rs"),
+        "pub fn trim_budget() -> usize {\n    2\n}\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn collect_scope_limits_grep_to_a_module() {
+    let dir = setup_scoped_crate();
+    cmd()
+        .args([
+            "collect",
+            "budget",
+            "--scope",
+            "crate::commands::pack",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pack_budget"))
+        .stdout(predicate::str::contains("trim_budget").not());
+}
+
+#[test]
+fn collect_scope_directory_alone_collects_its_files() {
+    let dir = setup_scoped_crate();
+    cmd()
+        .args([
+            "collect",
+            "--scope",
+            "src/commands/",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pack_budget"))
+        .stdout(predicate::str::contains("trim_budget"))
+        .stdout(predicate::str::contains("pub mod commands").not());
+}
+
+#[test]
+fn collect_scope_filters_explicit_files_and_symbols() {
+    let dir = setup_scoped_crate();
+    let root = dir.path().to_str().unwrap();
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "src/commands/pack.rs",
+            "--files",
+            "src/lib.rs",
+            "--scope",
+            "src/commands",
+            "--root",
+            root,
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pack_budget"))
+        .stdout(predicate::str::contains("pub mod commands").not())
+        .stderr(predicate::str::contains(
+            "skipping files outside --scope: src/lib.rs",
+        ));
+
+    cmd()
+        .args([
+            "collect",
+            "--symbol",
+            "trim_budget",
+            "--scope",
+            "src/commands/pack.rs",
+            "--root",
+            root,
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No matching content found."));
+}
+
+/// A file with one numbered line per line number, 1 through `n`.
+fn write_numbered_file(path: &std::path::Path, n: usize) {
+    let content: String = (1..=n).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(path, content).unwrap();
+}
+
+#[test]
+fn collect_spans_become_sections_with_real_lines() {
+    let dir = tempdir().unwrap();
+    write_numbered_file(&dir.path().join("a.rs"), 200);
+    let out_path = dir.path().join("out.md");
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "a.rs",
+            "--span",
+            "10:12",
+            "--span",
+            "120-121",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(output.contains("line 10\nline 11\nline 12"));
+    assert!(output.contains("line 121"));
+    assert!(!output.contains("line 13\n"));
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let mut ranges: Vec<(u64, u64)> = manifest["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            (
+                e["start_line"].as_u64().unwrap(),
+                e["end_line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    ranges.sort();
+    assert_eq!(ranges, vec![(10, 12), (120, 121)]);
+}
+
+#[test]
+fn collect_path_span_needs_no_files_flag() {
+    let dir = tempdir().unwrap();
+    write_numbered_file(&dir.path().join("a.rs"), 20);
+    write_numbered_file(&dir.path().join("b.rs"), 20);
+    cmd()
+        .args([
+            "collect",
+            "--span",
+            "b.rs:3-4",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("line 3\nline 4"))
+        .stdout(predicate::str::contains("line 5").not());
+}
+
+#[test]
+fn collect_span_errors() {
+    let dir = tempdir().unwrap();
+    write_numbered_file(&dir.path().join("a.rs"), 20);
+    let root = dir.path().to_str().unwrap();
+    cmd()
+        .args(["collect", "--span", "10:50", "--root", root, "--stdout"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("needs --files"));
+    cmd()
+        .args([
+            "collect", "--files", "a.rs", "--span", "30:40", "--root", root, "--stdout",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "starts past the end of the file (20 lines)",
+        ));
+}
+
+#[test]
+fn collect_unknown_scope_errors() {
+    let dir = setup_scoped_crate();
+    cmd()
+        .args([
+            "collect",
+            "budget",
+            "--scope",
+            "crate::missing",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'crate::missing' matches no file, directory or module",
+        ));
+}
+
+#[test]
+fn collect_rank_strategy_is_recorded_in_manifest() {
+    let dir = setup_git_repo();
+    let out_path = dir.path().join("out.md");
+    cmd()
+        .args([
+            "collect",
+            "println",
+            "--rank",
+            "path-order",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["summary"]["ranking_strategy"], "path-order");
+}
+
+#[test]
+fn collect_unknown_rank_strategy_errors() {
+    let dir = setup_git_repo();
+    cmd()
+        .args([
+            "collect",
+            "println",
+            "--rank",
+            "hybrid",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown strategy 'hybrid'"));
+}
+
+#[test]
+fn config_ranking_strategy_applies_to_diff() {
+    let dir = setup_git_repo();
+    let config_path = dir.path().join("contextsmith.toml");
+    std::fs::write(&config_path, "[ranking]\nstrategy = \"diff-first\"\n").unwrap();
+    let out_path = dir.path().join("ctx.md");
+    cmd()
+        .args([
+            "--config",
+            config_path.to_str().unwrap(),
+            "diff",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("ctx.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["summary"]["ranking_strategy"], "diff-first");
+}
+
+// -----------------------------------------------------------------------
+// Stats command tests
+// -----------------------------------------------------------------------
+
+#[test]
+fn stats_repo_scan_shows_file_count() {
+    let dir = setup_git_repo();
+    cmd()
+        .args(["stats", "--root", dir.path().to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("files:"));
+}
+
+#[test]
+fn stats_repo_scan_with_tokens() {
+    let dir = setup_git_repo();
+    cmd()
+        .args(["stats", "--root", dir.path().to_str().unwrap(), "--tokens"])
+        .assert()
         .success()
         .stdout(predicate::str::contains("total tokens:"));
 }
 
+#[test]
+fn stats_uses_bpe_vocabulary_when_installed() {
+    let dir = tempdir().unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "aaaaaaaa").unwrap();
+    // A vocabulary with only the byte `a`: every character is its own token,
+    // where the character heuristic would estimate 2.
+    let vocab_dir = tempdir().unwrap();
+    std::fs::write(vocab_dir.path().join("cl100k_base.tiktoken"), "YQ== 0\n").unwrap();
+
+    cmd()
+        .env("CONTEXTSMITH_TOKENIZER_DIR", vocab_dir.path())
+        .args(["stats", "--tokens", "--root"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("total tokens:    ~8"));
+}
+
 #[test]
 fn stats_repo_scan_by_lang() {
     let dir = setup_git_repo();
@@ -1025,3 +2263,479 @@ fn stats_bundle_mode_top_files() {
         .success()
         .stdout(predicate::str::contains("Top 5 files"));
 }
+
+#[test]
+fn collect_writes_index_cache_reported_by_cache_stats() {
+    let dir = setup_git_repo();
+    let root = dir.path().to_str().unwrap();
+    cmd()
+        .args(["collect", "println", "--root", root, "--stdout"])
+        .assert()
+        .success();
+    assert!(dir.path().join(".contextsmith/cache/index.json").exists());
+
+    cmd()
+        .args(["cache", "stats", "--root", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("entries:         1"))
+        .stdout(predicate::str::contains("stale entries:   0"));
+
+    // Editing the file marks its entry stale.
+    std::fs::write(dir.path().join("hello.rs"), "fn main() {}\n").unwrap();
+    cmd()
+        .args(["cache", "stats", "--root", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stale entries:   1"));
+
+    cmd()
+        .args(["cache", "clear", "--root", root])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("removed 1 entry"));
+    assert!(!dir.path().join(".contextsmith/cache/index.json").exists());
+}
+
+#[test]
+fn cached_grep_sees_edited_files() {
+    let dir = setup_git_repo();
+    let root = dir.path().to_str().unwrap();
+    cmd()
+        .args(["collect", "println", "--root", root, "--stdout"])
+        .assert()
+        .success();
+
+    // The cached terms of hello.rs lack `goodbye` until the file changes.
+    std::fs::write(
+        dir.path().join("hello.rs"),
+        "fn main() {\n    goodbye();\n}\n",
+    )
+    .unwrap();
+    cmd()
+        .args(["collect", "goodbye", "--root", root, "--stdout"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("goodbye();"));
+}
+
+#[test]
+fn no_cache_and_cache_dir_flags() {
+    let dir = setup_git_repo();
+    let root = dir.path().to_str().unwrap();
+    cmd()
+        .args(["--no-cache", "stats", "--tokens", "--root", root])
+        .assert()
+        .success();
+    assert!(!dir.path().join(".contextsmith").exists());
+
+    let cache_dir = tempdir().unwrap();
+    cmd()
+        .args(["stats", "--tokens", "--root", root, "--cache-dir"])
+        .arg(cache_dir.path())
+        .assert()
+        .success();
+    assert!(cache_dir.path().join("index.json").exists());
+    assert!(!dir.path().join(".contextsmith").exists());
+}
+
+fn setup_defs_crate() -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src")).unwrap();
+    std::fs::write(
+        dir.path().join("src/config.rs"),
+        "pub struct Config {\n    pub budget: usize,\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("src/pack.rs"),
+        "use crate::config::Config;\n\npub fn pack_items(cfg: &Config) -> usize {\n    cfg.budget\n}\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn collect_include_defs_adds_referenced_definitions() {
+    let dir = setup_defs_crate();
+    let out_path = dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--grep",
+            "fn pack_items",
+            "--include-defs",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(output.contains("pub struct Config"));
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["file_path"], "src/pack.rs");
+    assert_eq!(entries[1]["file_path"], "src/config.rs");
+    assert_eq!(
+        entries[1]["reason"],
+        "definition of `Config` referenced from src/pack.rs:1"
+    );
+}
+
+#[test]
+fn collect_include_defs_only_uses_leftover_budget() {
+    let dir = setup_defs_crate();
+    let out_path = dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "src/pack.rs",
+            "--include-defs",
+            "--budget",
+            "5",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["included"], true);
+    assert_eq!(entries[1]["file_path"], "src/config.rs");
+    assert_eq!(entries[1]["included"], false);
+}
+
+#[test]
+fn collect_include_refs_adds_call_sites() {
+    let dir = setup_defs_crate();
+    std::fs::write(
+        dir.path().join("src/main.rs"),
+        "fn main() {\n    // pack_items is documented elsewhere\n    let n = pack_items(&cfg());\n    println!(\"{n}\");\n}\n",
+    )
+    .unwrap();
+    let out_path = dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--symbol",
+            "pack_items",
+            "--include-refs",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(output.contains("symbol 'pack_items': 1 definition in 1 file, 1 reference in 1 file"));
+    let manifest_path = dir.path().join("out.manifest.json");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["reason"], "definition of 'pack_items'");
+    assert_eq!(entries[1]["file_path"], "src/main.rs");
+    assert_eq!(entries[1]["reason"], "reference to 'pack_items'");
+    assert_eq!(manifest["summary"]["references"]["src/main.rs"], 1);
+
+    cmd()
+        .args(["explain", manifest_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("references: src/main.rs (1)"));
+}
+
+#[test]
+fn collect_include_refs_needs_symbol() {
+    let dir = setup_defs_crate();
+    cmd()
+        .args([
+            "collect",
+            "--grep",
+            "Config",
+            "--include-refs",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--stdout",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--include-refs needs --symbol"));
+}
+
+#[test]
+fn collect_include_imports_adds_header_before_first_section_per_file() {
+    let dir = tempdir().unwrap();
+    let mut lines = vec![
+        "use std::fmt;".to_string(),
+        "use crate::config::Config;".to_string(),
+    ];
+    lines.extend((1..=20).map(|i| format!("// filler {i}")));
+    lines.push("fn render(cfg: &Config) -> usize { cfg.needle }".to_string());
+    lines.extend((1..=20).map(|i| format!("// more {i}")));
+    lines.push("fn again() { needle() }".to_string());
+    std::fs::write(dir.path().join("lib.rs"), lines.join("\n")).unwrap();
+    let out_path = dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "needle",
+            "--include-imports",
+            "--root",
+            dir.path().to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert_eq!(output.matches("use crate::config::Config;").count(), 1);
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    let imports: Vec<&serde_json::Value> = entries
+        .iter()
+        .filter(|e| e["reason"] == "imports")
+        .collect();
+    assert_eq!(imports.len(), 1);
+    assert_eq!(imports[0]["start_line"], 1);
+    assert_eq!(imports[0]["end_line"], 2);
+    assert_eq!(imports[0]["included"], true);
+}
+
+#[test]
+fn collect_tests_adds_related_tests() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::create_dir_all(root.join("tests")).unwrap();
+    std::fs::write(
+        root.join("src/pack.rs"),
+        "pub fn pack_items() -> usize {\n    1\n}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn packs() {\n        assert_eq!(super::pack_items(), 1);\n    }\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("tests/cli.rs"),
+        "#[test]\nfn cli_packs() {\n    assert_eq!(demo::pack_items(), 1);\n}\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("tests/other.rs"), "#[test]\nfn other() {}\n").unwrap();
+    let out_path = root.join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "src/pack.rs",
+            "--span",
+            "1:3",
+            "--tests",
+            "--root",
+            root.to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(root.join("out.manifest.json")).unwrap())
+            .unwrap();
+    let entries = manifest["entries"].as_array().unwrap();
+    let tests: Vec<(&str, u64)> = entries
+        .iter()
+        .filter(|e| e["reason"] == "test for src/pack.rs")
+        .map(|e| {
+            (
+                e["file_path"].as_str().unwrap(),
+                e["start_line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(tests.len(), 2);
+    assert!(tests.contains(&("tests/cli.rs", 1)));
+    assert!(tests.contains(&("src/pack.rs", 5)));
+    assert!(!entries.iter().any(|e| e["file_path"] == "tests/other.rs"));
+}
+
+fn setup_branch_repo() -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.email", "test@test.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    for name in ["a.rs", "b.rs"] {
+        std::fs::write(
+            root.join(name),
+            "fn caller() {\n    parse_model(\"x\");\n}\n",
+        )
+        .unwrap();
+    }
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+    std::fs::write(
+        root.join("b.rs"),
+        "fn caller() {\n    parse_model(\"y\");\n}\n",
+    )
+    .unwrap();
+    git(root, &["commit", "-am", "touch b"]);
+    dir
+}
+
+fn manifest_paths(dir: &std::path::Path) -> Vec<String> {
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("out.manifest.json")).unwrap())
+            .unwrap();
+    manifest["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["file_path"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn collect_diff_boosts_changed_sections() {
+    let dir = setup_branch_repo();
+    let root = dir.path();
+    // Outputs live outside the repo so later runs do not grep them.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.md");
+    let run = |extra: &[&str]| {
+        cmd()
+            .args(["collect", "parse_model", "--root", root.to_str().unwrap()])
+            .args(["--out", out_path.to_str().unwrap(), "--quiet"])
+            .args(extra)
+            .assert()
+            .success();
+        manifest_paths(out_dir.path())
+    };
+
+    assert_eq!(run(&["--diff", "HEAD~1..HEAD"]), vec!["b.rs", "a.rs"]);
+    assert_eq!(
+        run(&["--diff", "HEAD~1..HEAD", "--diff-only"]),
+        vec!["b.rs"]
+    );
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(output.contains("1 of 2 sections overlap HEAD~1..HEAD"));
+}
+
+#[test]
+fn collect_diff_only_requires_diff() {
+    let dir = setup_branch_repo();
+    cmd()
+        .args([
+            "collect",
+            "parse_model",
+            "--diff-only",
+            "--root",
+            dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--diff <RANGE>"));
+}
+
+#[test]
+fn collect_combines_modes_and_merges_overlaps() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    let mut lib: Vec<String> = (1..=30).map(|i| format!("// line {i}")).collect();
+    lib[9] = "pub struct Budget {".to_string();
+    lib[10] = "    pub tokens: usize, // needle".to_string();
+    lib[11] = "}".to_string();
+    lib[24] = "fn far() { needle() }".to_string();
+    std::fs::write(root.join("lib.rs"), lib.join("\n")).unwrap();
+    std::fs::write(root.join("notes.txt"), "just notes\n").unwrap();
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.md");
+
+    cmd()
+        .args([
+            "collect",
+            "--files",
+            "notes.txt",
+            "--grep",
+            "needle",
+            "--symbol",
+            "Budget",
+            "--root",
+            root.to_str().unwrap(),
+            "--out",
+            out_path.to_str().unwrap(),
+            "--quiet",
+        ])
+        .assert()
+        .success();
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(out_dir.path().join("out.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let mut sections: Vec<(String, u64, u64, String)> = manifest["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            (
+                e["file_path"].as_str().unwrap().to_string(),
+                e["start_line"].as_u64().unwrap(),
+                e["end_line"].as_u64().unwrap(),
+                e["reason"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    sections.sort();
+    assert_eq!(
+        sections,
+        vec![
+            (
+                "lib.rs".to_string(),
+                7,
+                14,
+                "definition of 'Budget'; grep match for 'needle'".to_string()
+            ),
+            (
+                "lib.rs".to_string(),
+                22,
+                28,
+                "grep match for 'needle'".to_string()
+            ),
+            ("notes.txt".to_string(), 1, 1, "explicit file".to_string()),
+        ]
+    );
+    let output = std::fs::read_to_string(&out_path).unwrap();
+    assert!(
+        output.contains("collected 1 file; grep 'needle': 2 matches in 1 file; symbol 'Budget'")
+    );
+}